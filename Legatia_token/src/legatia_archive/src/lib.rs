@@ -0,0 +1,262 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::{api, init, query, update};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use icrc_ledger_types::icrc::generic_value::ICRC3Value;
+use icrc_ledger_types::icrc3::blocks::{BlockWithId, GetBlocksRequest, GetBlocksResult};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+// Upper bound on blocks returned by a single icrc3_get_blocks call
+const MAX_BLOCKS_PER_REQUEST: u64 = 100;
+
+// Memory management
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+const CONFIG_MEM_ID: MemoryId = MemoryId::new(0);
+const BLOCKS_MEM_ID: MemoryId = MemoryId::new(1);
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static CONFIG: RefCell<StableCell<ArchiveConfig, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONFIG_MEM_ID)),
+            ArchiveConfig::default(),
+        ).expect("Failed to initialize archive config")
+    );
+
+    // Archived blocks, keyed by their index in the ledger
+    static BLOCKS: RefCell<StableBTreeMap<u64, StoredBlock, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(BLOCKS_MEM_ID)),
+        )
+    );
+}
+
+// Types
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ArchiveInitArgs {
+    pub ledger_id: Principal,
+    pub block_offset: u64,
+    pub max_blocks: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct ArchiveConfig {
+    ledger_id: Principal,
+    block_offset: u64, // Ledger index of the first block held here
+    max_blocks: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            ledger_id: Principal::anonymous(),
+            block_offset: 0,
+            max_blocks: 0,
+        }
+    }
+}
+
+struct StoredBlock(ICRC3Value);
+
+impl Storable for ArchiveConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(self).expect("Failed to encode archive config"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("Failed to decode archive config")
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Unbounded;
+}
+
+impl Storable for StoredBlock {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(&self.0).expect("Failed to encode block"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StoredBlock(candid::decode_one(&bytes).expect("Failed to decode block"))
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Unbounded;
+}
+
+// Helper functions
+fn config() -> ArchiveConfig {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn block_count() -> u64 {
+    BLOCKS.with(|b| b.borrow().len())
+}
+
+fn remaining() -> u64 {
+    config().max_blocks.saturating_sub(block_count())
+}
+
+// Blocks are stored contiguously after the ones already held. `start` is the ledger index
+// of the first block; blocks already held are a retried call and must match what is stored.
+fn append(start: u64, blocks: Vec<ICRC3Value>) -> Result<(), String> {
+    let next_index = config().block_offset + block_count();
+    if start < config().block_offset || start > next_index {
+        return Err(format!("Expected blocks starting at {}, got {}", next_index, start));
+    }
+
+    let already_held = ((next_index - start) as usize).min(blocks.len());
+    let (replayed, new) = blocks.split_at(already_held);
+    let matches = BLOCKS.with(|b| {
+        let stored = b.borrow();
+        replayed.iter().enumerate().all(|(i, block)| {
+            stored.get(&(start + i as u64)).is_some_and(|held| held.0 == *block)
+        })
+    });
+    if !matches {
+        return Err(format!("Blocks from {} differ from the ones already archived", start));
+    }
+
+    if new.len() as u64 > remaining() {
+        return Err("Archive is full".to_string());
+    }
+    BLOCKS.with(|b| {
+        let mut stored = b.borrow_mut();
+        for (i, block) in new.iter().enumerate() {
+            stored.insert(next_index + i as u64, StoredBlock(block.clone()));
+        }
+    });
+    Ok(())
+}
+
+fn get_blocks(requests: Vec<GetBlocksRequest>) -> GetBlocksResult {
+    let end_of_archive = config().block_offset + block_count();
+    let mut budget = MAX_BLOCKS_PER_REQUEST;
+    let mut blocks = Vec::new();
+
+    for request in requests {
+        let Ok((start, length)) = request.as_start_and_length() else {
+            continue;
+        };
+        let end = start.saturating_add(length.min(budget)).min(end_of_archive);
+        if start >= end {
+            continue;
+        }
+
+        BLOCKS.with(|b| {
+            for (id, block) in b.borrow().range(start..end) {
+                blocks.push(BlockWithId {
+                    id: Nat::from(id),
+                    block: block.0,
+                });
+            }
+        });
+        budget -= end - start;
+    }
+
+    GetBlocksResult {
+        log_length: Nat::from(end_of_archive),
+        blocks,
+        archived_blocks: Vec::new(),
+    }
+}
+
+// Canister lifecycle
+#[init]
+fn init(args: ArchiveInitArgs) {
+    let config = ArchiveConfig {
+        ledger_id: args.ledger_id,
+        block_offset: args.block_offset,
+        max_blocks: args.max_blocks,
+    };
+    if CONFIG.with(|c| c.borrow_mut().set(config)).is_err() {
+        ic_cdk::trap("Failed to store archive config");
+    }
+}
+
+// Ledger-only methods
+#[update]
+fn append_blocks(start: u64, blocks: Vec<ICRC3Value>) -> Result<(), String> {
+    if api::caller() != config().ledger_id {
+        return Err("Only the ledger can append blocks".to_string());
+    }
+
+    append(start, blocks)
+}
+
+// Query methods
+#[query]
+fn remaining_capacity() -> u64 {
+    remaining()
+}
+
+#[query]
+fn icrc3_get_blocks(requests: Vec<GetBlocksRequest>) -> GetBlocksResult {
+    get_blocks(requests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configure(block_offset: u64, max_blocks: u64) {
+        let config = ArchiveConfig {
+            ledger_id: Principal::anonymous(),
+            block_offset,
+            max_blocks,
+        };
+        CONFIG.with(|c| c.borrow_mut().set(config)).unwrap();
+    }
+
+    fn request(start: u64, length: u64) -> GetBlocksRequest {
+        GetBlocksRequest {
+            start: Nat::from(start),
+            length: Nat::from(length),
+        }
+    }
+
+    #[test]
+    fn test_blocks_keep_ledger_indices() {
+        configure(10, 5);
+        append(10, (0..3u64).map(|i| ICRC3Value::Nat(Nat::from(i))).collect()).unwrap();
+
+        let result = get_blocks(vec![request(0, 100)]);
+        assert_eq!(result.log_length, Nat::from(13u64));
+        let ids: Vec<Nat> = result.blocks.iter().map(|b| b.id.clone()).collect();
+        assert_eq!(ids, vec![Nat::from(10u64), Nat::from(11u64), Nat::from(12u64)]);
+
+        assert_eq!(get_blocks(vec![request(11, 1)]).blocks[0].block, ICRC3Value::Nat(Nat::from(1u64)));
+    }
+
+    #[test]
+    fn test_capacity_is_enforced() {
+        configure(0, 2);
+        assert!(append(0, vec![ICRC3Value::Text("a".to_string())]).is_ok());
+        assert_eq!(remaining(), 1);
+        assert!(append(1, vec![ICRC3Value::Text("b".to_string()), ICRC3Value::Text("c".to_string())]).is_err());
+        assert_eq!(block_count(), 1);
+    }
+
+    #[test]
+    fn test_retried_appends_are_idempotent() {
+        configure(10, 5);
+        let block = |i: u64| ICRC3Value::Nat(Nat::from(i));
+        append(10, vec![block(10), block(11)]).unwrap();
+
+        // The ledger never saw the reply and sends the same batch again
+        append(10, vec![block(10), block(11)]).unwrap();
+        assert_eq!(block_count(), 2);
+
+        // A retry overlapping the held blocks only stores the new tail
+        append(11, vec![block(11), block(12)]).unwrap();
+        assert_eq!(block_count(), 3);
+        assert_eq!(get_blocks(vec![request(12, 1)]).blocks[0].block, block(12));
+
+        // Gaps, blocks before the offset and replays that differ are rejected
+        assert!(append(14, vec![block(14)]).is_err());
+        assert!(append(9, vec![block(9)]).is_err());
+        assert!(append(12, vec![block(99)]).is_err());
+        assert_eq!(block_count(), 3);
+    }
+}
@@ -0,0 +1,680 @@
+// Token-weighted platform governance for the LGT ledger
+//
+// A voter's power is their staking voting power on the ledger
+// (get_voting_power). Stakes only carry power while they stay locked for at
+// least MAX_VOTING_PERIOD_DAYS, so the same tokens cannot vote on a proposal,
+// unstake and vote again from another principal.
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::{api, init, post_upgrade, query, update};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MAX_VOTING_PERIOD_DAYS: u32 = 30; // The ledger's minimum lock for voting power
+const MAX_TITLE_LENGTH: usize = 100;
+const MAX_SUMMARY_LENGTH: usize = 5_000;
+const PROPOSAL_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// An execution still marked as running after this long trapped before recording its result
+const EXECUTION_TIMEOUT_NANOS: u64 = NANOS_PER_DAY;
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+// Memory management
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+const CONFIG_MEM_ID: MemoryId = MemoryId::new(0);
+const PROPOSALS_MEM_ID: MemoryId = MemoryId::new(1);
+const UNSETTLED_MEM_ID: MemoryId = MemoryId::new(2);
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static CONFIG: RefCell<StableCell<GovernanceConfig, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONFIG_MEM_ID)),
+            GovernanceConfig::default(),
+        ).expect("Failed to initialize governance config")
+    );
+
+    static PROPOSALS: RefCell<StableBTreeMap<u64, Proposal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PROPOSALS_MEM_ID)),
+        )
+    );
+
+    // Proposals that are open, adopted or executing, keyed by ID, with their voting end
+    static UNSETTLED: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(UNSETTLED_MEM_ID)),
+        )
+    );
+
+    // Principals with a ledger call in flight; one operation per principal at a time
+    static BUSY: RefCell<BTreeSet<Principal>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+// Ledger types
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct TransferArgs {
+    from_subaccount: Option<Vec<u8>>,
+    to: Account,
+    amount: u128,
+    fee: Option<u128>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum LedgerError {
+    BadFee { expected_fee: u128 },
+    BadBurn { min_burn_amount: u128 },
+    InsufficientFunds { balance: u128 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u64 },
+    TemporarilyUnavailable,
+    GenericError { error_code: u128, message: String },
+    InvalidAmount,
+    MemoTooLarge,
+    SupplyExceeded,
+    Overflow,
+    Unauthorized,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct EmissionSchedule {
+    pub epoch_days: u32,
+    pub initial_epoch_budget: u128,
+    pub halving_interval_epochs: u32,
+    pub daily_account_cap: u128,
+}
+
+// Governance types
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GovernanceInitArgs {
+    pub ledger_id: Principal,
+    pub voting_period_days: u32,
+    pub quorum: u128,             // Minimum voting power cast for a proposal to be adopted
+    pub proposal_threshold: u128, // Minimum voting power needed to submit a proposal
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct GovernanceConfig {
+    ledger_id: Principal,
+    voting_period_days: u32,
+    quorum: u128,
+    proposal_threshold: u128,
+    next_proposal_id: u64,
+}
+
+impl Default for GovernanceConfig {
+    fn default() -> Self {
+        Self {
+            ledger_id: Principal::anonymous(),
+            voting_period_days: 7,
+            quorum: 0,
+            proposal_threshold: 0,
+            next_proposal_id: 0,
+        }
+    }
+}
+
+// What an adopted proposal does on the ledger
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ProposalAction {
+    SetFee { fee: u128 },
+    SetMaxRewardPerCall { amount: u128 },
+    SetRewardDistributor { distributor: Principal, enabled: bool },
+    SetEmissionSchedule(EmissionSchedule),
+    // Paid from the governance canister's default account on the ledger
+    TreasuryTransfer { to: Account, amount: u128, memo: Option<Vec<u8>> },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum ProposalStatus {
+    Open,
+    Rejected,
+    Adopted,
+    Executing,
+    Executed,
+    Failed(String),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Ballot {
+    pub voter: Principal,
+    pub approve: bool,
+    pub voting_power: u128,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Principal,
+    pub title: String,
+    pub summary: String,
+    pub action: ProposalAction,
+    pub created_at: u64,
+    pub voting_ends: u64,
+    pub ballots: Vec<Ballot>,
+    pub yes: u128,
+    pub no: u128,
+    pub status: ProposalStatus,
+    pub executed_at: Option<u64>,
+    pub execution_started_at: Option<u64>,
+}
+
+// A proposal without its ballots, for listings; get_proposal has the ballots
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ProposalSummary {
+    pub id: u64,
+    pub proposer: Principal,
+    pub title: String,
+    pub action: ProposalAction,
+    pub created_at: u64,
+    pub voting_ends: u64,
+    pub ballot_count: u64,
+    pub yes: u128,
+    pub no: u128,
+    pub status: ProposalStatus,
+    pub executed_at: Option<u64>,
+}
+
+// Proposals newest first, starting below `before` when it is set
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct ListProposalsArgs {
+    pub before: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ProposalPage {
+    pub proposals: Vec<ProposalSummary>,
+    pub next_before: Option<u64>, // None on the last page
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CreateProposalArgs {
+    pub title: String,
+    pub summary: String,
+    pub action: ProposalAction,
+}
+
+impl Storable for GovernanceConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(self).expect("Failed to encode governance config"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("Failed to decode governance config")
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Unbounded;
+}
+
+impl Storable for Proposal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(self).expect("Failed to encode proposal"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("Failed to decode proposal")
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Unbounded;
+}
+
+// Releases a principal's busy flag when the operation ends, including on early return
+struct CallerGuard(Principal);
+
+impl CallerGuard {
+    fn new(principal: Principal) -> Result<Self, String> {
+        BUSY.with(|busy| {
+            if !busy.borrow_mut().insert(principal) {
+                return Err("Another operation for this principal is in progress".to_string());
+            }
+            Ok(CallerGuard(principal))
+        })
+    }
+}
+
+impl Drop for CallerGuard {
+    fn drop(&mut self) {
+        BUSY.with(|busy| busy.borrow_mut().remove(&self.0));
+    }
+}
+
+// Helper functions
+fn config() -> GovernanceConfig {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn update_config(f: impl FnOnce(&mut GovernanceConfig)) {
+    CONFIG.with(|c| {
+        let mut cell = c.borrow_mut();
+        let mut config = cell.get().clone();
+        f(&mut config);
+        if cell.set(config).is_err() {
+            ic_cdk::trap("Failed to persist governance config");
+        }
+    });
+}
+
+fn get_proposal_internal(id: u64) -> Option<Proposal> {
+    PROPOSALS.with(|p| p.borrow().get(&id))
+}
+
+// Keeps UNSETTLED in step so the timer never has to scan every proposal
+fn save_proposal(proposal: &Proposal) {
+    PROPOSALS.with(|p| p.borrow_mut().insert(proposal.id, proposal.clone()));
+    UNSETTLED.with(|u| {
+        let mut unsettled = u.borrow_mut();
+        match proposal.status {
+            ProposalStatus::Open | ProposalStatus::Adopted | ProposalStatus::Executing => {
+                unsettled.insert(proposal.id, proposal.voting_ends);
+            }
+            _ => {
+                unsettled.remove(&proposal.id);
+            }
+        }
+    });
+}
+
+fn validate_proposal(args: &CreateProposalArgs) -> Result<(), String> {
+    if args.title.trim().is_empty() || args.title.len() > MAX_TITLE_LENGTH {
+        return Err(format!("Title must be between 1 and {} characters", MAX_TITLE_LENGTH));
+    }
+    if args.summary.len() > MAX_SUMMARY_LENGTH {
+        return Err(format!("Summary cannot exceed {} characters", MAX_SUMMARY_LENGTH));
+    }
+
+    match &args.action {
+        ProposalAction::SetMaxRewardPerCall { amount: 0 } | ProposalAction::TreasuryTransfer { amount: 0, .. } => {
+            Err("Amount must be greater than zero".to_string())
+        }
+        ProposalAction::TreasuryTransfer { to, .. } if to.owner == Principal::anonymous() => {
+            Err("Treasury transfers need a recipient".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Adopted when enough LGT took part and yes outweighs no
+fn decide(yes: u128, no: u128, quorum: u128) -> ProposalStatus {
+    if yes.saturating_add(no) >= quorum && yes > no {
+        ProposalStatus::Adopted
+    } else {
+        ProposalStatus::Rejected
+    }
+}
+
+fn record_ballot(proposal: &mut Proposal, ballot: Ballot) -> Result<(), String> {
+    if proposal.ballots.iter().any(|b| b.voter == ballot.voter) {
+        return Err("You have already voted on this proposal".to_string());
+    }
+
+    if ballot.approve {
+        proposal.yes = proposal.yes.saturating_add(ballot.voting_power);
+    } else {
+        proposal.no = proposal.no.saturating_add(ballot.voting_power);
+    }
+    proposal.ballots.push(ballot);
+    Ok(())
+}
+
+async fn voting_power(voter: Principal) -> Result<u128, String> {
+    let (power,): (u128,) = ic_cdk::call(config().ledger_id, "get_voting_power", (voter,))
+        .await
+        .map_err(|(code, message)| format!("Failed to read voting power ({:?}): {}", code, message))?;
+    Ok(power)
+}
+
+async fn call_ledger<A: candid::utils::ArgumentEncoder>(method: &str, args: A) -> Result<String, String> {
+    let result: Result<(Result<(), LedgerError>,), _> = ic_cdk::call(config().ledger_id, method, args).await;
+    match result {
+        Ok((Ok(()),)) => Ok(format!("{} succeeded", method)),
+        Ok((Err(error),)) => Err(format!("Ledger rejected {}: {:?}", method, error)),
+        Err((code, message)) => Err(format!("Call to {} failed ({:?}): {}", method, code, message)),
+    }
+}
+
+async fn execute(action: &ProposalAction) -> Result<String, String> {
+    match action {
+        ProposalAction::SetFee { fee } => call_ledger("set_fee", (*fee,)).await,
+        ProposalAction::SetMaxRewardPerCall { amount } => call_ledger("set_max_reward_per_call", (*amount,)).await,
+        ProposalAction::SetRewardDistributor { distributor, enabled } => {
+            call_ledger("set_reward_distributor", (*distributor, *enabled)).await
+        }
+        ProposalAction::SetEmissionSchedule(schedule) => {
+            call_ledger("set_emission_schedule", (schedule.clone(),)).await
+        }
+        ProposalAction::TreasuryTransfer { to, amount, memo } => {
+            let args = TransferArgs {
+                from_subaccount: None,
+                to: to.clone(),
+                amount: *amount,
+                fee: None,
+                memo: memo.clone(),
+                created_at_time: None,
+            };
+            let result: Result<(Result<u64, LedgerError>,), _> =
+                ic_cdk::call(config().ledger_id, "icrc1_transfer", (args,)).await;
+            match result {
+                Ok((Ok(block),)) => Ok(format!("Treasury transfer recorded in block {}", block)),
+                Ok((Err(error),)) => Err(format!("Ledger rejected treasury transfer: {:?}", error)),
+                Err((code, message)) => Err(format!("Treasury transfer failed ({:?}): {}", code, message)),
+            }
+        }
+    }
+}
+
+// Closes ended votes and gives up on executions that trapped; returns the proposals to execute
+fn settle_due_proposals(now: u64) -> Vec<Proposal> {
+    let quorum = config().quorum;
+    let due: Vec<Proposal> = UNSETTLED.with(|u| {
+        u.borrow()
+            .iter()
+            .filter(|(_, voting_ends)| *voting_ends <= now)
+            .filter_map(|(id, _)| get_proposal_internal(id))
+            .collect()
+    });
+
+    let mut adopted = Vec::new();
+    for mut proposal in due {
+        match proposal.status {
+            ProposalStatus::Open => {
+                proposal.status = decide(proposal.yes, proposal.no, quorum);
+                save_proposal(&proposal);
+            }
+            // The ledger call may or may not have gone through, so it is not retried
+            ProposalStatus::Executing
+                if proposal.execution_started_at.is_some_and(|started| now.saturating_sub(started) >= EXECUTION_TIMEOUT_NANOS) =>
+            {
+                proposal.status = ProposalStatus::Failed("Execution did not finish; check the ledger before resubmitting".to_string());
+                save_proposal(&proposal);
+            }
+            _ => {}
+        }
+        if proposal.status == ProposalStatus::Adopted {
+            adopted.push(proposal);
+        }
+    }
+    adopted
+}
+
+// Close proposals whose voting period ended and execute the adopted ones
+async fn process_proposals() {
+    for mut proposal in settle_due_proposals(api::time()) {
+        // Mark before awaiting so an overlapping run cannot execute it twice
+        proposal.status = ProposalStatus::Executing;
+        proposal.execution_started_at = Some(api::time());
+        save_proposal(&proposal);
+
+        let result = execute(&proposal.action).await;
+        proposal.status = match result {
+            Ok(_) => ProposalStatus::Executed,
+            Err(error) => ProposalStatus::Failed(error),
+        };
+        proposal.executed_at = Some(api::time());
+        save_proposal(&proposal);
+    }
+}
+
+fn start_proposal_timer() {
+    ic_cdk_timers::set_timer_interval(PROPOSAL_CHECK_INTERVAL, || ic_cdk::spawn(process_proposals()));
+}
+
+// Canister lifecycle
+#[init]
+fn init(args: GovernanceInitArgs) {
+    if args.voting_period_days == 0 || args.voting_period_days > MAX_VOTING_PERIOD_DAYS {
+        ic_cdk::trap(&format!("Voting period must be between 1 and {} days", MAX_VOTING_PERIOD_DAYS));
+    }
+
+    update_config(|config| {
+        config.ledger_id = args.ledger_id;
+        config.voting_period_days = args.voting_period_days;
+        config.quorum = args.quorum;
+        config.proposal_threshold = args.proposal_threshold;
+    });
+    start_proposal_timer();
+}
+
+#[post_upgrade]
+fn post_upgrade() {
+    // Timers do not survive upgrades
+    start_proposal_timer();
+}
+
+// Update methods
+#[update]
+async fn create_proposal(args: CreateProposalArgs) -> Result<u64, String> {
+    let caller = api::caller();
+    validate_proposal(&args)?;
+    let _guard = CallerGuard::new(caller)?;
+
+    let voting_power = voting_power(caller).await?;
+    let config = config();
+    if voting_power == 0 || voting_power < config.proposal_threshold {
+        return Err(format!("At least {} voting power is needed to submit a proposal", config.proposal_threshold.max(1)));
+    }
+
+    let now = api::time();
+    let id = config.next_proposal_id;
+    update_config(|config| config.next_proposal_id += 1);
+
+    let mut proposal = Proposal {
+        id,
+        proposer: caller,
+        title: args.title,
+        summary: args.summary,
+        action: args.action,
+        created_at: now,
+        voting_ends: now + config.voting_period_days as u64 * NANOS_PER_DAY,
+        ballots: Vec::new(),
+        yes: 0,
+        no: 0,
+        status: ProposalStatus::Open,
+        executed_at: None,
+        execution_started_at: None,
+    };
+
+    // The proposer votes in favour with their full voting power
+    record_ballot(&mut proposal, Ballot { voter: caller, approve: true, voting_power })?;
+    save_proposal(&proposal);
+    Ok(id)
+}
+
+#[update]
+async fn vote(proposal_id: u64, approve: bool) -> Result<Proposal, String> {
+    let caller = api::caller();
+    let _guard = CallerGuard::new(caller)?;
+
+    let voting_power = voting_power(caller).await?;
+    if voting_power == 0 {
+        return Err("Stake LGT on the ledger to vote".to_string());
+    }
+
+    // Re-read after the ledger call; the proposal may have closed meanwhile
+    let mut proposal = get_proposal_internal(proposal_id).ok_or("Proposal not found".to_string())?;
+    if proposal.status != ProposalStatus::Open || api::time() >= proposal.voting_ends {
+        return Err("Voting on this proposal has closed".to_string());
+    }
+
+    record_ballot(&mut proposal, Ballot { voter: caller, approve, voting_power })?;
+    save_proposal(&proposal);
+    Ok(proposal)
+}
+
+// Query methods
+#[query]
+fn treasury_account() -> Account {
+    Account {
+        owner: api::id(),
+        subaccount: None,
+    }
+}
+
+#[query]
+fn get_proposal(id: u64) -> Option<Proposal> {
+    get_proposal_internal(id)
+}
+
+#[query]
+fn list_proposals(args: ListProposalsArgs) -> ProposalPage {
+    list_proposals_internal(&args)
+}
+
+fn list_proposals_internal(args: &ListProposalsArgs) -> ProposalPage {
+    let limit = args.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize;
+    let mut proposals: Vec<ProposalSummary> = PROPOSALS.with(|p| {
+        p.borrow()
+            .range(..args.before.unwrap_or(u64::MAX))
+            .rev()
+            .take(limit + 1)
+            .map(|(_, proposal)| summarize(&proposal))
+            .collect()
+    });
+    let next_before = if proposals.len() > limit {
+        proposals.truncate(limit);
+        proposals.last().map(|proposal| proposal.id)
+    } else {
+        None
+    };
+    ProposalPage { proposals, next_before }
+}
+
+fn summarize(proposal: &Proposal) -> ProposalSummary {
+    ProposalSummary {
+        id: proposal.id,
+        proposer: proposal.proposer,
+        title: proposal.title.clone(),
+        action: proposal.action.clone(),
+        created_at: proposal.created_at,
+        voting_ends: proposal.voting_ends,
+        ballot_count: proposal.ballots.len() as u64,
+        yes: proposal.yes,
+        no: proposal.no,
+        status: proposal.status.clone(),
+        executed_at: proposal.executed_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposal() -> Proposal {
+        Proposal {
+            id: 0,
+            proposer: Principal::anonymous(),
+            title: "Lower the fee".to_string(),
+            summary: String::new(),
+            action: ProposalAction::SetFee { fee: 100 },
+            created_at: 0,
+            voting_ends: 10,
+            ballots: Vec::new(),
+            yes: 0,
+            no: 0,
+            status: ProposalStatus::Open,
+            executed_at: None,
+            execution_started_at: None,
+        }
+    }
+
+    #[test]
+    fn test_decide() {
+        assert_eq!(decide(600, 400, 1_000), ProposalStatus::Adopted);
+        assert_eq!(decide(600, 300, 1_000), ProposalStatus::Rejected);
+        assert_eq!(decide(500, 500, 1_000), ProposalStatus::Rejected);
+    }
+
+    #[test]
+    fn test_ballots_are_weighted_and_unique() {
+        let mut proposal = proposal();
+        let voter = Principal::from_slice(&[1]);
+
+        record_ballot(&mut proposal, Ballot { voter, approve: true, voting_power: 700 }).unwrap();
+        record_ballot(&mut proposal, Ballot { voter: Principal::from_slice(&[2]), approve: false, voting_power: 200 }).unwrap();
+        assert!(record_ballot(&mut proposal, Ballot { voter, approve: false, voting_power: 700 }).is_err());
+        assert_eq!((proposal.yes, proposal.no), (700, 200));
+    }
+
+    #[test]
+    fn test_validate_proposal() {
+        let args = |action| CreateProposalArgs {
+            title: "Treasury grant".to_string(),
+            summary: String::new(),
+            action,
+        };
+        let to = Account { owner: Principal::from_slice(&[1]), subaccount: None };
+
+        assert!(validate_proposal(&args(ProposalAction::SetFee { fee: 0 })).is_ok());
+        assert!(validate_proposal(&args(ProposalAction::SetMaxRewardPerCall { amount: 0 })).is_err());
+        assert!(validate_proposal(&args(ProposalAction::TreasuryTransfer { to, amount: 0, memo: None })).is_err());
+    }
+
+    #[test]
+    fn test_settling_uses_the_unsettled_index() {
+        let mut adopted = Proposal { id: 1, yes: 10, ..proposal() };
+        let rejected = Proposal { id: 2, no: 10, ..proposal() };
+        let later = Proposal { id: 3, voting_ends: 100, ..proposal() };
+        for p in [&adopted, &rejected, &later] {
+            save_proposal(p);
+        }
+
+        let due = settle_due_proposals(10);
+        assert_eq!(due.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(get_proposal_internal(2).unwrap().status, ProposalStatus::Rejected);
+        assert_eq!(UNSETTLED.with(|u| u.borrow().iter().map(|(id, _)| id).collect::<Vec<_>>()), vec![1, 3]);
+
+        // An execution that trapped after being marked stays put until it times out
+        adopted.status = ProposalStatus::Executing;
+        adopted.execution_started_at = Some(10);
+        save_proposal(&adopted);
+        assert!(settle_due_proposals(10 + EXECUTION_TIMEOUT_NANOS - 1).is_empty());
+        assert_eq!(get_proposal_internal(1).unwrap().status, ProposalStatus::Executing);
+
+        assert!(settle_due_proposals(10 + EXECUTION_TIMEOUT_NANOS).is_empty());
+        assert!(matches!(get_proposal_internal(1).unwrap().status, ProposalStatus::Failed(_)));
+        assert_eq!(get_proposal_internal(3).unwrap().status, ProposalStatus::Rejected);
+        assert!(UNSETTLED.with(|u| u.borrow().is_empty()));
+    }
+
+    #[test]
+    fn test_proposals_are_listed_in_pages_without_ballots() {
+        for id in 0..5 {
+            let ballots = vec![Ballot { voter: Principal::from_slice(&[1]), approve: true, voting_power: 10 }];
+            save_proposal(&Proposal { id, ballots, ..proposal() });
+        }
+        let page = |before| list_proposals_internal(&ListProposalsArgs { before, limit: Some(2) });
+
+        let first = page(None);
+        assert_eq!(first.proposals.iter().map(|p| p.id).collect::<Vec<_>>(), vec![4, 3]);
+        assert_eq!(first.proposals[0].ballot_count, 1);
+        let second = page(first.next_before);
+        assert_eq!(second.proposals.iter().map(|p| p.id).collect::<Vec<_>>(), vec![2, 1]);
+        let last = page(second.next_before);
+        assert_eq!(last.proposals.iter().map(|p| p.id).collect::<Vec<_>>(), vec![0]);
+        assert_eq!(last.next_before, None);
+    }
+
+    #[test]
+    fn test_caller_guard() {
+        let principal = Principal::from_slice(&[1]);
+        let guard = CallerGuard::new(principal).unwrap();
+        assert!(CallerGuard::new(principal).is_err());
+        drop(guard);
+        assert!(CallerGuard::new(principal).is_ok());
+    }
+}
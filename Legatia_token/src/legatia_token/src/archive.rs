@@ -0,0 +1,499 @@
+// Spills old blocks from TRANSACTIONS into spawned archive canisters
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api::management_canister::main::{
+    create_canister, install_code, CanisterInstallMode, CanisterSettings, CreateCanisterArgument,
+    InstallCodeArgument,
+};
+use ic_cdk::{api, query, update};
+use icrc_ledger_types::icrc::generic_value::ICRC3Value;
+use icrc_ledger_types::icrc3::archive::{GetArchivesArgs, ICRC3ArchiveInfo};
+use serde_bytes::ByteBuf;
+use std::cell::Cell;
+use std::time::Duration;
+
+use crate::{icrc3, multisig, token_data, TransferError, ARCHIVE_STATE, ARCHIVE_WASM, BLOCK_HASHES, TRANSACTIONS};
+
+// How often the ledger checks whether it should archive
+const ARCHIVE_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// Keeps a single append_blocks call well under the inter-canister message limit
+const MAX_BLOCKS_PER_ARCHIVE_CALL: u64 = 1000;
+// A run still marked as active after this long is presumed dead and stops blocking new runs
+const ARCHIVING_TIMEOUT: u64 = 60 * 60 * 1_000_000_000; // 1 hour in nanoseconds
+
+thread_local! {
+    // Start time of the running archiving run; deliberately not persisted
+    static ARCHIVING_SINCE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// Held for the length of an archiving run. Dropping it ends the run on every path,
+// including a trap after an await, where the task's future is dropped during cleanup;
+// the timeout covers anything that slips past that.
+struct ArchivingGuard {
+    started_at: u64,
+}
+
+impl ArchivingGuard {
+    fn acquire(now: u64) -> Option<Self> {
+        let running = ARCHIVING_SINCE.with(|s| s.get());
+        if running.is_some_and(|since| now.saturating_sub(since) < ARCHIVING_TIMEOUT) {
+            return None;
+        }
+        ARCHIVING_SINCE.with(|s| s.set(Some(now)));
+        Some(ArchivingGuard { started_at: now })
+    }
+}
+
+impl Drop for ArchivingGuard {
+    fn drop(&mut self) {
+        // A run that timed out must not end the run that replaced it
+        ARCHIVING_SINCE.with(|s| {
+            if s.get() == Some(self.started_at) {
+                s.set(None);
+            }
+        });
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ArchiveOptions {
+    pub trigger_threshold: u64,     // Local block count that triggers archiving
+    pub num_blocks_to_archive: u64, // Blocks moved per archiving run
+    pub max_blocks_per_archive: u64,
+    pub cycles_for_archive_creation: u64,
+}
+
+// Init arguments of the legatia_archive canister
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct ArchiveInitArgs {
+    ledger_id: Principal,
+    block_offset: u64,
+    max_blocks: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ArchiveRecord {
+    pub canister_id: Principal,
+    pub start: u64,
+    pub end: u64, // Exclusive
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub(crate) struct ArchiveState {
+    pub options: Option<ArchiveOptions>,
+    pub archives: Vec<ArchiveRecord>,
+    // Created but not yet installed; reused by the next attempt instead of creating another
+    pub pending_canister: Option<Principal>,
+    // SHA-256 of the archive wasm the signers approved; only checked once multisig is enabled
+    pub approved_wasm_hash: Option<ByteBuf>,
+}
+
+fn archive_state() -> ArchiveState {
+    ARCHIVE_STATE.with(|s| s.borrow().get().clone())
+}
+
+fn update_archive_state(f: impl FnOnce(&mut ArchiveState)) {
+    ARCHIVE_STATE.with(|s| {
+        let mut cell = s.borrow_mut();
+        let mut state = cell.get().clone();
+        f(&mut state);
+        if cell.set(state).is_err() {
+            ic_cdk::trap("Failed to persist archive state");
+        }
+    });
+}
+
+pub(crate) fn validate_archive_options(options: &ArchiveOptions) -> Result<(), TransferError> {
+    if options.num_blocks_to_archive == 0 || options.max_blocks_per_archive == 0 {
+        return Err(TransferError::GenericError {
+            error_code: 1009,
+            message: "Archive block counts must be positive".to_string(),
+        });
+    }
+
+    if options.trigger_threshold < options.num_blocks_to_archive {
+        return Err(TransferError::GenericError {
+            error_code: 1009,
+            message: "Trigger threshold must be at least the number of blocks to archive".to_string(),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn set_options(options: Option<ArchiveOptions>) {
+    update_archive_state(|state| state.options = options);
+}
+
+pub(crate) fn approve_wasm(sha256: ByteBuf) {
+    update_archive_state(|state| state.approved_wasm_hash = Some(sha256));
+}
+
+// The representation-independent hash of a blob is the SHA-256 of its bytes
+fn wasm_hash(wasm: &[u8]) -> [u8; 32] {
+    ICRC3Value::Blob(ByteBuf::from(wasm.to_vec())).hash()
+}
+
+// With a signer set, only a wasm the signers approved may be installed
+fn check_wasm_approved(wasm: &[u8]) -> Result<(), String> {
+    if !multisig::is_enabled() {
+        return Ok(());
+    }
+    match archive_state().approved_wasm_hash {
+        Some(approved) if approved.as_slice() == wasm_hash(wasm) => Ok(()),
+        _ => Err("Archive wasm has not been approved by the signers".to_string()),
+    }
+}
+
+// Splits [start, end) into the parts held by each archive
+pub(crate) fn archived_ranges(start: u64, end: u64) -> Vec<(Principal, u64, u64)> {
+    archive_state()
+        .archives
+        .iter()
+        .filter_map(|archive| {
+            let from = start.max(archive.start);
+            let to = end.min(archive.end);
+            (from < to).then_some((archive.canister_id, from, to))
+        })
+        .collect()
+}
+
+// The oldest contiguous run of local blocks, encoded for the archive
+pub(crate) fn blocks_to_archive(count: u64) -> Vec<(u64, ICRC3Value)> {
+    let mut blocks: Vec<(u64, ICRC3Value)> = Vec::new();
+    TRANSACTIONS.with(|t| {
+        for (id, tx) in t.borrow().iter().take(count as usize) {
+            if blocks.last().is_some_and(|(last, _)| *last + 1 != id) {
+                break;
+            }
+            blocks.push((id, icrc3::transaction_to_block(&tx, icrc3::parent_hash(id))));
+        }
+    });
+    blocks
+}
+
+// Drop archived blocks locally, keeping the last hash so the chain stays linked
+pub(crate) fn remove_archived_blocks(canister_id: Principal, first: u64, last: u64) {
+    TRANSACTIONS.with(|t| {
+        let mut transactions = t.borrow_mut();
+        for id in first..=last {
+            transactions.remove(&id);
+        }
+    });
+    BLOCK_HASHES.with(|h| {
+        let mut hashes = h.borrow_mut();
+        let stale: Vec<u64> = hashes.range(..last).map(|(id, _)| id).collect();
+        for id in stale {
+            hashes.remove(&id);
+        }
+    });
+    update_archive_state(|state| {
+        if let Some(archive) = state.archives.iter_mut().find(|a| a.canister_id == canister_id) {
+            archive.end = last + 1;
+        }
+    });
+}
+
+pub(crate) fn start_archive_timer() {
+    ic_cdk_timers::set_timer_interval(ARCHIVE_CHECK_INTERVAL, || ic_cdk::spawn(archive_blocks()));
+}
+
+async fn archive_blocks() {
+    let Some(options) = archive_state().options else {
+        return;
+    };
+    if TRANSACTIONS.with(|t| t.borrow().len()) < options.trigger_threshold {
+        return;
+    }
+    // Blocks are only archived once they have been hashed onto the chain
+    if icrc3::backfill_pending() {
+        return;
+    }
+
+    let Some(_guard) = ArchivingGuard::acquire(api::time()) else {
+        return;
+    };
+    if let Err(e) = archive_oldest_blocks(&options).await {
+        ic_cdk::println!("Archiving failed: {}", e);
+    }
+}
+
+async fn archive_oldest_blocks(options: &ArchiveOptions) -> Result<(), String> {
+    let first_local = TRANSACTIONS.with(|t| t.borrow().first_key_value().map(|(id, _)| id));
+    let Some(first_local) = first_local else {
+        return Ok(());
+    };
+
+    // Reuse the newest archive while it has room, otherwise spawn a new one
+    let current = archive_state()
+        .archives
+        .last()
+        .filter(|a| a.end == first_local && a.end - a.start < options.max_blocks_per_archive)
+        .cloned();
+    let archive = match current {
+        Some(archive) => archive,
+        None => spawn_archive(options, first_local).await?,
+    };
+
+    let capacity = options.max_blocks_per_archive - (archive.end - archive.start);
+    let count = options.num_blocks_to_archive.min(capacity).min(MAX_BLOCKS_PER_ARCHIVE_CALL);
+    let blocks = blocks_to_archive(count);
+    let (Some((first, _)), Some((last, _))) = (blocks.first(), blocks.last()) else {
+        return Ok(());
+    };
+    let (first, last) = (*first, *last);
+
+    let values: Vec<ICRC3Value> = blocks.into_iter().map(|(_, block)| block).collect();
+    // The start index lets the archive recognise a batch it already stored when a reply was lost
+    let (result,): (Result<(), String>,) = ic_cdk::call(archive.canister_id, "append_blocks", (first, values))
+        .await
+        .map_err(|(code, msg)| format!("append_blocks rejected ({:?}): {}", code, msg))?;
+    result?;
+
+    remove_archived_blocks(archive.canister_id, first, last);
+    Ok(())
+}
+
+async fn spawn_archive(options: &ArchiveOptions, block_offset: u64) -> Result<ArchiveRecord, String> {
+    let wasm_module = ARCHIVE_WASM.with(|w| w.borrow().get().clone());
+    if wasm_module.is_empty() {
+        return Err("Archive wasm has not been uploaded".to_string());
+    }
+    check_wasm_approved(&wasm_module)?;
+
+    // A canister left over from a failed install is reinstalled rather than leaked
+    let (canister_id, mode) = match archive_state().pending_canister {
+        Some(canister_id) => (canister_id, CanisterInstallMode::Reinstall),
+        None => {
+            let settings = CanisterSettings {
+                controllers: Some(vec![api::id(), token_data().admin]),
+                ..Default::default()
+            };
+            let (record,) = create_canister(
+                CreateCanisterArgument { settings: Some(settings) },
+                options.cycles_for_archive_creation as u128,
+            )
+            .await
+            .map_err(|(code, msg)| format!("create_canister rejected ({:?}): {}", code, msg))?;
+            update_archive_state(|state| state.pending_canister = Some(record.canister_id));
+            (record.canister_id, CanisterInstallMode::Install)
+        }
+    };
+
+    let arg = candid::encode_one(ArchiveInitArgs {
+        ledger_id: api::id(),
+        block_offset,
+        max_blocks: options.max_blocks_per_archive,
+    })
+    .map_err(|e| format!("Failed to encode archive init args: {}", e))?;
+
+    install_code(InstallCodeArgument {
+        mode,
+        canister_id,
+        wasm_module,
+        arg,
+    })
+    .await
+    .map_err(|(code, msg)| format!("install_code rejected ({:?}): {}", code, msg))?;
+
+    let archive = ArchiveRecord {
+        canister_id,
+        start: block_offset,
+        end: block_offset,
+    };
+    update_archive_state(|state| {
+        state.archives.push(archive.clone());
+        state.pending_canister = None;
+    });
+    Ok(archive)
+}
+
+// Administrative Methods
+#[update]
+fn set_archive_options(options: ArchiveOptions) -> Result<(), TransferError> {
+    if api::caller() != token_data().admin {
+        return Err(TransferError::Unauthorized);
+    }
+    // With a signer set, options change through propose_operation instead
+    multisig::require_disabled()?;
+
+    validate_archive_options(&options)?;
+    set_options(Some(options));
+    Ok(())
+}
+
+// With a signer set the upload is only staged: archives are not spawned from it until
+// the signers approve its SHA-256 through propose_operation
+#[update]
+fn upload_archive_wasm(wasm: Vec<u8>) -> Result<(), TransferError> {
+    if api::caller() != token_data().admin {
+        return Err(TransferError::Unauthorized);
+    }
+
+    // Accept raw or gzipped wasm modules
+    if !wasm.starts_with(b"\0asm") && !wasm.starts_with(&[0x1f, 0x8b]) {
+        return Err(TransferError::GenericError {
+            error_code: 1010,
+            message: "Not a wasm module".to_string(),
+        });
+    }
+
+    ARCHIVE_WASM.with(|w| w.borrow_mut().set(wasm)).map_err(|_| TransferError::GenericError {
+        error_code: 1010,
+        message: "Failed to store archive wasm".to_string(),
+    })?;
+    Ok(())
+}
+
+#[query]
+fn icrc3_get_archives(args: GetArchivesArgs) -> Vec<ICRC3ArchiveInfo> {
+    let archives = archive_state().archives;
+    let skip = args
+        .from
+        .and_then(|from| archives.iter().position(|a| a.canister_id == from).map(|i| i + 1))
+        .unwrap_or(0);
+
+    archives
+        .into_iter()
+        .skip(skip)
+        .filter(|a| a.end > a.start)
+        .map(|a| ICRC3ArchiveInfo {
+            canister_id: a.canister_id,
+            start: Nat::from(a.start),
+            end: Nat::from(a.end - 1),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Account, Transaction, TransactionType, TRANSACTION_COUNTER};
+    use icrc_ledger_types::icrc3::blocks::GetBlocksRequest;
+
+    fn store_transactions(ids: std::ops::Range<u64>) {
+        let account = Account {
+            owner: Principal::from_slice(&[1]),
+            subaccount: None,
+        };
+        for id in ids {
+            let tx = Transaction {
+                id,
+                from: account.clone(),
+                to: account.clone(),
+                amount: 1,
+                fee: 0,
+                timestamp: id,
+                memo: None,
+                transaction_type: TransactionType::Transfer,
+                tx_hash: [0; 32],
+                spender: None,
+            };
+            icrc3::record_block(&tx);
+            TRANSACTIONS.with(|t| t.borrow_mut().insert(id, tx));
+        }
+    }
+
+    fn archive(id: u8, start: u64, end: u64) -> ArchiveRecord {
+        ArchiveRecord {
+            canister_id: Principal::from_slice(&[id]),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_archived_ranges() {
+        update_archive_state(|state| state.archives = vec![archive(1, 0, 10), archive(2, 10, 15)]);
+
+        assert_eq!(
+            archived_ranges(5, 12),
+            vec![(Principal::from_slice(&[1]), 5, 10), (Principal::from_slice(&[2]), 10, 12)]
+        );
+        assert!(archived_ranges(15, 20).is_empty());
+    }
+
+    #[test]
+    fn test_remove_archived_blocks() {
+        store_transactions(0..5);
+        update_archive_state(|state| state.archives = vec![archive(1, 0, 0)]);
+
+        let blocks = blocks_to_archive(3);
+        assert_eq!(blocks.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        remove_archived_blocks(Principal::from_slice(&[1]), 0, 2);
+        assert_eq!(TRANSACTIONS.with(|t| t.borrow().first_key_value().map(|(id, _)| id)), Some(3));
+        assert_eq!(archive_state().archives[0].end, 3);
+
+        // The next local block still links to the last archived one
+        let next = blocks_to_archive(1);
+        let expected = icrc3::transaction_to_block(
+            &TRANSACTIONS.with(|t| t.borrow().get(&3).unwrap()),
+            BLOCK_HASHES.with(|h| h.borrow().get(&2)),
+        );
+        assert_eq!(next[0].1, expected);
+        assert_eq!(BLOCK_HASHES.with(|h| h.borrow().first_key_value().map(|(id, _)| id)), Some(2));
+
+        // icrc3_get_blocks redirects the archived part of a range
+        TRANSACTION_COUNTER.with(|c| c.borrow_mut().set(5).unwrap());
+        let result = icrc3::get_blocks(vec![GetBlocksRequest {
+            start: Nat::from(1u64),
+            length: Nat::from(10u64),
+        }]);
+        assert_eq!(result.blocks.iter().map(|b| b.id.clone()).collect::<Vec<_>>(), vec![Nat::from(3u64), Nat::from(4u64)]);
+        assert_eq!(result.archived_blocks.len(), 1);
+        assert_eq!(result.archived_blocks[0].callback.canister_id, Principal::from_slice(&[1]));
+        assert_eq!(
+            result.archived_blocks[0].args,
+            vec![GetBlocksRequest { start: Nat::from(1u64), length: Nat::from(2u64) }]
+        );
+    }
+
+    #[test]
+    fn test_archiving_guard() {
+        let guard = ArchivingGuard::acquire(0).unwrap();
+        assert!(ArchivingGuard::acquire(1).is_none());
+        drop(guard);
+        assert!(ArchivingGuard::acquire(2).is_some());
+
+        // A run that never released its guard stops blocking once it times out
+        std::mem::forget(ArchivingGuard::acquire(3).unwrap());
+        assert!(ArchivingGuard::acquire(4).is_none());
+        let stale = ArchivingGuard { started_at: 3 };
+        let replacement = ArchivingGuard::acquire(3 + ARCHIVING_TIMEOUT).unwrap();
+        drop(stale);
+        assert!(ArchivingGuard::acquire(4 + ARCHIVING_TIMEOUT).is_none());
+        drop(replacement);
+    }
+
+    #[test]
+    fn test_wasm_needs_approval_once_multisig_is_enabled() {
+        // SHA-256 of "abc"
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let hex: String = wasm_hash(b"abc").iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, abc);
+
+        assert!(check_wasm_approved(b"abc").is_ok());
+        crate::MULTISIG_STATE.with(|s| {
+            let mut state = s.borrow().get().clone();
+            state.threshold = 2;
+            s.borrow_mut().set(state).unwrap();
+        });
+        assert!(check_wasm_approved(b"abc").is_err());
+        approve_wasm(ByteBuf::from(wasm_hash(b"abc").to_vec()));
+        assert!(check_wasm_approved(b"abc").is_ok());
+        assert!(check_wasm_approved(b"abd").is_err());
+    }
+
+    #[test]
+    fn test_validate_archive_options() {
+        let mut options = ArchiveOptions {
+            trigger_threshold: 2000,
+            num_blocks_to_archive: 1000,
+            max_blocks_per_archive: 100_000,
+            cycles_for_archive_creation: 0,
+        };
+        assert!(validate_archive_options(&options).is_ok());
+
+        options.trigger_threshold = 500;
+        assert!(validate_archive_options(&options).is_err());
+    }
+}
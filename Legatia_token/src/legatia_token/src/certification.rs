@@ -0,0 +1,296 @@
+// Certified state: balances, total supply and the ICRC-3 ledger tip
+//
+// The certified tree has these top-level labels, in order:
+//   balances         -> account label -> LEB128 balance
+//   last_block_hash  -> hash of the newest block
+//   last_block_index -> LEB128 index of the newest block
+//   total_supply     -> LEB128 total supply
+//
+// The balance tree lives on the heap and follows every balance change. After an
+// upgrade it is rebuilt from stable memory in timer batches, and `balances` is left
+// out of the certified tree until it is complete.
+use candid::{CandidType, Deserialize};
+use ic_cdk::{api, query};
+use ic_certification::{fork, label, leaf, pruned, AsHashTree, HashTree, RbTree};
+use serde_bytes::ByteBuf;
+use std::cell::{Cell, RefCell};
+use std::ops::Bound::{Excluded, Unbounded};
+use std::time::Duration;
+
+use crate::{token_data, AccountKey, BalanceArgs, BALANCES, BLOCK_HASHES};
+
+// Balances copied into the tree per timer callback while rebuilding it
+const BALANCE_TREE_BATCH_SIZE: usize = 5_000;
+
+thread_local! {
+    // Mirror of BALANCES, keyed by account label
+    static BALANCE_TREE: RefCell<RbTree<Vec<u8>, Vec<u8>>> = const { RefCell::new(RbTree::new()) };
+    // Last balance copied by an unfinished rebuild; None with BALANCES_READY unset means not started
+    static REBUILD_CURSOR: RefCell<Option<AccountKey>> = const { RefCell::new(None) };
+    static BALANCES_READY: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CertifiedValue {
+    pub value: u128,
+    pub certificate: Option<ByteBuf>,
+    pub hash_tree: ByteBuf, // CBOR-encoded witness
+}
+
+// Which parts of the tree a witness should reveal
+enum Reveal<'a> {
+    Nothing,
+    Balance(&'a [u8]),
+    Tip,
+    TotalSupply,
+}
+
+// Label of an account in the balances subtree: owner length, owner bytes,
+// then 0 for no subaccount or 1 followed by the subaccount bytes
+pub(crate) fn account_label(key: &AccountKey) -> Vec<u8> {
+    let owner = key.owner.as_slice();
+    let mut label = Vec::with_capacity(owner.len() + 34);
+    label.push(owner.len() as u8);
+    label.extend_from_slice(owner);
+    match &key.subaccount {
+        Some(subaccount) => {
+            label.push(1);
+            label.extend_from_slice(subaccount);
+        }
+        None => label.push(0),
+    }
+    label
+}
+
+fn encode_nat(value: u128) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut value = value;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+// Called on every balance change, including ones made while the tree is being rebuilt
+pub(crate) fn update_balance(key: &AccountKey, amount: u128) {
+    BALANCE_TREE.with(|tree| {
+        let mut tree = tree.borrow_mut();
+        if amount == 0 {
+            tree.delete(&account_label(key));
+        } else {
+            tree.insert(account_label(key), encode_nat(amount));
+        }
+    });
+}
+
+// Copies the next `limit` balances into the tree; returns whether any are left
+pub(crate) fn rebuild_balance_batch(limit: usize) -> bool {
+    let after = REBUILD_CURSOR.with(|c| c.borrow().clone());
+    let batch: Vec<(AccountKey, u128)> = BALANCES.with(|b| {
+        let balances = b.borrow();
+        match after {
+            Some(after) => balances.range((Excluded(after), Unbounded)).take(limit).collect(),
+            None => balances.iter().take(limit).collect(),
+        }
+    });
+
+    for (key, amount) in &batch {
+        update_balance(key, *amount);
+    }
+    let pending = batch.len() == limit;
+    REBUILD_CURSOR.with(|c| *c.borrow_mut() = batch.last().map(|(key, _)| key.clone()).filter(|_| pending));
+    BALANCES_READY.with(|ready| ready.set(!pending));
+    pending
+}
+
+fn continue_balance_rebuild() {
+    if rebuild_balance_batch(BALANCE_TREE_BATCH_SIZE) {
+        ic_cdk_timers::set_timer(Duration::ZERO, continue_balance_rebuild);
+    }
+    certify();
+}
+
+// The heap tree is empty after an upgrade; refill it one batch per timer callback
+pub(crate) fn rebuild_balance_tree() {
+    BALANCE_TREE.with(|tree| *tree.borrow_mut() = RbTree::new());
+    REBUILD_CURSOR.with(|c| *c.borrow_mut() = None);
+    BALANCES_READY.with(|ready| ready.set(false));
+    continue_balance_rebuild();
+}
+
+// Keep `subtree` when revealed, otherwise prune it down to its digest
+fn reveal_if(revealed: bool, subtree: HashTree) -> HashTree {
+    if revealed {
+        subtree
+    } else {
+        pruned(subtree.digest())
+    }
+}
+
+fn certified_tree(reveal: Reveal) -> HashTree {
+    let mut parts = Vec::new();
+
+    if BALANCES_READY.with(|ready| ready.get()) {
+        let balances = BALANCE_TREE.with(|tree| {
+            let tree = tree.borrow();
+            match reveal {
+                Reveal::Balance(key) => tree.witness(key),
+                _ => pruned(tree.root_hash()),
+            }
+        });
+        parts.push(label("balances", balances));
+    }
+
+    if let Some((index, hash)) = BLOCK_HASHES.with(|h| h.borrow().last_key_value()) {
+        let show_tip = matches!(reveal, Reveal::Tip);
+        parts.push(label("last_block_hash", reveal_if(show_tip, leaf(hash.to_vec()))));
+        parts.push(label("last_block_index", reveal_if(show_tip, leaf(encode_nat(index as u128)))));
+    }
+
+    let supply = leaf(encode_nat(token_data().total_supply));
+    parts.push(label("total_supply", reveal_if(matches!(reveal, Reveal::TotalSupply), supply)));
+
+    // Fold the labels into a left-leaning chain of forks
+    parts
+        .into_iter()
+        .reduce(fork)
+        .expect("Certified tree always has a total_supply label")
+}
+
+// Refresh the certified data after balances, the supply or the block log changed
+pub(crate) fn certify() {
+    api::set_certified_data(&certified_tree(Reveal::Nothing).digest());
+}
+
+fn encode_tree(tree: &HashTree) -> ByteBuf {
+    let mut serializer = serde_cbor::Serializer::new(Vec::new());
+    serializer.self_describe().expect("Failed to encode hash tree");
+    serde::Serialize::serialize(tree, &mut serializer).expect("Failed to encode hash tree");
+    ByteBuf::from(serializer.into_inner())
+}
+
+// Witness for the ICRC-3 tip certificate
+pub(crate) fn tip_witness() -> ByteBuf {
+    encode_tree(&certified_tree(Reveal::Tip))
+}
+
+fn certified_value(value: u128, reveal: Reveal) -> CertifiedValue {
+    CertifiedValue {
+        value,
+        certificate: api::data_certificate().map(ByteBuf::from),
+        hash_tree: encode_tree(&certified_tree(reveal)),
+    }
+}
+
+#[query]
+fn certified_balance_of(args: BalanceArgs) -> CertifiedValue {
+    if !BALANCES_READY.with(|ready| ready.get()) {
+        ic_cdk::trap("Balances are being re-certified after an upgrade; retry shortly");
+    }
+    let label = account_label(&AccountKey::from(args.account.clone()));
+    certified_value(crate::get_balance(&args.account), Reveal::Balance(&label))
+}
+
+#[query]
+fn certified_total_supply() -> CertifiedValue {
+    certified_value(token_data().total_supply, Reveal::TotalSupply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Account;
+    use candid::Principal;
+    use ic_certification::{Label, LookupResult};
+
+    fn account(id: u8, subaccount: Option<Vec<u8>>) -> Account {
+        Account {
+            owner: Principal::from_slice(&[id]),
+            subaccount,
+        }
+    }
+
+    #[test]
+    fn test_encode_nat() {
+        assert_eq!(encode_nat(0), vec![0]);
+        assert_eq!(encode_nat(127), vec![0x7f]);
+        assert_eq!(encode_nat(624_485), vec![0xe5, 0x8e, 0x26]);
+    }
+
+    #[test]
+    fn test_account_labels_are_distinct() {
+        let none = account_label(&AccountKey::from(account(1, None)));
+        let empty = account_label(&AccountKey::from(account(1, Some(vec![]))));
+        let zeros = account_label(&AccountKey::from(account(1, Some(vec![0; 32]))));
+        assert_ne!(none, empty);
+        assert_ne!(empty, zeros);
+    }
+
+    #[test]
+    fn test_witnesses_share_the_certified_root() {
+        let user = account(1, None);
+        crate::set_balance(&user, 5000).unwrap();
+        crate::set_balance(&account(2, None), 700).unwrap();
+        assert!(!rebuild_balance_batch(BALANCE_TREE_BATCH_SIZE));
+        crate::update_token_data(|data| data.total_supply = 5000);
+        BLOCK_HASHES.with(|h| h.borrow_mut().insert(0, [7; 32]));
+
+        let root = certified_tree(Reveal::Nothing).digest();
+        let label = account_label(&AccountKey::from(user));
+        let balance_witness = certified_tree(Reveal::Balance(&label));
+        let tip_witness = certified_tree(Reveal::Tip);
+        let supply_witness = certified_tree(Reveal::TotalSupply);
+        assert_eq!(balance_witness.digest(), root);
+        assert_eq!(tip_witness.digest(), root);
+        assert_eq!(supply_witness.digest(), root);
+
+        let path: Vec<Label> = vec!["balances".into(), label.into()];
+        assert_eq!(balance_witness.lookup_path(&path), LookupResult::Found(&encode_nat(5000)[..]));
+
+        let path: Vec<Label> = vec!["total_supply".into()];
+        assert_eq!(supply_witness.lookup_path(&path), LookupResult::Found(&encode_nat(5000)[..]));
+        let path: Vec<Label> = vec!["last_block_hash".into()];
+        assert_eq!(tip_witness.lookup_path(&path), LookupResult::Found(&[7; 32][..]));
+
+        // Supply changes and new blocks move the root
+        crate::update_token_data(|data| data.total_supply = 4000);
+        let after_burn = certified_tree(Reveal::Nothing).digest();
+        assert_ne!(after_burn, root);
+        BLOCK_HASHES.with(|h| h.borrow_mut().insert(1, [8; 32]));
+        let after_block = certified_tree(Reveal::Nothing).digest();
+        assert_ne!(after_block, after_burn);
+
+        // Balance changes move the root
+        crate::set_balance(&account(2, None), 0).unwrap();
+        assert_ne!(certified_tree(Reveal::Nothing).digest(), after_block);
+    }
+
+    #[test]
+    fn test_rebuild_in_batches_matches_incremental_updates() {
+        for id in 1..=5 {
+            crate::set_balance(&account(id, None), id as u128 * 10).unwrap();
+        }
+        assert!(!rebuild_balance_batch(BALANCE_TREE_BATCH_SIZE));
+        let before = certified_tree(Reveal::Nothing).digest();
+
+        // An upgrade empties the heap tree; balances stay out of the certificate until it is refilled
+        BALANCE_TREE.with(|tree| *tree.borrow_mut() = RbTree::new());
+        BALANCES_READY.with(|ready| ready.set(false));
+        assert!(rebuild_balance_batch(2));
+        assert!(!BALANCES_READY.with(|ready| ready.get()));
+
+        // A transfer during the rebuild lands in the tree straight away
+        crate::set_balance(&account(5, None), 60).unwrap();
+        assert!(rebuild_balance_batch(2));
+        assert!(!rebuild_balance_batch(2));
+        assert!(BALANCES_READY.with(|ready| ready.get()));
+
+        crate::set_balance(&account(5, None), 50).unwrap();
+        assert_eq!(certified_tree(Reveal::Nothing).digest(), before);
+    }
+}
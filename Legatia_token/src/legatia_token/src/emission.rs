@@ -0,0 +1,282 @@
+// Emission schedule for reward_user
+//
+// Rewards draw on a per-epoch budget that halves every `halving_interval_epochs`
+// epochs, and each account can receive at most `daily_account_cap` per day.
+// Epochs count from the ledger's genesis; the per-account daily tally lives in
+// REWARD_STATS next to the account's lifetime totals.
+use candid::{CandidType, Deserialize};
+use ic_cdk::{api, query, update};
+
+use crate::{token_data, Account, AccountKey, RewardStats, TransferError, EMISSION_STATE, REWARD_STATS};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct EmissionSchedule {
+    pub epoch_days: u32,
+    pub initial_epoch_budget: u128,
+    pub halving_interval_epochs: u32,
+    pub daily_account_cap: u128,
+}
+
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        Self {
+            epoch_days: 30,
+            initial_epoch_budget: 1_000_000_000_000_000, // 10 million tokens per epoch
+            halving_interval_epochs: 12,                 // Roughly yearly halvings
+            daily_account_cap: 500_000_000_000,          // 5000 tokens per account per day
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub(crate) struct EmissionState {
+    pub schedule: EmissionSchedule,
+    pub genesis: u64,
+    pub epoch: u64,
+    pub emitted_in_epoch: u128,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct EmissionStatus {
+    pub schedule: EmissionSchedule,
+    pub epoch: u64,
+    pub epoch_budget: u128,
+    pub emitted_in_epoch: u128,
+    pub remaining_in_epoch: u128,
+    pub epoch_ends_at: u64,
+}
+
+fn emission_error(error_code: u128, message: &str) -> TransferError {
+    TransferError::GenericError {
+        error_code,
+        message: message.to_string(),
+    }
+}
+
+fn emission_state() -> EmissionState {
+    EMISSION_STATE.with(|s| s.borrow().get().clone())
+}
+
+fn update_emission_state(f: impl FnOnce(&mut EmissionState)) {
+    EMISSION_STATE.with(|s| {
+        let mut cell = s.borrow_mut();
+        let mut state = cell.get().clone();
+        f(&mut state);
+        if cell.set(state).is_err() {
+            ic_cdk::trap("Failed to persist emission state");
+        }
+    });
+}
+
+// Start counting epochs from now unless an earlier genesis is recorded
+pub(crate) fn init_genesis(now: u64) {
+    if emission_state().genesis == 0 {
+        update_emission_state(|state| state.genesis = now);
+    }
+}
+
+pub(crate) fn validate_schedule(schedule: &EmissionSchedule) -> Result<(), TransferError> {
+    if schedule.epoch_days == 0 || schedule.halving_interval_epochs == 0 {
+        return Err(emission_error(1022, "Epoch length and halving interval must be at least 1"));
+    }
+    if schedule.initial_epoch_budget == 0 || schedule.daily_account_cap == 0 {
+        return Err(emission_error(1022, "Budgets must be greater than zero"));
+    }
+    if schedule.daily_account_cap > schedule.initial_epoch_budget {
+        return Err(emission_error(1022, "Daily account cap cannot exceed the epoch budget"));
+    }
+    Ok(())
+}
+
+fn epoch_length(schedule: &EmissionSchedule) -> u64 {
+    schedule.epoch_days as u64 * NANOS_PER_DAY
+}
+
+fn epoch_at(state: &EmissionState, now: u64) -> u64 {
+    now.saturating_sub(state.genesis) / epoch_length(&state.schedule)
+}
+
+fn epoch_budget(schedule: &EmissionSchedule, epoch: u64) -> u128 {
+    let halvings = epoch / schedule.halving_interval_epochs as u64;
+    if halvings >= 128 {
+        0
+    } else {
+        schedule.initial_epoch_budget >> halvings
+    }
+}
+
+// Tallies from a past epoch or day no longer count
+fn emitted_in(state: &EmissionState, epoch: u64) -> u128 {
+    if state.epoch == epoch {
+        state.emitted_in_epoch
+    } else {
+        0
+    }
+}
+
+fn rewarded_on(stats: &RewardStats, day: u64) -> u128 {
+    match (stats.reward_day, stats.rewarded_today) {
+        (Some(reward_day), Some(rewarded)) if reward_day == day => rewarded,
+        _ => 0,
+    }
+}
+
+fn remaining_for(state: &EmissionState, stats: &RewardStats, now: u64) -> (u128, u128) {
+    let epoch = epoch_at(state, now);
+    let epoch_remaining = epoch_budget(&state.schedule, epoch).saturating_sub(emitted_in(state, epoch));
+    let daily_remaining = state.schedule.daily_account_cap.saturating_sub(rewarded_on(stats, now / NANOS_PER_DAY));
+    (epoch_remaining, daily_remaining)
+}
+
+fn check_budget(state: &EmissionState, stats: &RewardStats, amount: u128, now: u64) -> Result<(), TransferError> {
+    let (epoch_remaining, daily_remaining) = remaining_for(state, stats, now);
+    if amount > epoch_remaining {
+        return Err(emission_error(1020, "Reward emission budget for this epoch is exhausted"));
+    }
+    if amount > daily_remaining {
+        return Err(emission_error(1021, "Daily reward cap reached for this account"));
+    }
+    Ok(())
+}
+
+fn reward_stats(account: &Account) -> RewardStats {
+    let key = AccountKey::from(account.clone());
+    REWARD_STATS.with(|stats| stats.borrow().get(&key).unwrap_or_default())
+}
+
+// Called by reward_user before any balance changes
+pub(crate) fn check_reward(user: &Account, amount: u128, now: u64) -> Result<(), TransferError> {
+    check_budget(&emission_state(), &reward_stats(user), amount, now)
+}
+
+// Count a paid reward against the epoch budget and the account's day
+pub(crate) fn record_reward(stats: &mut RewardStats, amount: u128, now: u64) {
+    let day = now / NANOS_PER_DAY;
+    stats.rewarded_today = Some(rewarded_on(stats, day).saturating_add(amount));
+    stats.reward_day = Some(day);
+
+    update_emission_state(|state| {
+        let epoch = epoch_at(state, now);
+        state.emitted_in_epoch = emitted_in(state, epoch).saturating_add(amount);
+        state.epoch = epoch;
+    });
+}
+
+pub(crate) fn apply_schedule(schedule: EmissionSchedule) -> Result<(), TransferError> {
+    validate_schedule(&schedule)?;
+    update_emission_state(|state| state.schedule = schedule);
+    Ok(())
+}
+
+#[update]
+fn set_emission_schedule(schedule: EmissionSchedule) -> Result<(), TransferError> {
+    if !token_data().is_governor(&api::caller()) {
+        return Err(TransferError::Unauthorized);
+    }
+    apply_schedule(schedule)
+}
+
+#[query]
+fn get_emission_status() -> EmissionStatus {
+    let state = emission_state();
+    let now = api::time();
+    let epoch = epoch_at(&state, now);
+    let epoch_budget = epoch_budget(&state.schedule, epoch);
+    let emitted_in_epoch = emitted_in(&state, epoch);
+
+    EmissionStatus {
+        epoch,
+        epoch_budget,
+        emitted_in_epoch,
+        remaining_in_epoch: epoch_budget.saturating_sub(emitted_in_epoch),
+        epoch_ends_at: state.genesis + (epoch + 1) * epoch_length(&state.schedule),
+        schedule: state.schedule,
+    }
+}
+
+// How much more the account can be rewarded right now
+#[query]
+fn get_remaining_reward_allowance(user: Account) -> u128 {
+    let (epoch_remaining, daily_remaining) = remaining_for(&emission_state(), &reward_stats(&user), api::time());
+    epoch_remaining
+        .min(daily_remaining)
+        .min(token_data().reward_limit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = NANOS_PER_DAY;
+
+    fn state() -> EmissionState {
+        EmissionState {
+            schedule: EmissionSchedule {
+                epoch_days: 10,
+                initial_epoch_budget: 1_000,
+                halving_interval_epochs: 2,
+                daily_account_cap: 300,
+            },
+            genesis: 5 * DAY,
+            epoch: 0,
+            emitted_in_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_budget_halves_on_schedule() {
+        let schedule = state().schedule;
+        assert_eq!(epoch_budget(&schedule, 0), 1_000);
+        assert_eq!(epoch_budget(&schedule, 1), 1_000);
+        assert_eq!(epoch_budget(&schedule, 2), 500);
+        assert_eq!(epoch_budget(&schedule, 5), 250);
+        assert_eq!(epoch_budget(&schedule, 1_000), 0);
+
+        assert_eq!(epoch_at(&state(), 14 * DAY), 0);
+        assert_eq!(epoch_at(&state(), 15 * DAY), 1);
+    }
+
+    #[test]
+    fn test_daily_cap_resets_each_day() {
+        let state = state();
+        let mut stats = RewardStats {
+            reward_day: Some(6),
+            rewarded_today: Some(250),
+            ..RewardStats::default()
+        };
+
+        assert!(check_budget(&state, &stats, 50, 6 * DAY).is_ok());
+        assert!(check_budget(&state, &stats, 51, 6 * DAY).is_err());
+        assert!(check_budget(&state, &stats, 300, 7 * DAY).is_ok());
+
+        stats.reward_day = None;
+        assert!(check_budget(&state, &stats, 300, 6 * DAY).is_ok());
+    }
+
+    #[test]
+    fn test_epoch_budget_tracks_emissions() {
+        let mut state = state();
+        state.emitted_in_epoch = 900;
+        let stats = RewardStats::default();
+
+        assert!(check_budget(&state, &stats, 100, 6 * DAY).is_ok());
+        assert!(check_budget(&state, &stats, 101, 6 * DAY).is_err());
+
+        // The next epoch starts with a fresh budget
+        assert!(check_budget(&state, &stats, 300, 16 * DAY).is_ok());
+    }
+
+    #[test]
+    fn test_schedule_validation() {
+        assert!(validate_schedule(&EmissionSchedule::default()).is_ok());
+        assert!(validate_schedule(&EmissionSchedule { epoch_days: 0, ..EmissionSchedule::default() }).is_err());
+        assert!(validate_schedule(&EmissionSchedule { daily_account_cap: 0, ..EmissionSchedule::default() }).is_err());
+        assert!(validate_schedule(&EmissionSchedule {
+            daily_account_cap: 2_000_000_000_000_000,
+            ..EmissionSchedule::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,314 @@
+// ICRC-3 block log built from the stored `Transaction` records
+use candid::Nat;
+use ic_cdk::{api, query};
+use icrc_ledger_types::icrc::generic_value::{ICRC3Map, ICRC3Value};
+use icrc_ledger_types::icrc3::archive::QueryArchiveFn;
+use icrc_ledger_types::icrc3::blocks::{
+    ArchivedBlocks, BlockWithId, GetBlocksRequest, GetBlocksResult, ICRC3DataCertificate, SupportedBlockType,
+};
+use serde_bytes::ByteBuf;
+use std::time::Duration;
+
+use crate::archive::archived_ranges;
+use crate::certification;
+use crate::{Account, Transaction, TransactionType, BLOCK_HASHES, TRANSACTIONS, TRANSACTION_COUNTER};
+
+// Upper bound on blocks returned by a single icrc3_get_blocks call
+const MAX_BLOCKS_PER_REQUEST: u64 = 100;
+// Blocks hashed per timer callback while catching up on an unhashed log
+const BLOCK_HASH_BATCH_SIZE: usize = 1_000;
+
+fn account_value(account: &Account) -> ICRC3Value {
+    let mut parts = vec![ICRC3Value::Blob(ByteBuf::from(account.owner.as_slice().to_vec()))];
+    if let Some(subaccount) = account.subaccount.as_ref().filter(|s| !s.is_empty()) {
+        parts.push(ICRC3Value::Blob(ByteBuf::from(subaccount.clone())));
+    }
+    ICRC3Value::Array(parts)
+}
+
+// ICRC-3 block type for a transaction; rewards are recorded as mints
+fn block_type(tx: &Transaction) -> &'static str {
+    match tx.transaction_type {
+        TransactionType::Transfer if tx.spender.is_some() => "2xfer",
+        TransactionType::Transfer => "1xfer",
+        TransactionType::Mint | TransactionType::Reward => "1mint",
+        TransactionType::Burn => "1burn",
+        TransactionType::Approve => "2approve",
+    }
+}
+
+pub(crate) fn transaction_to_block(tx: &Transaction, phash: Option<[u8; 32]>) -> ICRC3Value {
+    let mut inner = ICRC3Map::new();
+    inner.insert("amt".to_string(), ICRC3Value::Nat(Nat::from(tx.amount)));
+    match tx.transaction_type {
+        TransactionType::Mint | TransactionType::Reward => {
+            inner.insert("to".to_string(), account_value(&tx.to));
+        }
+        TransactionType::Burn => {
+            inner.insert("from".to_string(), account_value(&tx.from));
+        }
+        TransactionType::Approve => {
+            inner.insert("from".to_string(), account_value(&tx.from));
+            if let Some(spender) = &tx.spender {
+                inner.insert("spender".to_string(), account_value(spender));
+            }
+        }
+        TransactionType::Transfer => {
+            inner.insert("from".to_string(), account_value(&tx.from));
+            inner.insert("to".to_string(), account_value(&tx.to));
+            if let Some(spender) = &tx.spender {
+                inner.insert("spender".to_string(), account_value(spender));
+            }
+        }
+    }
+    if let Some(memo) = &tx.memo {
+        inner.insert("memo".to_string(), ICRC3Value::Blob(ByteBuf::from(memo.clone())));
+    }
+
+    let mut block = ICRC3Map::new();
+    block.insert("btype".to_string(), ICRC3Value::Text(block_type(tx).to_string()));
+    block.insert("ts".to_string(), ICRC3Value::Nat(Nat::from(tx.timestamp)));
+    if tx.fee > 0 {
+        block.insert("fee".to_string(), ICRC3Value::Nat(Nat::from(tx.fee)));
+    }
+    if let Some(phash) = phash {
+        block.insert("phash".to_string(), ICRC3Value::Blob(ByteBuf::from(phash.to_vec())));
+    }
+    block.insert("tx".to_string(), ICRC3Value::Map(inner));
+    ICRC3Value::Map(block)
+}
+
+// The parent of block `id` is always block `id - 1`; a gap means no parent hash
+pub(crate) fn parent_hash(id: u64) -> Option<[u8; 32]> {
+    id.checked_sub(1).and_then(|parent| BLOCK_HASHES.with(|h| h.borrow().get(&parent)))
+}
+
+fn hash_block(tx: &Transaction) {
+    let hash = transaction_to_block(tx, parent_hash(tx.id)).hash();
+    BLOCK_HASHES.with(|h| h.borrow_mut().insert(tx.id, hash));
+}
+
+fn next_unhashed() -> u64 {
+    BLOCK_HASHES.with(|h| h.borrow().last_key_value().map(|(id, _)| id + 1).unwrap_or(0))
+}
+
+// Hash a freshly stored transaction onto the chain. While older transactions are still
+// being hashed the new one waits for the backfill, so each block hashes onto its parent.
+pub(crate) fn record_block(tx: &Transaction) {
+    if tx.id == next_unhashed() {
+        hash_block(tx);
+    }
+}
+
+// True while stored transactions are still waiting to be hashed
+pub(crate) fn backfill_pending() -> bool {
+    let next = next_unhashed();
+    TRANSACTIONS.with(|t| t.borrow().range(next..).next().is_some())
+}
+
+// Hashes up to `limit` transactions that were stored before the block log existed;
+// true if some are left
+pub(crate) fn hash_pending_blocks(limit: usize) -> bool {
+    let next = next_unhashed();
+    let pending: Vec<Transaction> = TRANSACTIONS.with(|t| t.borrow().range(next..).take(limit).map(|(_, tx)| tx).collect());
+    for tx in &pending {
+        hash_block(tx);
+    }
+    backfill_pending()
+}
+
+// Catches up one batch per timer callback, so an upgrade never hashes the whole log
+pub(crate) fn backfill_block_hashes() {
+    if hash_pending_blocks(BLOCK_HASH_BATCH_SIZE) {
+        ic_cdk_timers::set_timer(Duration::ZERO, backfill_block_hashes);
+    }
+    certification::certify();
+}
+
+fn last_block() -> Option<(u64, [u8; 32])> {
+    BLOCK_HASHES.with(|h| h.borrow().last_key_value())
+}
+
+// Serves local blocks and points callers at the archives for older ranges
+pub(crate) fn get_blocks(requests: Vec<GetBlocksRequest>) -> GetBlocksResult {
+    let log_length = TRANSACTION_COUNTER.with(|c| *c.borrow().get());
+    let first_local = TRANSACTIONS.with(|t| t.borrow().first_key_value().map(|(id, _)| id)).unwrap_or(log_length);
+    let mut budget = MAX_BLOCKS_PER_REQUEST;
+    let mut blocks = Vec::new();
+    let mut archived_blocks: Vec<ArchivedBlocks> = Vec::new();
+
+    for request in requests {
+        let Ok((start, length)) = request.as_start_and_length() else {
+            continue;
+        };
+        let requested_end = start.saturating_add(length).min(log_length);
+
+        for (canister_id, from, to) in archived_ranges(start, requested_end.min(first_local)) {
+            let args = GetBlocksRequest {
+                start: Nat::from(from),
+                length: Nat::from(to - from),
+            };
+            match archived_blocks.iter_mut().find(|a| a.callback.canister_id == canister_id) {
+                Some(archived) => archived.args.push(args),
+                None => archived_blocks.push(ArchivedBlocks {
+                    args: vec![args],
+                    callback: QueryArchiveFn::new(canister_id, "icrc3_get_blocks"),
+                }),
+            }
+        }
+
+        let start = start.max(first_local);
+        let end = start.saturating_add(budget).min(requested_end);
+        if start >= end {
+            continue;
+        }
+
+        TRANSACTIONS.with(|t| {
+            for (id, tx) in t.borrow().range(start..end) {
+                blocks.push(BlockWithId {
+                    id: Nat::from(id),
+                    block: transaction_to_block(&tx, parent_hash(id)),
+                });
+            }
+        });
+        budget -= end - start;
+    }
+
+    GetBlocksResult {
+        log_length: Nat::from(log_length),
+        blocks,
+        archived_blocks,
+    }
+}
+
+#[query]
+fn icrc3_get_blocks(requests: Vec<GetBlocksRequest>) -> GetBlocksResult {
+    get_blocks(requests)
+}
+
+#[query]
+fn icrc3_get_tip_certificate() -> Option<ICRC3DataCertificate> {
+    let certificate = api::data_certificate()?;
+    last_block()?;
+
+    Some(ICRC3DataCertificate {
+        certificate: ByteBuf::from(certificate),
+        hash_tree: certification::tip_witness(),
+    })
+}
+
+#[query]
+fn icrc3_supported_block_types() -> Vec<SupportedBlockType> {
+    let icrc1 = "https://github.com/dfinity/ICRC-1/blob/main/standards/ICRC-1/README.md";
+    let icrc2 = "https://github.com/dfinity/ICRC-1/blob/main/standards/ICRC-2/README.md";
+    [("1burn", icrc1), ("1mint", icrc1), ("1xfer", icrc1), ("2approve", icrc2), ("2xfer", icrc2)]
+        .into_iter()
+        .map(|(block_type, url)| SupportedBlockType {
+            block_type: block_type.to_string(),
+            url: url.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    fn account(id: u8) -> Account {
+        Account {
+            owner: Principal::from_slice(&[id]),
+            subaccount: None,
+        }
+    }
+
+    fn transaction(id: u64, transaction_type: TransactionType) -> Transaction {
+        Transaction {
+            id,
+            from: account(1),
+            to: account(2),
+            amount: 500,
+            fee: 10,
+            timestamp: 1_000 + id,
+            memo: None,
+            transaction_type,
+            tx_hash: [0; 32],
+            spender: None,
+        }
+    }
+
+    fn field<'a>(block: &'a ICRC3Value, name: &str) -> Option<&'a ICRC3Value> {
+        match block {
+            ICRC3Value::Map(map) => map.get(name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_value_hash_matches_spec() {
+        // Example vectors from the ICRC-3 specification
+        let hex = |hash: [u8; 32]| hash.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        assert_eq!(
+            hex(ICRC3Value::Nat(Nat::from(42u64)).hash()),
+            "684888c0ebb17f374298b65ee2807526c066094c701bcc7ebbe1c1095f494fc1"
+        );
+        assert_eq!(
+            hex(ICRC3Value::Text("Hello, World!".to_string()).hash()),
+            "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+        );
+    }
+
+    #[test]
+    fn test_block_encoding() {
+        let mut transfer_from = transaction(3, TransactionType::Transfer);
+        transfer_from.spender = Some(account(9));
+        let block = transaction_to_block(&transfer_from, Some([7; 32]));
+
+        assert_eq!(field(&block, "btype"), Some(&ICRC3Value::Text("2xfer".to_string())));
+        assert_eq!(field(&block, "fee"), Some(&ICRC3Value::Nat(Nat::from(10u64))));
+        assert_eq!(field(&block, "phash"), Some(&ICRC3Value::Blob(ByteBuf::from(vec![7; 32]))));
+        let tx = field(&block, "tx").unwrap();
+        assert_eq!(field(tx, "spender"), Some(&account_value(&account(9))));
+        assert_eq!(field(tx, "amt"), Some(&ICRC3Value::Nat(Nat::from(500u64))));
+
+        let reward = transaction_to_block(&transaction(0, TransactionType::Reward), None);
+        assert_eq!(field(&reward, "btype"), Some(&ICRC3Value::Text("1mint".to_string())));
+        assert!(field(&reward, "phash").is_none());
+        assert!(field(field(&reward, "tx").unwrap(), "from").is_none());
+    }
+
+    #[test]
+    fn test_blocks_are_hash_chained() {
+        for id in 0..3 {
+            let tx = transaction(id, TransactionType::Transfer);
+            TRANSACTIONS.with(|t| t.borrow_mut().insert(id, tx));
+        }
+        TRANSACTION_COUNTER.with(|c| c.borrow_mut().set(3).unwrap());
+        // Hashed in two batches, as the upgrade backfill would. A block recorded
+        // mid-backfill waits for its parent to be hashed.
+        assert!(hash_pending_blocks(1));
+        record_block(&transaction(2, TransactionType::Transfer));
+        assert!(BLOCK_HASHES.with(|h| h.borrow().get(&2)).is_none());
+        assert!(!hash_pending_blocks(2));
+
+        let result = get_blocks(vec![GetBlocksRequest {
+            start: Nat::from(0u64),
+            length: Nat::from(10u64),
+        }]);
+        assert_eq!(result.log_length, Nat::from(3u64));
+        assert_eq!(result.blocks.len(), 3);
+
+        for pair in result.blocks.windows(2) {
+            let expected = ByteBuf::from(pair[0].block.clone().hash().to_vec());
+            assert_eq!(field(&pair[1].block, "phash"), Some(&ICRC3Value::Blob(expected)));
+        }
+        assert_eq!(last_block().map(|(id, _)| id), Some(2));
+
+        // Ranges past the end of the log are clamped
+        let result = get_blocks(vec![GetBlocksRequest {
+            start: Nat::from(2u64),
+            length: Nat::from(10u64),
+        }]);
+        assert_eq!(result.blocks.len(), 1);
+    }
+}
@@ -0,0 +1,435 @@
+// M-of-N approval for minting and ledger settings
+//
+// Once a signer set is configured, the admin and minting account lose their
+// single-key powers. Mints, burns from other holders' accounts, signer changes, archive
+// settings and (while no governance canister is registered) fee and reward settings
+// only take effect after `threshold` signers approve the same pending operation
+// before it expires.
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::{api, query, update};
+use serde_bytes::ByteBuf;
+
+use crate::archive::{self, ArchiveOptions};
+use crate::emission::{self, EmissionSchedule};
+use crate::{
+    apply_fee_change, apply_max_reward_per_call, apply_reward_distributor, burn_tokens, mint_tokens, token_data,
+    update_token_data, validate_account, validate_amount, Account, TransferError, MAX_FEE, MULTISIG_OPERATIONS,
+    MULTISIG_STATE,
+};
+
+const OPERATION_TTL: u64 = 48 * 60 * 60 * 1_000_000_000; // 48 hours in nanoseconds
+const MAX_SIGNERS: usize = 20;
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub(crate) struct MultisigState {
+    pub signers: Vec<Principal>,
+    pub threshold: u8, // Zero while no signer set is configured
+    pub next_operation_id: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignerSet {
+    pub signers: Vec<Principal>,
+    pub threshold: u8,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum MultisigOperation {
+    Mint { to: Account, amount: u128 },
+    Burn { from: Account, amount: u128 },
+    SetFee { fee: u128 },
+    SetMaxRewardPerCall { amount: u128 },
+    SetRewardDistributor { distributor: Principal, enabled: bool },
+    SetGovernanceCanister { governance: Option<Principal> },
+    SetEmissionSchedule(EmissionSchedule),
+    SetSigners(SignerSet),
+    SetArchiveOptions(ArchiveOptions),
+    // SHA-256 of the wasm uploaded with upload_archive_wasm
+    ApproveArchiveWasm { sha256: ByteBuf },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum OperationStatus {
+    Pending,
+    Executed { block_index: Option<u64> },
+    Failed(String),
+    Expired,
+    Cancelled, // The signer set changed before it was approved
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PendingOperation {
+    pub id: u64,
+    pub operation: MultisigOperation,
+    pub proposed_by: Principal,
+    pub approvals: Vec<Principal>,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub status: OperationStatus,
+}
+
+fn multisig_error(error_code: u128, message: &str) -> TransferError {
+    TransferError::GenericError {
+        error_code,
+        message: message.to_string(),
+    }
+}
+
+fn multisig_state() -> MultisigState {
+    MULTISIG_STATE.with(|s| s.borrow().get().clone())
+}
+
+fn update_multisig_state<R>(f: impl FnOnce(&mut MultisigState) -> R) -> R {
+    MULTISIG_STATE.with(|s| {
+        let mut cell = s.borrow_mut();
+        let mut state = cell.get().clone();
+        let result = f(&mut state);
+        if cell.set(state).is_err() {
+            ic_cdk::trap("Failed to persist multisig state");
+        }
+        result
+    })
+}
+
+pub(crate) fn is_enabled() -> bool {
+    multisig_state().threshold > 0
+}
+
+// Single-key paths call this before acting on their own
+pub(crate) fn require_disabled() -> Result<(), TransferError> {
+    if is_enabled() {
+        return Err(multisig_error(1016, "This action requires signer approval; use propose_operation"));
+    }
+    Ok(())
+}
+
+fn require_signer(state: &MultisigState, caller: &Principal) -> Result<(), TransferError> {
+    if !state.signers.contains(caller) {
+        return Err(TransferError::Unauthorized);
+    }
+    Ok(())
+}
+
+// At least two signers must agree, so no single key can act alone
+fn validate_signer_set(set: &SignerSet) -> Result<(), TransferError> {
+    if set.signers.len() > MAX_SIGNERS {
+        return Err(multisig_error(1017, &format!("At most {} signers are allowed", MAX_SIGNERS)));
+    }
+    if set.signers.contains(&Principal::anonymous()) {
+        return Err(multisig_error(1017, "Anonymous principal cannot be a signer"));
+    }
+
+    let mut unique = set.signers.clone();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != set.signers.len() {
+        return Err(multisig_error(1017, "Signers must be unique"));
+    }
+
+    if set.threshold < 2 || set.threshold as usize > set.signers.len() {
+        return Err(multisig_error(1017, "Threshold must be at least 2 and at most the number of signers"));
+    }
+    Ok(())
+}
+
+fn validate_operation(operation: &MultisigOperation) -> Result<(), TransferError> {
+    match operation {
+        MultisigOperation::Mint { to, amount } => {
+            validate_account(to)?;
+            validate_amount(*amount)
+        }
+        MultisigOperation::Burn { from, amount } => {
+            validate_account(from)?;
+            validate_amount(*amount)
+        }
+        MultisigOperation::SetFee { fee } if *fee > MAX_FEE => Err(TransferError::GenericError {
+            error_code: 1003,
+            message: "Fee exceeds maximum allowed".to_string(),
+        }),
+        MultisigOperation::SetMaxRewardPerCall { amount } => validate_amount(*amount),
+        MultisigOperation::SetSigners(set) => validate_signer_set(set),
+        MultisigOperation::SetEmissionSchedule(schedule) => emission::validate_schedule(schedule),
+        MultisigOperation::SetArchiveOptions(options) => archive::validate_archive_options(options),
+        MultisigOperation::ApproveArchiveWasm { sha256 } if sha256.len() != 32 => {
+            Err(multisig_error(1010, "Wasm hash must be 32 bytes"))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Adds the signer's approval; returns whether the operation can now execute
+fn record_approval(
+    operation: &mut PendingOperation,
+    signer: Principal,
+    threshold: u8,
+    now: u64,
+) -> Result<bool, TransferError> {
+    if operation.status != OperationStatus::Pending {
+        return Err(multisig_error(1018, "Operation is no longer pending"));
+    }
+    if now >= operation.expires_at {
+        operation.status = OperationStatus::Expired;
+        return Err(multisig_error(1018, "Operation has expired"));
+    }
+    if operation.approvals.contains(&signer) {
+        return Err(multisig_error(1018, "You have already approved this operation"));
+    }
+
+    operation.approvals.push(signer);
+    Ok(operation.approvals.len() >= threshold as usize)
+}
+
+fn save_operation(operation: &PendingOperation) {
+    MULTISIG_OPERATIONS.with(|ops| ops.borrow_mut().insert(operation.id, operation.clone()));
+}
+
+// Settings belong to governance once it is registered; the signers keep minting
+fn require_no_governance() -> Result<(), TransferError> {
+    if token_data().governance_canister.is_some() {
+        return Err(multisig_error(1019, "Ledger settings are controlled by the governance canister"));
+    }
+    Ok(())
+}
+
+fn cancel_pending_operations(except: u64) {
+    MULTISIG_OPERATIONS.with(|ops| {
+        let mut ops = ops.borrow_mut();
+        let pending: Vec<PendingOperation> = ops
+            .iter()
+            .map(|(_, op)| op)
+            .filter(|op| op.id != except && op.status == OperationStatus::Pending)
+            .collect();
+        for mut op in pending {
+            op.status = OperationStatus::Cancelled;
+            ops.insert(op.id, op);
+        }
+    });
+}
+
+fn execute(operation: &PendingOperation) -> Result<Option<u64>, TransferError> {
+    match &operation.operation {
+        MultisigOperation::Mint { to, amount } => mint_tokens(to.clone(), *amount).map(Some),
+        MultisigOperation::Burn { from, amount } => burn_tokens(from.clone(), *amount).map(Some),
+        MultisigOperation::SetFee { fee } => {
+            require_no_governance()?;
+            apply_fee_change(*fee, api::time()).map(|_| None)
+        }
+        MultisigOperation::SetMaxRewardPerCall { amount } => {
+            require_no_governance()?;
+            apply_max_reward_per_call(*amount).map(|_| None)
+        }
+        MultisigOperation::SetRewardDistributor { distributor, enabled } => {
+            require_no_governance()?;
+            apply_reward_distributor(*distributor, *enabled);
+            Ok(None)
+        }
+        MultisigOperation::SetGovernanceCanister { governance } => {
+            require_no_governance()?;
+            update_token_data(|token_data| token_data.governance_canister = *governance);
+            Ok(None)
+        }
+        MultisigOperation::SetEmissionSchedule(schedule) => {
+            require_no_governance()?;
+            emission::apply_schedule(schedule.clone()).map(|_| None)
+        }
+        MultisigOperation::SetSigners(set) => {
+            update_multisig_state(|state| {
+                state.signers = set.signers.clone();
+                state.threshold = set.threshold;
+            });
+            // Approvals were given under the old signer set
+            cancel_pending_operations(operation.id);
+            Ok(None)
+        }
+        MultisigOperation::SetArchiveOptions(options) => {
+            archive::validate_archive_options(options)?;
+            archive::set_options(Some(options.clone()));
+            Ok(None)
+        }
+        MultisigOperation::ApproveArchiveWasm { sha256 } => {
+            archive::approve_wasm(sha256.clone());
+            Ok(None)
+        }
+    }
+}
+
+fn approve_and_maybe_execute(mut operation: PendingOperation, signer: Principal, now: u64) -> Result<PendingOperation, TransferError> {
+    let threshold = multisig_state().threshold;
+    let result = record_approval(&mut operation, signer, threshold, now);
+    if let Ok(true) = result {
+        operation.status = match execute(&operation) {
+            Ok(block_index) => OperationStatus::Executed { block_index },
+            Err(error) => OperationStatus::Failed(format!("{:?}", error)),
+        };
+    }
+
+    // Persist expiry as well as successful approvals
+    save_operation(&operation);
+    result.map(|_| operation)
+}
+
+// Hand minting and settings to a signer set. The admin can do this once;
+// later changes go through a SetSigners operation.
+#[update]
+fn configure_signers(set: SignerSet) -> Result<(), TransferError> {
+    let caller = api::caller();
+    if caller != token_data().admin {
+        return Err(TransferError::Unauthorized);
+    }
+    require_disabled()?;
+    validate_signer_set(&set)?;
+
+    update_multisig_state(|state| {
+        state.signers = set.signers;
+        state.threshold = set.threshold;
+    });
+    Ok(())
+}
+
+#[query]
+fn get_signers() -> Option<SignerSet> {
+    let state = multisig_state();
+    (state.threshold > 0).then_some(SignerSet {
+        signers: state.signers,
+        threshold: state.threshold,
+    })
+}
+
+// Proposing counts as the proposer's approval
+#[update]
+fn propose_operation(operation: MultisigOperation) -> Result<PendingOperation, TransferError> {
+    let caller = api::caller();
+    require_signer(&multisig_state(), &caller)?;
+    validate_operation(&operation)?;
+
+    let now = api::time();
+    let id = update_multisig_state(|state| {
+        let id = state.next_operation_id;
+        state.next_operation_id += 1;
+        id
+    });
+
+    let pending = PendingOperation {
+        id,
+        operation,
+        proposed_by: caller,
+        approvals: Vec::new(),
+        created_at: now,
+        expires_at: now + OPERATION_TTL,
+        status: OperationStatus::Pending,
+    };
+    approve_and_maybe_execute(pending, caller, now)
+}
+
+#[update]
+fn approve_operation(operation_id: u64) -> Result<PendingOperation, TransferError> {
+    let caller = api::caller();
+    require_signer(&multisig_state(), &caller)?;
+
+    let operation = MULTISIG_OPERATIONS
+        .with(|ops| ops.borrow().get(&operation_id))
+        .ok_or(multisig_error(1018, "Operation not found"))?;
+    approve_and_maybe_execute(operation, caller, api::time())
+}
+
+#[query]
+fn get_operation(operation_id: u64) -> Option<PendingOperation> {
+    MULTISIG_OPERATIONS.with(|ops| ops.borrow().get(&operation_id))
+}
+
+// Operations that can still be approved
+#[query]
+fn get_pending_operations() -> Vec<PendingOperation> {
+    let now = api::time();
+    MULTISIG_OPERATIONS.with(|ops| {
+        ops.borrow()
+            .iter()
+            .map(|(_, op)| op)
+            .filter(|op| op.status == OperationStatus::Pending && op.expires_at > now)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn pending_fee_change() -> PendingOperation {
+        PendingOperation {
+            id: 0,
+            operation: MultisigOperation::SetFee { fee: 500 },
+            proposed_by: signer(1),
+            approvals: Vec::new(),
+            created_at: 0,
+            expires_at: 100,
+            status: OperationStatus::Pending,
+        }
+    }
+
+    #[test]
+    fn test_signer_set_validation() {
+        let set = |signers: Vec<Principal>, threshold| SignerSet { signers, threshold };
+
+        assert!(validate_signer_set(&set(vec![signer(1), signer(2), signer(3)], 2)).is_ok());
+        assert!(validate_signer_set(&set(vec![signer(1), signer(2)], 1)).is_err());
+        assert!(validate_signer_set(&set(vec![signer(1), signer(2)], 3)).is_err());
+        assert!(validate_signer_set(&set(vec![signer(1), signer(1)], 2)).is_err());
+        assert!(validate_signer_set(&set(vec![signer(1), Principal::anonymous()], 2)).is_err());
+    }
+
+    #[test]
+    fn test_approvals_reach_threshold_once() {
+        let mut operation = pending_fee_change();
+
+        assert!(!record_approval(&mut operation, signer(1), 2, 10).unwrap());
+        assert!(record_approval(&mut operation, signer(1), 2, 20).is_err());
+        assert!(record_approval(&mut operation, signer(2), 2, 30).unwrap());
+
+        operation.status = OperationStatus::Executed { block_index: None };
+        assert!(record_approval(&mut operation, signer(3), 2, 40).is_err());
+    }
+
+    #[test]
+    fn test_expired_operations_cannot_be_approved() {
+        let mut operation = pending_fee_change();
+        assert!(record_approval(&mut operation, signer(1), 2, 100).is_err());
+        assert_eq!(operation.status, OperationStatus::Expired);
+    }
+
+    #[test]
+    fn test_signer_set_disables_single_key_powers() {
+        update_token_data(|data| data.admin = signer(9));
+        assert!(token_data().is_governor(&signer(9)));
+        assert!(require_disabled().is_ok());
+
+        update_multisig_state(|state| {
+            state.signers = vec![signer(1), signer(2)];
+            state.threshold = 2;
+        });
+        assert!(!token_data().is_governor(&signer(9)));
+        assert!(require_disabled().is_err());
+        assert!(require_signer(&multisig_state(), &signer(9)).is_err());
+    }
+
+    #[test]
+    fn test_minter_burns_need_signers_once_enabled() {
+        let minter = signer(9);
+        let holder = Account { owner: signer(5), subaccount: None };
+        update_token_data(|data| data.minting_account = Some(Account { owner: minter, subaccount: None }));
+
+        assert!(crate::may_burn_from(&minter, &holder));
+        assert!(!crate::may_burn_from(&signer(6), &holder));
+
+        update_multisig_state(|state| {
+            state.signers = vec![signer(1), signer(2)];
+            state.threshold = 2;
+        });
+        assert!(!crate::may_burn_from(&minter, &holder));
+        assert!(crate::may_burn_from(&signer(5), &holder));
+        assert!(validate_operation(&MultisigOperation::Burn { from: holder, amount: 10 }).is_ok());
+    }
+}
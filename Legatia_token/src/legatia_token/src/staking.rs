@@ -0,0 +1,331 @@
+// LGT staking: tokens locked for a chosen period earn voting power
+//
+// Staked LGT sits in a subaccount of the ledger itself. A stake is either
+// locked, keeping its full dissolve delay and ageing, or dissolving, counting
+// down to the moment it can be unstaked (like NNS neurons). Locking with
+// `start_dissolving` set gives a plain fixed-term lock.
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::{api, query, update};
+
+use crate::{
+    create_transaction_atomic, get_balance, safe_add, safe_sub, set_balance, validate_account, validate_amount,
+    Account, TransactionType, TransferError, NEXT_STAKE_ID, STAKES,
+};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MIN_LOCK_DAYS: u32 = 30; // Also the least remaining lock that still carries voting power
+const MAX_LOCK_DAYS: u32 = 4 * 365; // A stake locked this long votes with twice its amount
+const MAX_AGE_DAYS: u64 = 4 * 365; // Ageing this long adds the full age bonus
+const MAX_AGE_BONUS_PERCENT: u128 = 25;
+const MAX_STAKES_PER_OWNER: usize = 100;
+
+// Subaccount of the ledger canister that holds all staked LGT
+const STAKING_SUBACCOUNT: [u8; 32] = [1; 32];
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum StakeState {
+    Locked { dissolve_delay: u64, aging_since: u64 },
+    Dissolving { unlocks_at: u64 },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Stake {
+    pub id: u64,
+    pub owner: Account, // Receives the LGT back on unstake
+    pub amount: u128,
+    pub created_at: u64,
+    pub state: StakeState,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StakeArgs {
+    pub from_subaccount: Option<Vec<u8>>,
+    pub amount: u128,
+    pub lock_days: u32,
+    pub start_dissolving: bool, // Count down right away instead of staying locked
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StakeInfo {
+    pub stake: Stake,
+    pub voting_power: u128,
+}
+
+fn staking_error(error_code: u128, message: &str) -> TransferError {
+    TransferError::GenericError {
+        error_code,
+        message: message.to_string(),
+    }
+}
+
+fn staking_account() -> Account {
+    Account {
+        owner: api::id(),
+        subaccount: Some(STAKING_SUBACCOUNT.to_vec()),
+    }
+}
+
+fn next_stake_id() -> u64 {
+    NEXT_STAKE_ID.with(|c| {
+        let mut counter = c.borrow_mut();
+        let id = *counter.get();
+        if counter.set(id.saturating_add(1)).is_err() {
+            ic_cdk::trap("Failed to persist stake counter");
+        }
+        id
+    })
+}
+
+fn stakes_of(owner: Principal) -> Vec<Stake> {
+    STAKES.with(|s| s.borrow().range((owner, 0)..=(owner, u64::MAX)).map(|(_, stake)| stake).collect())
+}
+
+fn get_stake(owner: Principal, id: u64) -> Result<Stake, TransferError> {
+    STAKES
+        .with(|s| s.borrow().get(&(owner, id)))
+        .ok_or(staking_error(1011, "Stake not found"))
+}
+
+fn save_stake(stake: &Stake) {
+    STAKES.with(|s| s.borrow_mut().insert((stake.owner.owner, stake.id), stake.clone()));
+}
+
+fn validate_lock_days(lock_days: u32) -> Result<(), TransferError> {
+    if !(MIN_LOCK_DAYS..=MAX_LOCK_DAYS).contains(&lock_days) {
+        return Err(staking_error(
+            1012,
+            &format!("Lock period must be between {} and {} days", MIN_LOCK_DAYS, MAX_LOCK_DAYS),
+        ));
+    }
+    Ok(())
+}
+
+// Move LGT between an owner and the staking account; staking is free of fees
+fn move_funds(from: &Account, to: &Account, amount: u128, memo: String) -> Result<u64, TransferError> {
+    let from_balance = get_balance(from);
+    if from_balance < amount {
+        return Err(TransferError::InsufficientFunds { balance: from_balance });
+    }
+
+    let new_to_balance = safe_add(get_balance(to), amount)?;
+    set_balance(from, safe_sub(from_balance, amount)?)?;
+    set_balance(to, new_to_balance)?;
+
+    create_transaction_atomic(
+        from.clone(),
+        to.clone(),
+        amount,
+        0,
+        Some(memo.into_bytes()),
+        TransactionType::Transfer,
+        [0u8; 32], // Staking transfers don't need deduplication
+        None,
+    )
+}
+
+fn remaining_lock(state: &StakeState, now: u64) -> u64 {
+    match state {
+        StakeState::Locked { dissolve_delay, .. } => *dissolve_delay,
+        StakeState::Dissolving { unlocks_at } => unlocks_at.saturating_sub(now),
+    }
+}
+
+// Amount scaled up to 2x by the remaining lock, then up to +25% by age.
+// Stakes close to unlocking carry no weight, so tokens cannot vote and then
+// leave before the vote is over.
+fn voting_power(stake: &Stake, now: u64) -> u128 {
+    let remaining = remaining_lock(&stake.state, now);
+    if remaining < MIN_LOCK_DAYS as u64 * NANOS_PER_DAY {
+        return 0;
+    }
+
+    let max_lock = MAX_LOCK_DAYS as u128 * NANOS_PER_DAY as u128;
+    let remaining = (remaining as u128).min(max_lock);
+    let locked_power = stake.amount.saturating_add(stake.amount.saturating_mul(remaining) / max_lock);
+
+    let max_age = MAX_AGE_DAYS as u128 * NANOS_PER_DAY as u128;
+    let age = match stake.state {
+        StakeState::Locked { aging_since, .. } => (now.saturating_sub(aging_since) as u128).min(max_age),
+        StakeState::Dissolving { .. } => 0,
+    };
+    let age_bonus = locked_power.saturating_mul(age).saturating_mul(MAX_AGE_BONUS_PERCENT) / (max_age * 100);
+
+    locked_power.saturating_add(age_bonus)
+}
+
+fn begin_dissolving(stake: &mut Stake, now: u64) -> Result<(), TransferError> {
+    match stake.state {
+        StakeState::Locked { dissolve_delay, .. } => {
+            stake.state = StakeState::Dissolving { unlocks_at: now.saturating_add(dissolve_delay) };
+            Ok(())
+        }
+        StakeState::Dissolving { .. } => Err(staking_error(1013, "Stake is already dissolving")),
+    }
+}
+
+// Freeze the countdown; the stake starts ageing again from now
+fn halt_dissolving(stake: &mut Stake, now: u64) -> Result<(), TransferError> {
+    match stake.state {
+        StakeState::Dissolving { unlocks_at } if unlocks_at > now => {
+            stake.state = StakeState::Locked { dissolve_delay: unlocks_at - now, aging_since: now };
+            Ok(())
+        }
+        StakeState::Dissolving { .. } => Err(staking_error(1014, "Stake has already unlocked")),
+        StakeState::Locked { .. } => Err(staking_error(1013, "Stake is not dissolving")),
+    }
+}
+
+fn check_unlocked(stake: &Stake, now: u64) -> Result<(), TransferError> {
+    match stake.state {
+        StakeState::Dissolving { unlocks_at } if unlocks_at <= now => Ok(()),
+        StakeState::Dissolving { .. } => Err(staking_error(1014, "Stake is still dissolving")),
+        StakeState::Locked { .. } => Err(staking_error(1014, "Stake is locked; start dissolving it first")),
+    }
+}
+
+#[update]
+fn stake(args: StakeArgs) -> Result<Stake, TransferError> {
+    validate_amount(args.amount)?;
+    validate_lock_days(args.lock_days)?;
+
+    let owner = Account {
+        owner: api::caller(),
+        subaccount: args.from_subaccount,
+    };
+    validate_account(&owner)?;
+
+    if stakes_of(owner.owner).len() >= MAX_STAKES_PER_OWNER {
+        return Err(staking_error(1015, "Too many stakes for this principal"));
+    }
+
+    let now = api::time();
+    let id = next_stake_id();
+    move_funds(&owner, &staking_account(), args.amount, format!("STAKE: {}", id))?;
+
+    let lock = args.lock_days as u64 * NANOS_PER_DAY;
+    let state = if args.start_dissolving {
+        StakeState::Dissolving { unlocks_at: now + lock }
+    } else {
+        StakeState::Locked { dissolve_delay: lock, aging_since: now }
+    };
+
+    let stake = Stake {
+        id,
+        owner,
+        amount: args.amount,
+        created_at: now,
+        state,
+    };
+    save_stake(&stake);
+    Ok(stake)
+}
+
+#[update]
+fn start_dissolving(stake_id: u64) -> Result<Stake, TransferError> {
+    let mut stake = get_stake(api::caller(), stake_id)?;
+    begin_dissolving(&mut stake, api::time())?;
+    save_stake(&stake);
+    Ok(stake)
+}
+
+#[update]
+fn stop_dissolving(stake_id: u64) -> Result<Stake, TransferError> {
+    let mut stake = get_stake(api::caller(), stake_id)?;
+    halt_dissolving(&mut stake, api::time())?;
+    save_stake(&stake);
+    Ok(stake)
+}
+
+// Return an unlocked stake to the account it came from
+#[update]
+fn unstake(stake_id: u64) -> Result<u64, TransferError> {
+    let stake = get_stake(api::caller(), stake_id)?;
+    check_unlocked(&stake, api::time())?;
+
+    let tx_id = move_funds(&staking_account(), &stake.owner, stake.amount, format!("UNSTAKE: {}", stake.id))?;
+    STAKES.with(|s| s.borrow_mut().remove(&(stake.owner.owner, stake.id)));
+    Ok(tx_id)
+}
+
+#[query]
+fn get_stakes(owner: Principal) -> Vec<StakeInfo> {
+    let now = api::time();
+    stakes_of(owner)
+        .into_iter()
+        .map(|stake| StakeInfo {
+            voting_power: voting_power(&stake, now),
+            stake,
+        })
+        .collect()
+}
+
+#[query]
+fn get_voting_power(owner: Principal) -> u128 {
+    let now = api::time();
+    stakes_of(owner)
+        .iter()
+        .fold(0u128, |total, stake| total.saturating_add(voting_power(stake, now)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = NANOS_PER_DAY;
+
+    fn stake_with(amount: u128, state: StakeState) -> Stake {
+        Stake {
+            id: 0,
+            owner: Account { owner: Principal::from_slice(&[1]), subaccount: None },
+            amount,
+            created_at: 0,
+            state,
+        }
+    }
+
+    #[test]
+    fn test_voting_power_scales_with_lock_and_age() {
+        let now = 10_000 * DAY;
+        let locked = |days: u32, aged: u64| {
+            stake_with(1_000, StakeState::Locked { dissolve_delay: days as u64 * DAY, aging_since: now - aged * DAY })
+        };
+
+        assert_eq!(voting_power(&locked(MAX_LOCK_DAYS, 0), now), 2_000);
+        assert_eq!(voting_power(&locked(MAX_LOCK_DAYS / 2, 0), now), 1_500);
+        assert_eq!(voting_power(&locked(MAX_LOCK_DAYS, MAX_AGE_DAYS), now), 2_500);
+        assert_eq!(voting_power(&locked(MAX_LOCK_DAYS, 2 * MAX_AGE_DAYS), now), 2_500);
+
+        // Dissolving stakes lose weight as they approach their unlock time
+        let dissolving = |days_left: u64| stake_with(1_000, StakeState::Dissolving { unlocks_at: now + days_left * DAY });
+        assert!(voting_power(&dissolving(365), now) > voting_power(&dissolving(60), now));
+        assert_eq!(voting_power(&dissolving(MIN_LOCK_DAYS as u64 - 1), now), 0);
+    }
+
+    #[test]
+    fn test_dissolve_lifecycle() {
+        let mut stake = stake_with(1_000, StakeState::Locked { dissolve_delay: 90 * DAY, aging_since: 0 });
+        assert!(check_unlocked(&stake, 1_000 * DAY).is_err());
+        assert!(halt_dissolving(&mut stake, 0).is_err());
+
+        begin_dissolving(&mut stake, 10 * DAY).unwrap();
+        assert_eq!(stake.state, StakeState::Dissolving { unlocks_at: 100 * DAY });
+        assert!(begin_dissolving(&mut stake, 10 * DAY).is_err());
+        assert!(check_unlocked(&stake, 99 * DAY).is_err());
+
+        // Stopping keeps the remaining delay and restarts ageing
+        halt_dissolving(&mut stake, 40 * DAY).unwrap();
+        assert_eq!(stake.state, StakeState::Locked { dissolve_delay: 60 * DAY, aging_since: 40 * DAY });
+
+        begin_dissolving(&mut stake, 50 * DAY).unwrap();
+        assert!(check_unlocked(&stake, 110 * DAY).is_ok());
+        assert!(halt_dissolving(&mut stake, 110 * DAY).is_err());
+    }
+
+    #[test]
+    fn test_lock_period_bounds() {
+        assert!(validate_lock_days(MIN_LOCK_DAYS - 1).is_err());
+        assert!(validate_lock_days(MIN_LOCK_DAYS).is_ok());
+        assert!(validate_lock_days(MAX_LOCK_DAYS).is_ok());
+        assert!(validate_lock_days(MAX_LOCK_DAYS + 1).is_err());
+    }
+}
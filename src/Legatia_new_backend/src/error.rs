@@ -0,0 +1,101 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::types::FamilyRole;
+use crate::validation::ValidationError;
+
+// Errors returned by the Candid API. Variants carry codes and identifiers rather than
+// prose so clients can branch on them and localise the message themselves.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum BackendError {
+    Unauthenticated,
+    NotFound { kind: String, id: String },
+    // The lowest family role that allows the action; None when no role does
+    Unauthorized { required_role: Option<FamilyRole> },
+    Validation { field: String, reason: String },
+    // An uploaded document could not be parsed; `line` is 1-based and 0 when no single line is at fault
+    Malformed { field: String, line: u64, reason: String },
+    Conflict { reason: String },
+    RateLimited { retry_after_secs: u64 },
+    // A call to another canister (the LGT ledger, the management canister) failed
+    ExternalCall { reason: String },
+}
+
+impl BackendError {
+    pub fn not_found(kind: &str, id: impl Into<String>) -> Self {
+        BackendError::NotFound { kind: kind.to_string(), id: id.into() }
+    }
+
+    pub fn requires(role: FamilyRole) -> Self {
+        BackendError::Unauthorized { required_role: Some(role) }
+    }
+
+    pub fn forbidden() -> Self {
+        BackendError::Unauthorized { required_role: None }
+    }
+
+    pub fn validation(field: &str, reason: &str) -> Self {
+        BackendError::Validation { field: field.to_string(), reason: reason.to_string() }
+    }
+
+    pub fn malformed(field: &str, line: u64, reason: &str) -> Self {
+        BackendError::Malformed { field: field.to_string(), line, reason: reason.to_string() }
+    }
+
+    pub fn conflict(reason: &str) -> Self {
+        BackendError::Conflict { reason: reason.to_string() }
+    }
+
+    pub fn external(reason: impl Into<String>) -> Self {
+        BackendError::ExternalCall { reason: reason.into() }
+    }
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::Unauthenticated => write!(f, "Authentication required"),
+            BackendError::NotFound { kind, id } => write!(f, "Not found: {} {}", kind, id),
+            BackendError::Unauthorized { required_role: Some(role) } => write!(f, "Requires the {:?} role", role),
+            BackendError::Unauthorized { required_role: None } => write!(f, "Not authorized"),
+            BackendError::Validation { field, reason } => write!(f, "Invalid {}: {}", field, reason),
+            BackendError::Malformed { field, line, reason } => write!(f, "Malformed {} at line {}: {}", field, line, reason),
+            BackendError::Conflict { reason } => write!(f, "Conflict: {}", reason),
+            BackendError::RateLimited { retry_after_secs } => write!(f, "Rate limited; retry in {} seconds", retry_after_secs),
+            BackendError::ExternalCall { reason } => write!(f, "External call failed: {}", reason),
+        }
+    }
+}
+
+impl From<ValidationError> for BackendError {
+    fn from(error: ValidationError) -> Self {
+        let (field, reason) = match error {
+            ValidationError::TooShort(field) => (field, "too_short"),
+            ValidationError::TooLong(field) => (field, "too_long"),
+            ValidationError::InvalidCharacters(field) => (field, "invalid_characters"),
+            ValidationError::Empty(field) => (field, "empty"),
+        };
+        BackendError::Validation { field, reason: reason.to_string() }
+    }
+}
+
+// Lets code that still reports plain strings, such as proposal results, use `?`
+impl From<BackendError> for String {
+    fn from(error: BackendError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_errors_keep_their_field() {
+        let error = BackendError::from(crate::validation::validate_name("", "full_name").unwrap_err());
+        assert_eq!(error, BackendError::validation("full_name", "empty"));
+
+        let error = BackendError::from(crate::validation::validate_name("<b>", "birth_city").unwrap_err());
+        assert_eq!(error, BackendError::validation("birth_city", "invalid_characters"));
+    }
+}
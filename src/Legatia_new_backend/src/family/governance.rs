@@ -0,0 +1,475 @@
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk_macros::*;
+
+use crate::types::{
+    Family, FamilyGovernanceConfig, FamilyProposal, FamilyProposalStatus, FamilyProposalType,
+    CreateFamilyProposalRequest, DEV_MODE
+};
+use crate::storage::{FAMILY_PROPOSALS, GOVERNANCE_CONFIGS, generate_id};
+use crate::error::BackendError;
+use super::{
+    store, get_family_roster, has_family_access, add_family_member_internal,
+    remove_family_member_internal, set_family_visibility_internal
+};
+use super::roles::{require_permission, Permission};
+use super::succession::transfer_ownership;
+use super::treasury::{queue_governed_spend, validate_spend};
+
+const NANOS_PER_HOUR: u64 = 60 * 60 * 1_000_000_000;
+const NANOS_PER_DAY: u64 = 24 * NANOS_PER_HOUR;
+const MAX_VOTING_PERIOD_DAYS: u32 = 30;
+const MAX_PROPOSAL_COOLDOWN_HOURS: u32 = 30 * 24;
+
+// Where a proposal stands given the votes cast so far
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    Pending,
+    Pass,
+    Reject,
+    Expire,
+}
+
+#[update]
+pub fn create_family_proposal(request: CreateFamilyProposalRequest) -> Result<FamilyProposal, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    crate::validation::validate_name(&request.title, "title")?;
+    crate::validation::validate_description(&request.description)?;
+
+    let family = get_family_roster(&request.family_id)?;
+    if !family_voters(&family).contains(&caller) {
+        return Err(BackendError::forbidden());
+    }
+    validate_proposal(&family, &request.proposal_type)?;
+
+    let config = get_governance_config_internal(&request.family_id);
+    let now = api::time();
+    check_cooldown(last_proposal_at(&request.family_id, caller), now, &config)?;
+
+    // Members added and payments made by proposal always belong to the proposing family
+    let proposal_type = match request.proposal_type {
+        FamilyProposalType::AddMember(mut member) => {
+            member.family_id = request.family_id.clone();
+            FamilyProposalType::AddMember(member)
+        }
+        FamilyProposalType::SpendTreasury(mut spend) => {
+            spend.family_id = request.family_id.clone();
+            FamilyProposalType::SpendTreasury(spend)
+        }
+        other => other,
+    };
+
+    let proposal = FamilyProposal {
+        id: generate_id(),
+        family_id: request.family_id,
+        proposer: caller,
+        proposal_type,
+        title: request.title,
+        description: request.description,
+        votes: vec![(caller, true)], // The proposer votes in favour
+        status: FamilyProposalStatus::Active,
+        created_at: now,
+        voting_ends: now + config.voting_period_days as u64 * NANOS_PER_DAY,
+        executed_at: None,
+        execution_result: None,
+        voting_threshold_percent: Some(config.voting_threshold_percent),
+    };
+
+    Ok(settle(proposal, &family, &config, now))
+}
+
+#[update]
+pub fn vote_on_family_proposal(proposal_id: String, approve: bool) -> Result<FamilyProposal, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let mut proposal = FAMILY_PROPOSALS.with(|p| p.borrow().get(&proposal_id))
+        .ok_or_else(|| BackendError::not_found("proposal", &proposal_id))?;
+    let family = get_family_roster(&proposal.family_id)?;
+    let config = get_governance_config_internal(&proposal.family_id);
+    let now = api::time();
+
+    if proposal.status != FamilyProposalStatus::Active || now >= proposal.voting_ends {
+        return Err(BackendError::conflict("voting_closed"));
+    }
+    if !family_voters(&family).contains(&caller) {
+        return Err(BackendError::forbidden());
+    }
+
+    proposal.votes.retain(|(voter, _)| *voter != caller);
+    proposal.votes.push((caller, approve));
+
+    Ok(settle(proposal, &family, &config, now))
+}
+
+#[query]
+pub fn get_family_proposals(family_id: String) -> Result<Vec<FamilyProposal>, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    let now = api::time();
+    let mut proposals: Vec<FamilyProposal> = FAMILY_PROPOSALS.with(|p| {
+        p.borrow()
+            .iter()
+            .map(|(_, proposal)| proposal)
+            .filter(|proposal| proposal.family_id == family_id)
+            .collect()
+    });
+
+    // Proposals past their deadline read as expired even before anyone touches them
+    for proposal in proposals.iter_mut() {
+        if proposal.status == FamilyProposalStatus::Active && now >= proposal.voting_ends {
+            proposal.status = FamilyProposalStatus::Expired;
+        }
+    }
+
+    proposals.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+    Ok(proposals)
+}
+
+#[query]
+pub fn get_family_governance_config(family_id: String) -> Result<FamilyGovernanceConfig, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    Ok(get_governance_config_internal(&family_id))
+}
+
+// Internal helper functions
+pub fn get_governance_config_internal(family_id: &str) -> FamilyGovernanceConfig {
+    GOVERNANCE_CONFIGS.with(|c| c.borrow().get(&family_id.to_string())).unwrap_or_default()
+}
+
+// The owner and every linked member get one vote each
+pub fn family_voters(family: &Family) -> Vec<Principal> {
+    let mut voters: Vec<Principal> = family.members.iter()
+        .filter_map(|member| member.profile_principal)
+        .chain(std::iter::once(family.admin))
+        .collect();
+    voters.sort();
+    voters.dedup();
+    voters
+}
+
+pub fn decide(votes: &[(Principal, bool)], voters: &[Principal], threshold_percent: u8, now: u64, voting_ends: u64) -> Decision {
+    let counted = votes.iter().filter(|(voter, _)| voters.contains(voter));
+    let (yes, no) = counted.fold((0u64, 0u64), |(yes, no), (_, approve)| {
+        if *approve { (yes + 1, no) } else { (yes, no + 1) }
+    });
+    let needed = threshold_percent as u64 * voters.len() as u64;
+
+    if yes * 100 >= needed {
+        Decision::Pass
+    } else if (voters.len() as u64 - no) * 100 < needed {
+        // Even if everyone left votes yes the threshold can no longer be reached
+        Decision::Reject
+    } else if now >= voting_ends {
+        Decision::Expire
+    } else {
+        Decision::Pending
+    }
+}
+
+fn last_proposal_at(family_id: &str, proposer: Principal) -> Option<u64> {
+    FAMILY_PROPOSALS.with(|p| {
+        p.borrow()
+            .iter()
+            .map(|(_, proposal)| proposal)
+            .filter(|proposal| proposal.family_id == family_id && proposal.proposer == proposer)
+            .map(|proposal| proposal.created_at)
+            .max()
+    })
+}
+
+pub fn validate_config(config: &FamilyGovernanceConfig) -> Result<(), BackendError> {
+    if config.voting_threshold_percent == 0 || config.voting_threshold_percent > 100 {
+        return Err(BackendError::validation("voting_threshold_percent", "out_of_range"));
+    }
+    if config.voting_period_days == 0 || config.voting_period_days > MAX_VOTING_PERIOD_DAYS {
+        return Err(BackendError::validation("voting_period_days", "out_of_range"));
+    }
+    if config.proposal_cooldown_hours > MAX_PROPOSAL_COOLDOWN_HOURS {
+        return Err(BackendError::validation("proposal_cooldown_hours", "out_of_range"));
+    }
+    Ok(())
+}
+
+pub fn check_cooldown(last_proposal: Option<u64>, now: u64, config: &FamilyGovernanceConfig) -> Result<(), BackendError> {
+    let cooldown = config.proposal_cooldown_hours as u64 * NANOS_PER_HOUR;
+    match last_proposal {
+        Some(last) if now.saturating_sub(last) < cooldown => Err(BackendError::RateLimited {
+            retry_after_secs: (cooldown - now.saturating_sub(last)).div_ceil(1_000_000_000),
+        }),
+        _ => Ok(()),
+    }
+}
+
+pub fn validate_proposal(family: &Family, proposal_type: &FamilyProposalType) -> Result<(), BackendError> {
+    match proposal_type {
+        FamilyProposalType::ChangeAdmin(new_admin) => {
+            if *new_admin == family.admin {
+                return Err(BackendError::validation("proposal_type", "already_owner"));
+            }
+            if !has_family_access(family, *new_admin) {
+                return Err(BackendError::validation("proposal_type", "not_a_family_member"));
+            }
+        }
+        FamilyProposalType::ModifyVisibility(is_visible) => {
+            if *is_visible == family.is_visible {
+                return Err(BackendError::validation("proposal_type", "no_change"));
+            }
+        }
+        FamilyProposalType::AddMember(member) => {
+            crate::validation::validate_name(&member.full_name, "full_name")?;
+        }
+        FamilyProposalType::RemoveMember(member_id) => {
+            if !family.members.iter().any(|m| &m.id == member_id) {
+                return Err(BackendError::not_found("member", member_id));
+            }
+        }
+        FamilyProposalType::ValidateEvent { member_id, event_id } => {
+            if !family.members.iter().any(|m| &m.id == member_id) {
+                return Err(BackendError::not_found("member", member_id));
+            }
+            if store::load_event(&family.id, member_id, event_id).is_none() {
+                return Err(BackendError::not_found("event", event_id));
+            }
+        }
+        FamilyProposalType::SpendTreasury(spend) => validate_spend(spend)?,
+        FamilyProposalType::ChangeGovernanceConfig(config) => {
+            validate_config(config)?;
+            if *config == get_governance_config_internal(&family.id) {
+                return Err(BackendError::validation("proposal_type", "no_change"));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Apply the current decision to a proposal, executing it if it passed, and store it.
+// The threshold is the one the proposal was created under, so later config changes
+// do not move the goalposts for proposals already open.
+fn settle(mut proposal: FamilyProposal, family: &Family, config: &FamilyGovernanceConfig, now: u64) -> FamilyProposal {
+    let decision = decide(
+        &proposal.votes,
+        &family_voters(family),
+        proposal.voting_threshold_percent.unwrap_or(config.voting_threshold_percent),
+        now,
+        proposal.voting_ends,
+    );
+
+    let outcome = match decision {
+        Decision::Pending => None,
+        Decision::Pass => Some(execute(&proposal, now)),
+        Decision::Reject => {
+            proposal.status = FamilyProposalStatus::Rejected;
+            reject(&proposal)
+        }
+        Decision::Expire => {
+            proposal.status = FamilyProposalStatus::Expired;
+            None
+        }
+    };
+
+    if let Some(result) = outcome {
+        proposal.executed_at = Some(now);
+        match result {
+            Ok(message) => {
+                if decision == Decision::Pass {
+                    proposal.status = FamilyProposalStatus::Executed;
+                }
+                proposal.execution_result = Some(message);
+            }
+            Err(error) => {
+                proposal.status = FamilyProposalStatus::Failed;
+                proposal.execution_result = Some(error);
+            }
+        }
+    }
+
+    FAMILY_PROPOSALS.with(|p| p.borrow_mut().insert(proposal.id.clone(), proposal.clone()));
+    proposal
+}
+
+fn execute(proposal: &FamilyProposal, now: u64) -> Result<String, String> {
+    let family_id = &proposal.family_id;
+    match &proposal.proposal_type {
+        FamilyProposalType::ChangeAdmin(new_admin) => {
+            transfer_ownership(family_id, *new_admin, None, now)?;
+            Ok("Family ownership transferred".to_string())
+        }
+        FamilyProposalType::ModifyVisibility(is_visible) => {
+            set_family_visibility_internal(family_id, *is_visible)?;
+            Ok("Family visibility updated".to_string())
+        }
+        FamilyProposalType::AddMember(request) => {
+            let member = add_family_member_internal(request.clone(), proposal.proposer)?;
+            Ok(format!("Member {} added", member.id))
+        }
+        FamilyProposalType::RemoveMember(member_id) => {
+            remove_family_member_internal(family_id, member_id)?;
+            Ok("Member removed".to_string())
+        }
+        FamilyProposalType::ValidateEvent { .. } => Ok("Event confirmed by family vote".to_string()),
+        FamilyProposalType::SpendTreasury(spend) => Ok(queue_governed_spend(proposal, spend, now)?),
+        FamilyProposalType::ChangeGovernanceConfig(config) => {
+            validate_config(config)?;
+            GOVERNANCE_CONFIGS.with(|c| c.borrow_mut().insert(family_id.clone(), config.clone()));
+            Ok("Governance settings updated".to_string())
+        }
+    }
+}
+
+// Only disputed events have a consequence when the family votes a proposal down
+fn reject(proposal: &FamilyProposal) -> Option<Result<String, String>> {
+    match &proposal.proposal_type {
+        FamilyProposalType::ValidateEvent { member_id, event_id } => {
+            let result = remove_event(&proposal.family_id, member_id, event_id);
+            Some(result.map(|_| "Disputed event removed".to_string()).map_err(String::from))
+        }
+        _ => None,
+    }
+}
+
+fn remove_event(family_id: &str, member_id: &str, event_id: &str) -> Result<(), BackendError> {
+    let mut family = store::load_family_header(family_id)
+        .ok_or_else(|| BackendError::not_found("family", family_id))?;
+    if !store::member_exists(family_id, member_id) {
+        return Err(BackendError::not_found("member", member_id));
+    }
+    if store::load_event(family_id, member_id, event_id).is_none() {
+        return Err(BackendError::not_found("event", event_id));
+    }
+
+    store::remove_event(family_id, member_id, event_id);
+    family.updated_at = api::time();
+    store::save_family_header(&family);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::test_support::{event, member, user};
+
+    fn family() -> Family {
+        Family {
+            id: "f1".to_string(),
+            admin: user(1),
+            members: vec![
+                member("self", Some(user(1))),
+                member("m2", Some(user(2))),
+                member("m3", Some(user(3))),
+                member("m4", Some(user(4))),
+                member("ghost", None),
+            ],
+            is_visible: true,
+            ..Family::default()
+        }
+    }
+
+    #[test]
+    fn test_voters_are_unique_linked_members() {
+        assert_eq!(family_voters(&family()), vec![user(1), user(2), user(3), user(4)]);
+    }
+
+    #[test]
+    fn test_decide() {
+        let voters = family_voters(&family());
+
+        assert_eq!(decide(&[(user(2), true)], &voters, 51, 0, 10), Decision::Pending);
+        assert_eq!(decide(&[(user(2), true), (user(3), true)], &voters, 51, 0, 10), Decision::Pending);
+        assert_eq!(decide(&[(user(2), true), (user(3), true), (user(4), true)], &voters, 51, 0, 10), Decision::Pass);
+        assert_eq!(decide(&[(user(2), true), (user(3), true)], &voters, 50, 0, 10), Decision::Pass);
+
+        // Two no votes out of four make 51% unreachable
+        assert_eq!(decide(&[(user(2), false), (user(3), false)], &voters, 51, 0, 10), Decision::Reject);
+
+        // Votes from outsiders do not count
+        assert_eq!(decide(&[(user(9), true), (user(8), true), (user(7), true)], &voters, 51, 0, 10), Decision::Pending);
+
+        assert_eq!(decide(&[(user(2), true)], &voters, 51, 10, 10), Decision::Expire);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let config = FamilyGovernanceConfig::default();
+        assert!(check_cooldown(None, 0, &config).is_ok());
+        assert_eq!(
+            check_cooldown(Some(0), NANOS_PER_HOUR, &config),
+            Err(BackendError::RateLimited { retry_after_secs: 23 * 60 * 60 })
+        );
+        assert!(check_cooldown(Some(0), 24 * NANOS_PER_HOUR, &config).is_ok());
+    }
+
+    #[test]
+    fn test_validate_proposal() {
+        let family = family();
+
+        assert!(validate_proposal(&family, &FamilyProposalType::ChangeAdmin(user(2))).is_ok());
+        assert!(validate_proposal(&family, &FamilyProposalType::ChangeAdmin(user(1))).is_err());
+        assert!(validate_proposal(&family, &FamilyProposalType::ChangeAdmin(user(9))).is_err());
+        assert!(validate_proposal(&family, &FamilyProposalType::ModifyVisibility(true)).is_err());
+        assert!(validate_proposal(&family, &FamilyProposalType::RemoveMember("ghost".to_string())).is_ok());
+        assert!(validate_proposal(&family, &FamilyProposalType::RemoveMember("nobody".to_string())).is_err());
+
+        let missing_event = FamilyProposalType::ValidateEvent {
+            member_id: "m2".to_string(),
+            event_id: "e1".to_string(),
+        };
+        assert!(validate_proposal(&family, &missing_event).is_err());
+        // Events are looked up in storage, not on the loaded roster
+        store::save_event("f1", &event("e1", "m2", "1900-01-01")).unwrap();
+        assert!(validate_proposal(&family, &missing_event).is_ok());
+
+        let config = |threshold| FamilyGovernanceConfig { voting_threshold_percent: threshold, ..Default::default() };
+        assert!(validate_proposal(&family, &FamilyProposalType::ChangeGovernanceConfig(config(75))).is_ok());
+        assert!(validate_proposal(&family, &FamilyProposalType::ChangeGovernanceConfig(config(51))).is_err());
+        assert!(validate_proposal(&family, &FamilyProposalType::ChangeGovernanceConfig(config(0))).is_err());
+    }
+
+    #[test]
+    fn test_open_proposals_keep_their_threshold() {
+        let family = family();
+        let proposal = FamilyProposal {
+            id: "p1".to_string(),
+            family_id: "f1".to_string(),
+            proposer: user(2),
+            proposal_type: FamilyProposalType::ModifyVisibility(false),
+            title: String::new(),
+            description: String::new(),
+            votes: vec![(user(2), true), (user(3), true)],
+            status: FamilyProposalStatus::Active,
+            created_at: 0,
+            voting_ends: 10,
+            executed_at: None,
+            execution_result: None,
+            voting_threshold_percent: Some(75),
+        };
+
+        // Two of four voters would pass at 50%, but the proposal was opened at 75%
+        let lowered = FamilyGovernanceConfig { voting_threshold_percent: 50, ..Default::default() };
+        let settled = settle(proposal, &family, &lowered, 0);
+        assert_eq!(settled.status, FamilyProposalStatus::Active);
+    }
+}
@@ -0,0 +1,397 @@
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::types::{
+    Family, FamilyMember, FamilyRelationship, RelationshipType, RelatedMember, KinshipResult, FamilyRole, DEV_MODE
+};
+use crate::error::BackendError;
+use super::{get_family_roster, has_family_access};
+use super::relationships::{get_relationships_internal, parents_of, children_of, spouses_of};
+
+pub const MAX_TRAVERSAL_DEPTH: u32 = 32;
+
+#[query]
+pub fn get_ancestors(family_id: String, member_id: String, depth: Option<u32>) -> Result<Vec<RelatedMember>, BackendError> {
+    let (family, relationships) = load_family_graph(&family_id, &member_id)?;
+    let depth = depth.unwrap_or(MAX_TRAVERSAL_DEPTH).min(MAX_TRAVERSAL_DEPTH);
+
+    Ok(to_related_members(&family, walk_lineage(&relationships, &member_id, depth, Direction::Up)))
+}
+
+#[query]
+pub fn get_descendants(family_id: String, member_id: String, depth: Option<u32>) -> Result<Vec<RelatedMember>, BackendError> {
+    let (family, relationships) = load_family_graph(&family_id, &member_id)?;
+    let depth = depth.unwrap_or(MAX_TRAVERSAL_DEPTH).min(MAX_TRAVERSAL_DEPTH);
+
+    Ok(to_related_members(&family, walk_lineage(&relationships, &member_id, depth, Direction::Down)))
+}
+
+#[query]
+pub fn get_relationship_path(family_id: String, from_member_id: String, to_member_id: String) -> Result<KinshipResult, BackendError> {
+    let (family, relationships) = load_family_graph(&family_id, &from_member_id)?;
+    if !family.members.iter().any(|m| m.id == to_member_id) {
+        return Err(BackendError::not_found("member", &to_member_id));
+    }
+
+    find_kinship(&relationships, &family.members, &from_member_id, &to_member_id)
+        .ok_or_else(|| BackendError::not_found("relationship_path", &to_member_id))
+}
+
+fn load_family_graph(family_id: &str, member_id: &str) -> Result<(Family, Vec<FamilyRelationship>), BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(family_id)?;
+
+    if !has_family_access(&family, caller) {
+        return Err(BackendError::requires(FamilyRole::Viewer));
+    }
+
+    if !family.members.iter().any(|m| m.id == member_id) {
+        return Err(BackendError::not_found("member", member_id));
+    }
+
+    let relationships = get_relationships_internal(family_id);
+    Ok((family, relationships))
+}
+
+fn to_related_members(family: &Family, lineage: Vec<LineageStep>) -> Vec<RelatedMember> {
+    lineage.into_iter()
+        .filter_map(|step| {
+            family.members.iter().find(|m| m.id == step.member_id).map(|member| RelatedMember {
+                member_id: step.member_id,
+                full_name: member.full_name.clone(),
+                generation: step.generation,
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+}
+
+struct LineageStep {
+    member_id: String,
+    generation: u32,
+    via: String, // The member one generation closer to the origin
+}
+
+// Breadth-first walk over parent edges, so every member is reported at its closest generation
+fn walk_lineage(relationships: &[FamilyRelationship], origin: &str, max_depth: u32, direction: Direction) -> Vec<LineageStep> {
+    let mut steps: Vec<LineageStep> = Vec::new();
+    let mut visited = HashSet::from([origin.to_string()]);
+    let mut queue = VecDeque::from([(origin.to_string(), 0u32)]);
+
+    while let Some((current, generation)) = queue.pop_front() {
+        if generation >= max_depth {
+            continue;
+        }
+
+        let next: Vec<String> = match direction {
+            Direction::Up => parents_of(relationships, &current).into_iter().map(|r| r.from_member_id.clone()).collect(),
+            Direction::Down => children_of(relationships, &current).into_iter().map(|r| r.to_member_id.clone()).collect(),
+        };
+
+        for member_id in next {
+            if !visited.insert(member_id.clone()) {
+                continue;
+            }
+            steps.push(LineageStep {
+                member_id: member_id.clone(),
+                generation: generation + 1,
+                via: current.clone(),
+            });
+            queue.push_back((member_id, generation + 1));
+        }
+    }
+
+    steps
+}
+
+// Map of ancestor ID -> (generation, member one step closer to origin), including the origin itself
+fn ancestor_map(relationships: &[FamilyRelationship], origin: &str) -> HashMap<String, (u32, String)> {
+    let mut map: HashMap<String, (u32, String)> = walk_lineage(relationships, origin, MAX_TRAVERSAL_DEPTH, Direction::Up)
+        .into_iter()
+        .map(|step| (step.member_id, (step.generation, step.via)))
+        .collect();
+    map.insert(origin.to_string(), (0, origin.to_string()));
+    map
+}
+
+// Chain of member IDs from `ancestor` down to the origin of `map`
+fn chain_down(map: &HashMap<String, (u32, String)>, ancestor: &str) -> Vec<String> {
+    let mut chain = vec![ancestor.to_string()];
+    let mut current = ancestor.to_string();
+    while let Some((generation, via)) = map.get(&current) {
+        if *generation == 0 {
+            break;
+        }
+        chain.push(via.clone());
+        current = via.clone();
+    }
+    chain
+}
+
+pub fn find_kinship(
+    relationships: &[FamilyRelationship],
+    members: &[FamilyMember],
+    from_member_id: &str,
+    to_member_id: &str,
+) -> Option<KinshipResult> {
+    if let Some(result) = lineal_kinship(relationships, members, from_member_id, to_member_id) {
+        return Some(result);
+    }
+
+    // Direct marriage or partnership
+    if let Some(edge) = relationships.iter().find(|r| {
+        r.relationship_type == RelationshipType::SpouseOf
+            && ((r.from_member_id == from_member_id && r.to_member_id == to_member_id)
+                || (r.from_member_id == to_member_id && r.to_member_id == from_member_id))
+    }) {
+        let description = if edge.end_date.is_some() { "former spouse" } else { "spouse" };
+        return Some(KinshipResult {
+            from_member_id: from_member_id.to_string(),
+            to_member_id: to_member_id.to_string(),
+            description: description.to_string(),
+            path: vec![from_member_id.to_string(), to_member_id.to_string()],
+            common_ancestor_id: None,
+        });
+    }
+
+    // Relatives of the origin's spouse
+    for spouse in spouses_of(relationships, from_member_id) {
+        if let Some(inner) = lineal_kinship(relationships, members, &spouse, to_member_id) {
+            let description = match inner.description.as_str() {
+                "parent" | "sibling" | "half-sibling" => format!("{}-in-law", inner.description),
+                "child" => "stepchild".to_string(),
+                other => format!("spouse's {}", other),
+            };
+            let mut path = vec![from_member_id.to_string()];
+            path.extend(inner.path);
+            return Some(KinshipResult {
+                from_member_id: from_member_id.to_string(),
+                to_member_id: to_member_id.to_string(),
+                description,
+                path,
+                common_ancestor_id: inner.common_ancestor_id,
+            });
+        }
+    }
+
+    // Spouses of the origin's relatives
+    for spouse in spouses_of(relationships, to_member_id) {
+        if let Some(inner) = lineal_kinship(relationships, members, from_member_id, &spouse) {
+            let description = match inner.description.as_str() {
+                "child" => "child-in-law".to_string(),
+                "sibling" | "half-sibling" => "sibling-in-law".to_string(),
+                "parent" => "step-parent".to_string(),
+                other => format!("{}'s spouse", other),
+            };
+            let mut path = inner.path;
+            path.push(to_member_id.to_string());
+            return Some(KinshipResult {
+                from_member_id: from_member_id.to_string(),
+                to_member_id: to_member_id.to_string(),
+                description,
+                path,
+                common_ancestor_id: inner.common_ancestor_id,
+            });
+        }
+    }
+
+    None
+}
+
+fn lineal_kinship(
+    relationships: &[FamilyRelationship],
+    members: &[FamilyMember],
+    from_member_id: &str,
+    to_member_id: &str,
+) -> Option<KinshipResult> {
+    let from_ancestors = ancestor_map(relationships, from_member_id);
+    let to_ancestors = ancestor_map(relationships, to_member_id);
+
+    // Closest common ancestors; ties are broken by ID so results are stable
+    let mut common: Vec<(u32, u32, &String)> = from_ancestors.iter()
+        .filter_map(|(id, (up, _))| to_ancestors.get(id).map(|(down, _)| (*up, *down, id)))
+        .collect();
+    common.sort_by(|a, b| (a.0 + a.1).cmp(&(b.0 + b.1)).then(a.2.cmp(b.2)));
+
+    let (up, down, ancestor) = *common.first()?;
+    let closest_count = common.iter().filter(|c| c.0 + c.1 == up + down).count();
+    let half = up == 1
+        && down == 1
+        && closest_count == 1
+        && parents_of(relationships, from_member_id).len() >= 2
+        && parents_of(relationships, to_member_id).len() >= 2;
+
+    let sex = members.iter()
+        .find(|m| m.id == to_member_id)
+        .map(|m| m.sex.as_str())
+        .unwrap_or_default();
+
+    let mut path = chain_down(&from_ancestors, ancestor);
+    path.reverse();
+    path.extend(chain_down(&to_ancestors, ancestor).into_iter().skip(1));
+
+    Some(KinshipResult {
+        from_member_id: from_member_id.to_string(),
+        to_member_id: to_member_id.to_string(),
+        description: describe_kinship(up, down, sex, half),
+        path,
+        common_ancestor_id: if up == 0 || down == 0 { None } else { Some(ancestor.clone()) },
+    })
+}
+
+// Describe `to` relative to `from`, given generations from `from` up to the common
+// ancestor (`up`) and from the common ancestor down to `to` (`down`)
+pub fn describe_kinship(up: u32, down: u32, sex: &str, half: bool) -> String {
+    let sex = sex.to_lowercase();
+    let gendered = |male: &str, female: &str, neutral: &str| -> String {
+        match sex.as_str() {
+            "male" | "m" => male.to_string(),
+            "female" | "f" => female.to_string(),
+            _ => neutral.to_string(),
+        }
+    };
+
+    match (up, down) {
+        (0, 0) => "self".to_string(),
+        (0, 1) => "child".to_string(),
+        (0, n) => format!("{}grandchild", great_prefix(n - 2)),
+        (1, 0) => "parent".to_string(),
+        (n, 0) => format!("{}grandparent", great_prefix(n - 2)),
+        (1, 1) => if half { "half-sibling".to_string() } else { "sibling".to_string() },
+        (1, 2) => gendered("nephew", "niece", "niece/nephew"),
+        (1, n) => format!("{}grand{}", great_prefix(n - 3), gendered("nephew", "niece", "niece/nephew")),
+        (2, 1) => gendered("uncle", "aunt", "aunt/uncle"),
+        (n, 1) => format!("{}{}", great_prefix(n - 2), gendered("uncle", "aunt", "aunt/uncle")),
+        (up, down) => {
+            let degree = up.min(down) - 1;
+            let removed = up.abs_diff(down);
+            let cousin = format!("{} cousin", ordinal(degree));
+            match removed {
+                0 => cousin,
+                1 => format!("{} once removed", cousin),
+                2 => format!("{} twice removed", cousin),
+                n => format!("{} {} times removed", cousin, n),
+            }
+        }
+    }
+}
+
+fn great_prefix(count: u32) -> String {
+    match count {
+        0 => String::new(),
+        1 => "great-".to_string(),
+        2 => "great-great-".to_string(),
+        n => format!("{}x great-", n),
+    }
+}
+
+fn ordinal(n: u32) -> String {
+    const WORDS: [&str; 10] = [
+        "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth",
+    ];
+    if (1..=10).contains(&n) {
+        return WORDS[(n - 1) as usize].to_string();
+    }
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RelationshipQualifier;
+
+    fn parent(from: &str, to: &str) -> FamilyRelationship {
+        edge(from, to, RelationshipType::ParentOf)
+    }
+
+    fn edge(from: &str, to: &str, relationship_type: RelationshipType) -> FamilyRelationship {
+        FamilyRelationship {
+            id: format!("{}-{}", from, to),
+            family_id: String::new(),
+            from_member_id: from.to_string(),
+            to_member_id: to.to_string(),
+            relationship_type,
+            qualifier: RelationshipQualifier::Biological,
+            start_date: None,
+            end_date: None,
+            created_at: 0,
+            created_by: Principal::anonymous(),
+        }
+    }
+
+    // Two branches descending from a shared great-grandparent couple
+    fn tree() -> Vec<FamilyRelationship> {
+        vec![
+            parent("gg", "g1"),
+            parent("gg", "g2"),
+            parent("g1", "p1"),
+            parent("g2", "p2"),
+            parent("p1", "a"),
+            parent("p2", "b"),
+            parent("b", "c"),
+            edge("a", "spouse", RelationshipType::SpouseOf),
+        ]
+    }
+
+    #[test]
+    fn test_describe_kinship() {
+        assert_eq!(describe_kinship(0, 1, "", false), "child");
+        assert_eq!(describe_kinship(3, 0, "", false), "great-grandparent");
+        assert_eq!(describe_kinship(1, 1, "", true), "half-sibling");
+        assert_eq!(describe_kinship(2, 1, "female", false), "aunt");
+        assert_eq!(describe_kinship(1, 3, "male", false), "grandnephew");
+        assert_eq!(describe_kinship(2, 2, "", false), "first cousin");
+        assert_eq!(describe_kinship(3, 4, "", false), "second cousin once removed");
+        assert_eq!(describe_kinship(14, 12, "", false), "11th cousin twice removed");
+    }
+
+    #[test]
+    fn test_lineage_walk() {
+        let edges = tree();
+        let ancestors = walk_lineage(&edges, "a", MAX_TRAVERSAL_DEPTH, Direction::Up);
+        let ids: Vec<(&str, u32)> = ancestors.iter().map(|s| (s.member_id.as_str(), s.generation)).collect();
+        assert_eq!(ids, vec![("p1", 1), ("g1", 2), ("gg", 3)]);
+
+        let limited = walk_lineage(&edges, "gg", 2, Direction::Down);
+        assert_eq!(limited.len(), 4);
+    }
+
+    #[test]
+    fn test_find_kinship() {
+        let edges = tree();
+
+        let cousins = find_kinship(&edges, &[], "a", "c").unwrap();
+        assert_eq!(cousins.description, "second cousin once removed");
+        assert_eq!(cousins.common_ancestor_id, Some("gg".to_string()));
+        assert_eq!(cousins.path, vec!["a", "p1", "g1", "gg", "g2", "p2", "b", "c"]);
+
+        let grandparent = find_kinship(&edges, &[], "a", "g1").unwrap();
+        assert_eq!(grandparent.description, "grandparent");
+        assert_eq!(grandparent.common_ancestor_id, None);
+
+        let in_law = find_kinship(&edges, &[], "spouse", "p1").unwrap();
+        assert_eq!(in_law.description, "parent-in-law");
+        assert_eq!(in_law.path, vec!["spouse", "a", "p1"]);
+
+        assert!(find_kinship(&edges, &[], "a", "unknown").is_none());
+    }
+}
@@ -0,0 +1,704 @@
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+use crate::types::{
+    BeginMediaUploadRequest, Family, MediaAsset, MediaKind, MediaLinkRequest, MediaStatus, Page, PageRequest, DEV_MODE
+};
+use crate::storage::{
+    MEDIA_ASSETS, MEDIA_CHUNKS, MEDIA_BY_FAMILY, MEDIA_BY_UPLOADER, OPEN_UPLOADS_BY_ACTIVITY, FAMILY_MEDIA_BYTES, generate_id
+};
+use crate::indexes::{
+    count_with_status, family_media_key, open_upload_key, page_newest_first, reindex, uploader_media_key, UPLOADING
+};
+use crate::pagination::MAX_PAGE_SIZE;
+use crate::error::BackendError;
+use super::{get_family_internal, get_family_roster, store};
+use super::roles::{has_permission, minimum_role, require_permission, Permission};
+
+// Every chunk but the last is exactly this size, keeping each call well under the message limit
+pub const CHUNK_SIZE: u64 = 1024 * 1024;
+const MAX_FILE_NAME_LENGTH: usize = 255;
+// Storage a family may use, counting uploads still in progress
+pub const MAX_FAMILY_MEDIA_BYTES: u64 = 1024 * 1024 * 1024;
+// Uploads a user may have open at once
+pub const MAX_OPEN_UPLOADS_PER_USER: usize = 3;
+// Uploads with no chunk received for this long are dropped along with their chunks
+const STALE_UPLOAD_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MEDIA_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// Stale uploads removed per cleanup run, to stay well within instruction limits
+const MEDIA_CLEANUP_BATCH_SIZE: usize = 20;
+
+// Uploads happen in three steps: begin, one call per chunk, then finish to verify the hash
+#[update]
+pub fn begin_media_upload(request: BeginMediaUploadRequest) -> Result<MediaAsset, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&request.family_id)?;
+    require_permission(&family, caller, Permission::AddEvents)?;
+    let kind = validate_upload(&request)?;
+    check_upload_quota(&request.family_id, caller, request.size)?;
+
+    let now = api::time();
+    let asset = MediaAsset {
+        id: generate_id(),
+        family_id: request.family_id,
+        file_name: request.file_name,
+        mime_type: request.mime_type,
+        kind,
+        size: request.size,
+        chunk_count: chunk_count(request.size),
+        sha256: request.sha256,
+        status: MediaStatus::Uploading,
+        uploaded_by: caller,
+        created_at: now,
+        updated_at: now,
+    };
+
+    save_media(&asset, None);
+    Ok(asset)
+}
+
+// Re-sending a chunk replaces it, so failed calls can simply be retried
+#[update]
+pub fn upload_media_chunk(media_id: String, index: u32, data: Vec<u8>) -> Result<(), BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let mut asset = get_media_internal(&media_id)?;
+    require_uploader(&asset, caller)?;
+    if asset.status != MediaStatus::Uploading {
+        return Err(BackendError::conflict("upload_complete"));
+    }
+    if index >= asset.chunk_count {
+        return Err(BackendError::validation("index", "out_of_range"));
+    }
+    let expected = expected_chunk_len(asset.size, index);
+    if data.len() as u64 != expected {
+        return Err(BackendError::validation("data", "wrong_chunk_size"));
+    }
+
+    MEDIA_CHUNKS.with(|chunks| chunks.borrow_mut().insert(chunk_key(&media_id, index), data));
+    // Each chunk keeps the upload from being cleaned up as stale
+    let old = asset.clone();
+    asset.updated_at = api::time();
+    save_media(&asset, Some(&old));
+    Ok(())
+}
+
+#[update]
+pub fn finish_media_upload(media_id: String) -> Result<MediaAsset, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let mut asset = get_media_internal(&media_id)?;
+    require_uploader(&asset, caller)?;
+    if asset.status != MediaStatus::Uploading {
+        return Err(BackendError::conflict("upload_complete"));
+    }
+
+    let missing = missing_chunks(&asset);
+    if !missing.is_empty() {
+        return Err(BackendError::not_found("media_chunk", chunk_key(&media_id, missing[0])));
+    }
+    // A mismatch leaves the upload open so the bad chunks can be sent again
+    if content_hash(&asset) != asset.sha256 {
+        return Err(BackendError::validation("sha256", "hash_mismatch"));
+    }
+
+    let old = asset.clone();
+    asset.status = MediaStatus::Ready;
+    asset.updated_at = api::time();
+    save_media(&asset, Some(&old));
+    Ok(asset)
+}
+
+#[query]
+pub fn get_media(media_id: String) -> Result<MediaAsset, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let asset = get_media_internal(&media_id)?;
+    let family = get_family_roster(&asset.family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+    Ok(asset)
+}
+
+// Newest first, including uploads still in progress
+#[query]
+pub fn get_family_media(family_id: String, page: PageRequest) -> Result<Page<MediaAsset>, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    media_of(&family_id, &page)
+}
+
+// Downloads mirror uploads: fetch chunks 0..chunk_count and concatenate them
+#[query]
+pub fn get_media_chunk(media_id: String, index: u32) -> Result<Vec<u8>, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let asset = get_media_internal(&media_id)?;
+    let family = get_family_roster(&asset.family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+    if asset.status != MediaStatus::Ready {
+        return Err(BackendError::conflict("upload_incomplete"));
+    }
+
+    read_chunk(&media_id, index)
+}
+
+#[update]
+pub fn attach_media(request: MediaLinkRequest) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let asset = get_media_internal(&request.media_id)?;
+    if asset.status != MediaStatus::Ready {
+        return Err(BackendError::conflict("upload_incomplete"));
+    }
+
+    let mut family = get_family_internal(&asset.family_id)?;
+    let media_ids = linked_media_mut(&mut family, &request, caller)?;
+    if media_ids.contains(&request.media_id) {
+        return Err(BackendError::conflict("already_attached"));
+    }
+    media_ids.push(request.media_id.clone());
+
+    family.updated_at = api::time();
+    save_link_target(&family, &request)?;
+    Ok("Media attached successfully".to_string())
+}
+
+#[update]
+pub fn detach_media(request: MediaLinkRequest) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let asset = get_media_internal(&request.media_id)?;
+    let mut family = get_family_internal(&asset.family_id)?;
+    let media_ids = linked_media_mut(&mut family, &request, caller)?;
+
+    let initial_len = media_ids.len();
+    media_ids.retain(|id| id != &request.media_id);
+    if media_ids.len() == initial_len {
+        return Err(BackendError::not_found("media_link", &request.media_id));
+    }
+
+    family.updated_at = api::time();
+    save_link_target(&family, &request)?;
+    Ok("Media detached successfully".to_string())
+}
+
+// Uploaders can delete their own media; editors and admins can delete any
+#[update]
+pub fn delete_media(media_id: String) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let asset = get_media_internal(&media_id)?;
+    let mut family = get_family_internal(&asset.family_id)?;
+    if asset.uploaded_by != caller && !has_permission(&family, caller, Permission::EditAnyEvent) {
+        return Err(BackendError::requires(minimum_role(Permission::EditAnyEvent)));
+    }
+
+    if unlink_media(&mut family, &media_id)? {
+        family.updated_at = api::time();
+        store::save_family_header(&family);
+    }
+    remove_media_internal(&asset);
+    Ok("Media deleted successfully".to_string())
+}
+
+// Internal helper functions for other modules
+pub fn get_media_internal(media_id: &str) -> Result<MediaAsset, BackendError> {
+    MEDIA_ASSETS.with(|assets| {
+        assets.borrow()
+            .get(&media_id.to_string())
+            .ok_or_else(|| BackendError::not_found("media", media_id))
+    })
+}
+
+pub fn read_chunk(media_id: &str, index: u32) -> Result<Vec<u8>, BackendError> {
+    MEDIA_CHUNKS.with(|chunks| {
+        chunks.borrow()
+            .get(&chunk_key(media_id, index))
+            .ok_or_else(|| BackendError::not_found("media_chunk", chunk_key(media_id, index)))
+    })
+}
+
+pub fn media_kind(mime_type: &str) -> Result<MediaKind, BackendError> {
+    match mime_type {
+        "image/jpeg" | "image/png" | "image/gif" | "image/webp" | "image/tiff" => Ok(MediaKind::Photo),
+        "application/pdf" => Ok(MediaKind::Document),
+        "audio/mpeg" | "audio/mp4" | "audio/ogg" | "audio/wav" | "audio/webm" => Ok(MediaKind::Audio),
+        _ => Err(BackendError::validation("mime_type", "unsupported")),
+    }
+}
+
+// High-resolution scans and recordings run large; everything else should be small
+pub fn max_size(kind: MediaKind) -> u64 {
+    match kind {
+        MediaKind::Photo => 25 * 1024 * 1024,
+        MediaKind::Document => 50 * 1024 * 1024,
+        MediaKind::Audio => 100 * 1024 * 1024,
+    }
+}
+
+pub fn validate_upload(request: &BeginMediaUploadRequest) -> Result<MediaKind, BackendError> {
+    let name = request.file_name.trim();
+    if name.is_empty() || name.len() > MAX_FILE_NAME_LENGTH {
+        return Err(BackendError::validation("file_name", if name.is_empty() { "empty" } else { "too_long" }));
+    }
+    if name.contains(['/', '\\']) || name.chars().any(char::is_control) {
+        return Err(BackendError::validation("file_name", "invalid_characters"));
+    }
+
+    let kind = media_kind(&request.mime_type)?;
+    if request.size == 0 {
+        return Err(BackendError::validation("size", "empty"));
+    }
+    if request.size > max_size(kind) {
+        return Err(BackendError::validation("size", "too_large"));
+    }
+    if request.sha256.len() != 32 {
+        return Err(BackendError::validation("sha256", "invalid_length"));
+    }
+    Ok(kind)
+}
+
+// Uploads in progress count against the quota, so they cannot be used to get around it
+pub fn check_upload_quota(family_id: &str, uploader: Principal, size: u64) -> Result<(), BackendError> {
+    let open_uploads = MEDIA_BY_UPLOADER.with(|index| count_with_status(&index.borrow(), uploader.as_slice(), UPLOADING));
+    let family_bytes = family_media_bytes(family_id);
+
+    if open_uploads >= MAX_OPEN_UPLOADS_PER_USER as u64 {
+        return Err(BackendError::conflict("too_many_open_uploads"));
+    }
+    if family_bytes.saturating_add(size) > MAX_FAMILY_MEDIA_BYTES {
+        return Err(BackendError::conflict("media_quota_exceeded"));
+    }
+    Ok(())
+}
+
+pub fn start_media_cleanup_timer() {
+    ic_cdk_timers::set_timer_interval(MEDIA_CLEANUP_INTERVAL, || {
+        remove_stale_uploads(api::time());
+    });
+}
+
+// Drops abandoned uploads and their chunks, least recently active first; returns
+// how many were removed
+pub fn remove_stale_uploads(now: u64) -> usize {
+    let Some(cutoff) = now.checked_sub(STALE_UPLOAD_NANOS) else {
+        return 0;
+    };
+    let stale: Vec<String> = OPEN_UPLOADS_BY_ACTIVITY.with(|index| {
+        index.borrow()
+            .keys()
+            .take_while(|key| key.created_at <= cutoff)
+            .take(MEDIA_CLEANUP_BATCH_SIZE)
+            .map(|key| key.id)
+            .collect()
+    });
+    for id in &stale {
+        if let Ok(asset) = get_media_internal(id) {
+            remove_media_internal(&asset);
+        }
+    }
+    stale.len()
+}
+
+pub fn media_of(family_id: &str, page: &PageRequest) -> Result<Page<MediaAsset>, BackendError> {
+    let ids = MEDIA_BY_FAMILY.with(|index| page_newest_first(&index.borrow(), family_id.as_bytes(), 0, page, MAX_PAGE_SIZE))?;
+    let items = ids.items.iter().filter_map(|id| get_media_internal(id).ok()).collect();
+    Ok(Page { items, next_cursor: ids.next_cursor })
+}
+
+pub fn family_media_bytes(family_id: &str) -> u64 {
+    FAMILY_MEDIA_BYTES.with(|bytes| bytes.borrow().get(&family_id.to_string()).unwrap_or(0))
+}
+
+// Writes an asset and moves its index entries; `old` is None for new uploads
+pub fn save_media(asset: &MediaAsset, old: Option<&MediaAsset>) {
+    MEDIA_BY_FAMILY.with(|index| reindex(&mut index.borrow_mut(), old.map(family_media_key), family_media_key(asset)));
+    MEDIA_BY_UPLOADER.with(|index| reindex(&mut index.borrow_mut(), old.map(uploader_media_key), uploader_media_key(asset)));
+    OPEN_UPLOADS_BY_ACTIVITY.with(|index| {
+        let mut index = index.borrow_mut();
+        if let Some(key) = old.and_then(open_upload_key) {
+            index.remove(&key);
+        }
+        if let Some(key) = open_upload_key(asset) {
+            index.insert(key, ());
+        }
+    });
+    if old.is_none() {
+        update_family_bytes(&asset.family_id, |total| total.saturating_add(asset.size));
+    }
+    MEDIA_ASSETS.with(|assets| assets.borrow_mut().insert(asset.id.clone(), asset.clone()));
+}
+
+fn update_family_bytes(family_id: &str, update: impl FnOnce(u64) -> u64) {
+    FAMILY_MEDIA_BYTES.with(|bytes| {
+        let mut bytes = bytes.borrow_mut();
+        let total = update(bytes.get(&family_id.to_string()).unwrap_or(0));
+        if total == 0 {
+            bytes.remove(&family_id.to_string());
+        } else {
+            bytes.insert(family_id.to_string(), total);
+        }
+    });
+}
+
+// Builds the media indexes and byte counts for assets stored before they existed
+pub fn rebuild_media_indexes() -> u64 {
+    let assets: Vec<MediaAsset> = MEDIA_ASSETS.with(|assets| assets.borrow().iter().map(|(_, asset)| asset).collect());
+    for asset in &assets {
+        save_media(asset, None);
+    }
+    assets.len() as u64
+}
+
+pub fn chunk_count(size: u64) -> u32 {
+    size.div_ceil(CHUNK_SIZE) as u32
+}
+
+pub fn expected_chunk_len(size: u64, index: u32) -> u64 {
+    let start = index as u64 * CHUNK_SIZE;
+    size.saturating_sub(start).min(CHUNK_SIZE)
+}
+
+// Removes the media from every member and event, writing only the records it was on;
+// true if anything changed
+pub fn unlink_media(family: &mut Family, media_id: &str) -> Result<bool, BackendError> {
+    let unlink = |media_ids: &mut Option<Vec<String>>| match media_ids {
+        Some(ids) => {
+            let initial_len = ids.len();
+            ids.retain(|id| id != media_id);
+            ids.len() != initial_len
+        }
+        None => false,
+    };
+
+    let mut changed = false;
+    for member in family.members.iter_mut() {
+        if unlink(&mut member.media_ids) {
+            store::save_member(&family.id, member)?;
+            changed = true;
+        }
+        for event in member.events.iter_mut() {
+            if unlink(&mut event.media_ids) {
+                store::save_event(&family.id, event)?;
+                changed = true;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+pub fn remove_media_internal(asset: &MediaAsset) {
+    MEDIA_CHUNKS.with(|chunks| {
+        let mut chunks = chunks.borrow_mut();
+        for index in 0..asset.chunk_count {
+            chunks.remove(&chunk_key(&asset.id, index));
+        }
+    });
+    MEDIA_BY_FAMILY.with(|index| index.borrow_mut().remove(&family_media_key(asset)));
+    MEDIA_BY_UPLOADER.with(|index| index.borrow_mut().remove(&uploader_media_key(asset)));
+    if let Some(key) = open_upload_key(asset) {
+        OPEN_UPLOADS_BY_ACTIVITY.with(|index| index.borrow_mut().remove(&key));
+    }
+    if MEDIA_ASSETS.with(|assets| assets.borrow_mut().remove(&asset.id)).is_some() {
+        update_family_bytes(&asset.family_id, |total| total.saturating_sub(asset.size));
+    }
+}
+
+// Fixed-width indexes keep an asset's chunks in order within the map
+pub fn chunk_key(media_id: &str, index: u32) -> String {
+    format!("{}:{:08}", media_id, index)
+}
+
+fn require_uploader(asset: &MediaAsset, caller: Principal) -> Result<(), BackendError> {
+    if asset.uploaded_by != caller {
+        return Err(BackendError::forbidden());
+    }
+    Ok(())
+}
+
+fn missing_chunks(asset: &MediaAsset) -> Vec<u32> {
+    MEDIA_CHUNKS.with(|chunks| {
+        let chunks = chunks.borrow();
+        (0..asset.chunk_count)
+            .filter(|index| !chunks.contains_key(&chunk_key(&asset.id, *index)))
+            .collect()
+    })
+}
+
+fn content_hash(asset: &MediaAsset) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    MEDIA_CHUNKS.with(|chunks| {
+        let chunks = chunks.borrow();
+        for index in 0..asset.chunk_count {
+            if let Some(chunk) = chunks.get(&chunk_key(&asset.id, index)) {
+                hasher.update(&chunk);
+            }
+        }
+    });
+    hasher.finalize().to_vec()
+}
+
+// Writes the member or event named by the request, plus the family record
+fn save_link_target(family: &Family, request: &MediaLinkRequest) -> Result<(), BackendError> {
+    let member = family.members.iter().find(|m| m.id == request.member_id)
+        .ok_or_else(|| BackendError::not_found("member", &request.member_id))?;
+    match &request.event_id {
+        None => store::save_member(&family.id, member)?,
+        Some(event_id) => {
+            let event = member.events.iter().find(|e| &e.id == event_id)
+                .ok_or_else(|| BackendError::not_found("event", event_id))?;
+            store::save_event(&family.id, event)?;
+        }
+    }
+    store::save_family_header(family);
+    Ok(())
+}
+
+// Members need EditMembers; events follow the same rules as editing the event
+fn linked_media_mut<'a>(
+    family: &'a mut Family,
+    request: &MediaLinkRequest,
+    caller: Principal,
+) -> Result<&'a mut Vec<String>, BackendError> {
+    let can_edit_any = has_permission(family, caller, Permission::EditAnyEvent);
+    let can_edit_members = has_permission(family, caller, Permission::EditMembers);
+    let can_add_events = has_permission(family, caller, Permission::AddEvents);
+
+    let member = family.members.iter_mut().find(|m| m.id == request.member_id)
+        .ok_or_else(|| BackendError::not_found("member", &request.member_id))?;
+
+    let media_ids = match &request.event_id {
+        None => {
+            if !can_edit_members {
+                return Err(BackendError::requires(minimum_role(Permission::EditMembers)));
+            }
+            &mut member.media_ids
+        }
+        Some(event_id) => {
+            let event = member.events.iter_mut().find(|e| &e.id == event_id)
+                .ok_or_else(|| BackendError::not_found("event", event_id))?;
+            let may_edit = can_edit_any || (can_add_events && event.created_by == caller);
+            if !may_edit {
+                return Err(BackendError::requires(minimum_role(Permission::EditAnyEvent)));
+            }
+            &mut event.media_ids
+        }
+    };
+    Ok(media_ids.get_or_insert_with(Vec::new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::test_support::{event, family, member, user};
+    use crate::types::{FamilyEvent, FamilyMember};
+
+    fn upload(mime_type: &str, size: u64) -> BeginMediaUploadRequest {
+        BeginMediaUploadRequest {
+            family_id: "f1".to_string(),
+            file_name: "birth_certificate.pdf".to_string(),
+            mime_type: mime_type.to_string(),
+            size,
+            sha256: vec![0; 32],
+        }
+    }
+
+    fn asset(id: &str, size: u64) -> MediaAsset {
+        MediaAsset {
+            id: id.to_string(),
+            family_id: "f1".to_string(),
+            file_name: "photo.jpg".to_string(),
+            mime_type: "image/jpeg".to_string(),
+            kind: MediaKind::Photo,
+            size,
+            chunk_count: chunk_count(size),
+            sha256: vec![],
+            status: MediaStatus::Uploading,
+            uploaded_by: user(1),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_chunk_layout() {
+        assert_eq!(chunk_count(1), 1);
+        assert_eq!(chunk_count(CHUNK_SIZE), 1);
+        assert_eq!(chunk_count(CHUNK_SIZE + 1), 2);
+
+        assert_eq!(expected_chunk_len(CHUNK_SIZE + 10, 0), CHUNK_SIZE);
+        assert_eq!(expected_chunk_len(CHUNK_SIZE + 10, 1), 10);
+        assert_eq!(expected_chunk_len(CHUNK_SIZE + 10, 2), 0);
+
+        assert!(chunk_key("m1", 2) < chunk_key("m1", 10));
+    }
+
+    #[test]
+    fn test_validate_upload() {
+        assert_eq!(validate_upload(&upload("application/pdf", 1_000)), Ok(MediaKind::Document));
+        assert_eq!(validate_upload(&upload("audio/mpeg", 1_000)), Ok(MediaKind::Audio));
+        assert!(validate_upload(&upload("application/x-msdownload", 1_000)).is_err());
+        assert!(validate_upload(&upload("image/png", 0)).is_err());
+        assert!(validate_upload(&upload("image/png", max_size(MediaKind::Photo) + 1)).is_err());
+
+        let mut bad_name = upload("image/png", 10);
+        bad_name.file_name = "../scan.png".to_string();
+        assert!(validate_upload(&bad_name).is_err());
+
+        let mut bad_hash = upload("image/png", 10);
+        bad_hash.sha256 = vec![0; 16];
+        assert!(validate_upload(&bad_hash).is_err());
+    }
+
+    #[test]
+    fn test_content_hash_covers_all_chunks() {
+        let asset = asset("m1", CHUNK_SIZE + 3);
+        assert_eq!(missing_chunks(&asset), vec![0, 1]);
+
+        let first = vec![7u8; CHUNK_SIZE as usize];
+        MEDIA_CHUNKS.with(|chunks| {
+            let mut chunks = chunks.borrow_mut();
+            chunks.insert(chunk_key("m1", 0), first.clone());
+            chunks.insert(chunk_key("m1", 1), vec![1, 2, 3]);
+        });
+        assert!(missing_chunks(&asset).is_empty());
+
+        let mut expected = Sha256::new();
+        expected.update(&first);
+        expected.update([1, 2, 3]);
+        assert_eq!(content_hash(&asset), expected.finalize().to_vec());
+
+        remove_media_internal(&asset);
+        assert_eq!(missing_chunks(&asset), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_upload_quota() {
+        let insert = |asset: MediaAsset| save_media(&asset, None);
+
+        for i in 0..MAX_OPEN_UPLOADS_PER_USER {
+            assert!(check_upload_quota("f1", user(1), 10).is_ok());
+            insert(asset(&format!("open{}", i), 10));
+        }
+        assert_eq!(check_upload_quota("f1", user(1), 10), Err(BackendError::conflict("too_many_open_uploads")));
+        assert!(check_upload_quota("f1", user(2), 10).is_ok());
+
+        // Finished uploads no longer count as open, but still use the family's storage
+        let mut large = asset("large", MAX_FAMILY_MEDIA_BYTES - 100);
+        large.status = MediaStatus::Ready;
+        insert(large.clone());
+        assert!(check_upload_quota("f1", user(2), 70).is_ok());
+        assert_eq!(check_upload_quota("f1", user(2), 71), Err(BackendError::conflict("media_quota_exceeded")));
+
+        // Other families have their own quota, and deleting media frees it
+        assert!(check_upload_quota("f2", user(2), 71).is_ok());
+        remove_media_internal(&large);
+        assert_eq!(family_media_bytes("f1"), 30);
+        assert!(check_upload_quota("f1", user(2), 71).is_ok());
+    }
+
+    #[test]
+    fn test_finishing_an_upload_moves_its_index_entries() {
+        let upload = asset("m1", 10);
+        save_media(&upload, None);
+        let finished = MediaAsset { status: MediaStatus::Ready, updated_at: 5, ..upload.clone() };
+        save_media(&finished, Some(&upload));
+
+        assert_eq!(MEDIA_BY_UPLOADER.with(|index| count_with_status(&index.borrow(), user(1).as_slice(), UPLOADING)), 0);
+        assert!(OPEN_UPLOADS_BY_ACTIVITY.with(|index| index.borrow().is_empty()));
+        let media = media_of("f1", &PageRequest::default()).unwrap();
+        assert_eq!(media.items.iter().map(|asset| asset.id.as_str()).collect::<Vec<_>>(), vec!["m1"]);
+        // Finishing doesn't count the asset's bytes twice
+        assert_eq!(family_media_bytes("f1"), 10);
+    }
+
+    #[test]
+    fn test_stale_uploads_are_removed() {
+        let mut stale = asset("stale", 10);
+        stale.updated_at = 0;
+        let mut active = asset("active", 10);
+        active.updated_at = STALE_UPLOAD_NANOS;
+        let mut finished = asset("finished", 10);
+        finished.status = MediaStatus::Ready;
+        for asset in [&stale, &active, &finished] {
+            save_media(asset, None);
+        }
+        MEDIA_CHUNKS.with(|chunks| chunks.borrow_mut().insert(chunk_key("stale", 0), vec![0; 10]));
+
+        assert_eq!(remove_stale_uploads(STALE_UPLOAD_NANOS - 1), 0);
+        assert_eq!(remove_stale_uploads(STALE_UPLOAD_NANOS), 1);
+        assert!(get_media_internal("stale").is_err());
+        assert_eq!(missing_chunks(&stale), vec![0]);
+        assert!(get_media_internal("active").is_ok());
+        assert!(get_media_internal("finished").is_ok());
+    }
+
+    #[test]
+    fn test_unlink_media() {
+        let event = FamilyEvent {
+            media_ids: Some(vec!["scan".to_string(), "photo".to_string()]),
+            ..event("e1", "m1", "1900-01-01")
+        };
+        let member = FamilyMember {
+            events: vec![event],
+            media_ids: Some(vec!["photo".to_string()]),
+            ..member("m1", None)
+        };
+        let mut family = family(vec![member]);
+
+        store::save_family(&family).unwrap();
+
+        assert!(unlink_media(&mut family, "photo").unwrap());
+        assert_eq!(family.members[0].media_ids, Some(vec![]));
+        assert_eq!(family.members[0].events[0].media_ids, Some(vec!["scan".to_string()]));
+        assert!(!unlink_media(&mut family, "photo").unwrap());
+
+        // Each changed record was written on its own
+        let stored = store::load_family("f1").unwrap();
+        assert_eq!(stored.members[0].media_ids, Some(vec![]));
+        assert_eq!(stored.members[0].events[0].media_ids, Some(vec!["scan".to_string()]));
+    }
+}
@@ -4,31 +4,49 @@ use ic_cdk_macros::*;
 
 use crate::types::{
     Family, FamilyMember, FamilyEvent, CreateFamilyRequest, AddFamilyMemberRequest, 
-    AddEventRequest, UpdateFamilyMemberRequest, UpdateEventRequest, DEV_MODE
+    AddEventRequest, UpdateFamilyMemberRequest, UpdateEventRequest, FamilyRole, EventFilter,
+    Page, PageRequest, DEV_MODE
 };
-use crate::storage::{PROFILES, FAMILIES, USER_FAMILIES, generate_id};
+use crate::storage::{PROFILES, USER_FAMILIES, generate_id};
+use crate::rewards::queue_event_reward;
+use crate::error::BackendError;
+use crate::pagination::{after_id, page_limit, take_page, within, MAX_FAMILY_PAGE_SIZE, MAX_PAGE_SIZE};
+
+pub mod relationships;
+pub mod kinship;
+pub mod roles;
+pub mod succession;
+pub mod governance;
+pub mod treasury;
+pub mod media;
+pub mod store;
+#[cfg(test)]
+pub mod test_support;
+
+use roles::{has_permission, require_permission, role_of, Permission};
 
 #[update]
-pub fn create_family(request: CreateFamilyRequest) -> Result<Family, String> {
+pub fn create_family(request: CreateFamilyRequest) -> Result<Family, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
+    create_family_internal(caller, request)
+}
+
+// Shared by create_family and GEDCOM import
+pub fn create_family_internal(caller: Principal, request: CreateFamilyRequest) -> Result<Family, BackendError> {
     // Validate input fields
-    if let Err(_) = crate::validation::validate_name(&request.name, "family_name") {
-        return Err("Invalid family name format".to_string());
-    }
-    if let Err(_) = crate::validation::validate_description(&request.description) {
-        return Err("Invalid description format".to_string());
-    }
+    crate::validation::validate_name(&request.name, "name")?;
+    crate::validation::validate_description(&request.description)?;
 
     // Check if user has a profile
     PROFILES.with(|profiles| {
         let profiles = profiles.borrow();
         if !profiles.contains_key(&caller) {
-            return Err("You must create a user profile before creating a family".to_string());
+            return Err(BackendError::not_found("profile", caller.to_text()));
         }
         Ok(())
     })?;
@@ -47,91 +65,118 @@ pub fn create_family(request: CreateFamilyRequest) -> Result<Family, String> {
         updated_at: current_time,
     };
 
-    FAMILIES.with(|families| {
-        let mut families = families.borrow_mut();
-        families.insert(family_id.clone(), family.clone());
-        
-        // Add family to user's family list
-        USER_FAMILIES.with(|user_families| {
-            let mut user_families = user_families.borrow_mut();
-            let mut user_family_list = user_families.get(&caller).unwrap_or_default();
-            user_family_list.0.push(family_id);
-            user_families.insert(caller, user_family_list);
-        });
-        
-        Ok(family)
-    })
+    store::save_family_header(&family);
+
+    // Add family to user's family list
+    USER_FAMILIES.with(|user_families| {
+        let mut user_families = user_families.borrow_mut();
+        let mut user_family_list = user_families.get(&caller).unwrap_or_default();
+        user_family_list.0.push(family_id);
+        user_families.insert(caller, user_family_list);
+    });
+
+    Ok(family)
 }
 
 #[query]
-pub fn get_user_families() -> Result<Vec<Family>, String> {
+pub fn get_user_families(page: PageRequest) -> Result<Page<Family>, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
-    USER_FAMILIES.with(|user_families| {
-        let user_families = user_families.borrow();
-        let family_ids = user_families.get(&caller).unwrap_or_default();
-        
-        let families: Vec<Family> = FAMILIES.with(|families| {
-            let families = families.borrow();
-            family_ids.0.iter()
-                .filter_map(|id| families.get(id))
-                .collect()
-        });
-        
-        Ok(families)
-    })
+    let family_ids = USER_FAMILIES.with(|user_families| {
+        user_families.borrow().get(&caller).unwrap_or_default().0
+    });
+
+    // Page over the caller's family ids first so only one page of families is loaded.
+    // Families come back without members, as from get_family.
+    let ids = after_id(family_ids, |id| id.as_str(), &page, MAX_FAMILY_PAGE_SIZE)?;
+    let families = ids.items.iter()
+        .filter_map(|id| store::load_family_header(id))
+        .collect();
+
+    Ok(Page { items: families, next_cursor: ids.next_cursor })
 }
 
+// The family record without its members; page those with get_family_members
 #[query]
-pub fn get_family(family_id: String) -> Result<Family, String> {
+pub fn get_family(family_id: String) -> Result<Family, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
-    }
-
-    FAMILIES.with(|families| {
-        let families = families.borrow();
-        match families.get(&family_id) {
-            Some(family) => {
-                // Check if user has access to this family (is admin or member)
-                if family.admin == caller {
-                    Ok(family)
-                } else {
-                    // Check if user is a member of the family
-                    let is_member = family.members.iter().any(|member| {
-                        member.profile_principal == Some(caller)
-                    });
-                    
-                    if is_member {
-                        Ok(family)
-                    } else {
-                        Err("Access denied: You are not a member of this family".to_string())
-                    }
-                }
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_readable_family(&family_id, caller)?;
+    Ok(Family { members: Vec::new(), ..family })
+}
+
+// Members in the order they were added, without their events; page those with
+// get_family_events
+#[query]
+pub fn get_family_members(family_id: String, page: PageRequest) -> Result<Page<FamilyMember>, BackendError> {
+    let caller = api::caller();
+    
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    get_readable_family(&family_id, caller)?;
+
+    let limit = page_limit(&page, MAX_PAGE_SIZE);
+    let members = store::members_after(&family_id, page.cursor.as_deref(), limit + 1)?;
+    Ok(take_page(members.into_iter(), limit, |member| member.id.clone()))
+}
+
+// Events grouped by member. The cursor is "<member ID>/<event ID>" of the last event returned.
+#[query]
+pub fn get_family_events(family_id: String, filter: EventFilter, page: PageRequest) -> Result<Page<FamilyEvent>, BackendError> {
+    let caller = api::caller();
+    
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    get_readable_family(&family_id, caller)?;
+
+    let after = match &page.cursor {
+        Some(cursor) => Some(cursor.split_once('/').ok_or_else(|| BackendError::validation("cursor", "malformed"))?),
+        None => None,
+    };
+    let limit = page_limit(&page, MAX_PAGE_SIZE);
+    let events = store::events_after(
+        &family_id,
+        filter.member_id.as_deref(),
+        after,
+        limit + 1,
+        |event| event_matches(event, &filter),
+    )?;
+    Ok(take_page(events.into_iter(), limit, |event| format!("{}/{}", event.member_id, event.id)))
 }
 
 #[update]
-pub fn add_family_member(request: AddFamilyMemberRequest) -> Result<FamilyMember, String> {
+pub fn add_family_member(request: AddFamilyMemberRequest) -> Result<FamilyMember, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
+    let family = get_family_internal(&request.family_id)?;
+    require_permission(&family, caller, Permission::AddMembers)?;
+
+    // New members are unlinked; the reward is paid once someone accepts or claims them
+    add_family_member_internal(request, caller)
+}
+
+// Shared by add_family_member and executed governance proposals
+pub fn add_family_member_internal(request: AddFamilyMemberRequest, created_by: Principal) -> Result<FamilyMember, BackendError> {
     let current_time = api::time();
-    let member_id = generate_id();
     
     let member = FamilyMember {
-        id: member_id.clone(),
+        id: generate_id(),
         profile_principal: None, // Users can link their profile later
         full_name: request.full_name,
         surname_at_birth: request.surname_at_birth,
@@ -143,67 +188,54 @@ pub fn add_family_member(request: AddFamilyMemberRequest) -> Result<FamilyMember
         relationship_to_admin: request.relationship_to_admin,
         events: Vec::new(),
         created_at: current_time,
-        created_by: caller,
+        created_by,
+        media_ids: None,
     };
 
-    FAMILIES.with(|families| {
-        let mut families = families.borrow_mut();
-        match families.get(&request.family_id) {
-            Some(mut family) => {
-                // Check if caller is admin
-                if family.admin != caller {
-                    return Err("Only family admin can add members".to_string());
-                }
-                
-                family.members.push(member.clone());
-                family.updated_at = current_time;
-                families.insert(request.family_id, family);
-                Ok(member)
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+    let mut family = store::load_family_header(&request.family_id)
+        .ok_or_else(|| BackendError::not_found("family", &request.family_id))?;
+    store::save_member(&request.family_id, &member)?;
+    family.updated_at = current_time;
+    store::save_family_header(&family);
+    Ok(member)
 }
 
 #[update]
-pub fn remove_family_member(family_id: String, member_id: String) -> Result<String, String> {
+pub fn remove_family_member(family_id: String, member_id: String) -> Result<String, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
-    }
-
-    FAMILIES.with(|families| {
-        let mut families = families.borrow_mut();
-        match families.get(&family_id) {
-            Some(mut family) => {
-                // Check if caller is admin
-                if family.admin != caller {
-                    return Err("Only family admin can remove members".to_string());
-                }
-                
-                let initial_len = family.members.len();
-                family.members.retain(|member| member.id != member_id);
-                
-                if family.members.len() < initial_len {
-                    family.updated_at = api::time();
-                    families.insert(family_id, family);
-                    Ok("Member removed successfully".to_string())
-                } else {
-                    Err("Member not found".to_string())
-                }
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_internal(&family_id)?;
+    require_permission(&family, caller, Permission::RemoveMembers)?;
+
+    remove_family_member_internal(&family_id, &member_id)?;
+    Ok("Member removed successfully".to_string())
+}
+
+// Shared by remove_family_member and executed governance proposals
+pub fn remove_family_member_internal(family_id: &str, member_id: &str) -> Result<(), BackendError> {
+    let mut family = store::load_family_header(family_id)
+        .ok_or_else(|| BackendError::not_found("family", family_id))?;
+    if !store::member_exists(family_id, member_id) {
+        return Err(BackendError::not_found("member", member_id));
+    }
+
+    store::remove_member(family_id, member_id);
+    relationships::remove_member_relationships(family_id, member_id);
+    family.updated_at = api::time();
+    store::save_family_header(&family);
+    Ok(())
 }
 
 #[update]
-pub fn add_member_event(request: AddEventRequest) -> Result<FamilyEvent, String> {
+pub fn add_member_event(request: AddEventRequest) -> Result<FamilyEvent, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     let current_time = api::time();
@@ -218,110 +250,119 @@ pub fn add_member_event(request: AddEventRequest) -> Result<FamilyEvent, String>
         event_type: request.event_type,
         created_at: current_time,
         created_by: caller,
+        media_ids: None,
     };
 
-    FAMILIES.with(|families| {
-        let mut families = families.borrow_mut();
-        match families.get(&request.family_id) {
-            Some(mut family) => {
-                // Check if caller is admin
-                if family.admin != caller {
-                    return Err("Only family admin can add events".to_string());
-                }
-                
-                // Find the member and add the event
-                if let Some(member) = family.members.iter_mut().find(|m| m.id == request.member_id) {
-                    member.events.push(event.clone());
-                    
-                    // Sort events by date for chronological order
-                    member.events.sort_by(|a, b| a.event_date.cmp(&b.event_date));
-                    
-                    family.updated_at = current_time;
-                    families.insert(request.family_id, family);
-                    Ok(event)
-                } else {
-                    Err("Member not found in family".to_string())
-                }
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+    let mut family = get_family_roster(&request.family_id)?;
+    require_permission(&family, caller, Permission::AddEvents)?;
+    if !family.members.iter().any(|m| m.id == request.member_id) {
+        return Err(BackendError::not_found("member", &request.member_id));
+    }
+
+    // Only the new event and the family record are written
+    store::save_event(&family.id, &event)?;
+    family.updated_at = current_time;
+    store::save_family_header(&family);
+
+    queue_event_reward(caller);
+
+    Ok(event)
 }
 
 #[query]
-pub fn get_member_events_chronological(family_id: String, member_id: String) -> Result<Vec<FamilyEvent>, String> {
+pub fn get_member_events_chronological(family_id: String, member_id: String) -> Result<Vec<FamilyEvent>, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
-    }
-
-    FAMILIES.with(|families| {
-        let families = families.borrow();
-        match families.get(&family_id) {
-            Some(family) => {
-                // Check if user has access to this family
-                if family.admin != caller {
-                    return Err("Access denied: You are not a member of this family".to_string());
-                }
-                
-                // Find the member and return their events
-                if let Some(member) = family.members.iter().find(|m| m.id == member_id) {
-                    let mut events = member.events.clone();
-                    events.sort_by(|a, b| a.event_date.cmp(&b.event_date));
-                    Ok(events)
-                } else {
-                    Err("Member not found in family".to_string())
-                }
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+        return Err(BackendError::Unauthenticated);
+    }
+
+    get_readable_family(&family_id, caller)?;
+
+    if !store::member_exists(&family_id, &member_id) {
+        return Err(BackendError::not_found("member", &member_id));
+    }
+    Ok(store::load_events(&family_id, &member_id))
 }
 
 #[update]
-pub fn toggle_family_visibility(family_id: String, is_visible: bool) -> Result<String, String> {
+pub fn toggle_family_visibility(family_id: String, is_visible: bool) -> Result<String, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
-    }
-
-    FAMILIES.with(|families| {
-        let mut families = families.borrow_mut();
-        match families.get(&family_id) {
-            Some(mut family) => {
-                // Check if caller is admin
-                if family.admin != caller {
-                    return Err("Only family admin can change visibility settings".to_string());
-                }
-                
-                family.is_visible = is_visible;
-                family.updated_at = api::time();
-                families.insert(family_id, family);
-                
-                let status = if is_visible { "visible" } else { "hidden" };
-                Ok(format!("Family visibility updated to {}", status))
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_internal(&family_id)?;
+    require_permission(&family, caller, Permission::ManageVisibility)?;
+
+    set_family_visibility_internal(&family_id, is_visible)?;
+
+    let status = if is_visible { "visible" } else { "hidden" };
+    Ok(format!("Family visibility updated to {}", status))
+}
+
+// Shared by toggle_family_visibility and executed governance proposals
+pub fn set_family_visibility_internal(family_id: &str, is_visible: bool) -> Result<(), BackendError> {
+    let mut family = store::load_family_header(family_id)
+        .ok_or_else(|| BackendError::not_found("family", family_id))?;
+    family.is_visible = is_visible;
+    family.updated_at = api::time();
+    store::save_family_header(&family);
+    Ok(())
 }
 
 // Internal helper functions for other modules
-pub fn get_family_internal(family_id: &str) -> Result<Family, String> {
-    FAMILIES.with(|families| {
-        families.borrow()
-            .get(&family_id.to_string())
-            .ok_or("Family not found".to_string())
-    })
+pub fn get_family_internal(family_id: &str) -> Result<Family, BackendError> {
+    store::load_family(family_id).ok_or_else(|| BackendError::not_found("family", family_id))
 }
 
-pub fn update_family_internal(family: Family) -> Result<(), String> {
-    FAMILIES.with(|families| {
-        families.borrow_mut().insert(family.id.clone(), family);
-        Ok(())
-    })
+// The family and its members without their events; enough to check roles, and far
+// cheaper than get_family_internal for families with long histories
+pub fn get_family_roster(family_id: &str) -> Result<Family, BackendError> {
+    store::load_family_roster(family_id).ok_or_else(|| BackendError::not_found("family", family_id))
+}
+
+// The family and its members, without events, once the caller is known to have a role
+fn get_readable_family(family_id: &str, caller: Principal) -> Result<Family, BackendError> {
+    let family = get_family_roster(family_id)?;
+    if !has_family_access(&family, caller) {
+        return Err(BackendError::requires(FamilyRole::Viewer));
+    }
+    Ok(family)
+}
+
+// Anyone with a role, including linked members, may read a family's data
+pub fn has_family_access(family: &Family, caller: Principal) -> bool {
+    role_of(family, caller).is_some()
+}
+
+fn event_matches(event: &FamilyEvent, filter: &EventFilter) -> bool {
+    filter.member_id.as_ref().is_none_or(|id| &event.member_id == id)
+        && filter.event_type.as_ref().is_none_or(|t| &event.event_type == t)
+        && within(event.created_at, filter.created_after, filter.created_before)
+}
+
+// Keep a user's family list in step with the families they have a role in
+pub fn link_user_family(principal: Principal, family_id: &str) {
+    USER_FAMILIES.with(|user_families| {
+        let mut user_families = user_families.borrow_mut();
+        let mut user_family_list = user_families.get(&principal).unwrap_or_default();
+        if !user_family_list.0.iter().any(|id| id == family_id) {
+            user_family_list.0.push(family_id.to_string());
+            user_families.insert(principal, user_family_list);
+        }
+    });
+}
+
+pub fn unlink_user_family(principal: Principal, family_id: &str) {
+    USER_FAMILIES.with(|user_families| {
+        let mut user_families = user_families.borrow_mut();
+        if let Some(mut user_family_list) = user_families.get(&principal) {
+            user_family_list.0.retain(|id| id != family_id);
+            user_families.insert(principal, user_family_list);
+        }
+    });
 }
 
 pub fn generate_member_id() -> String {
@@ -329,134 +370,108 @@ pub fn generate_member_id() -> String {
 }
 
 #[update]
-pub fn update_family_member(request: UpdateFamilyMemberRequest) -> Result<FamilyMember, String> {
+pub fn update_family_member(request: UpdateFamilyMemberRequest) -> Result<FamilyMember, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     let current_time = api::time();
 
-    FAMILIES.with(|families| {
-        let mut families = families.borrow_mut();
-        match families.get(&request.family_id) {
-            Some(mut family) => {
-                // Check if caller is admin
-                ic_cdk::println!("DEBUG: Family admin: {:?}, Caller: {:?}", family.admin, caller);
-                if family.admin != caller {
-                    return Err(format!("Only family admin can update member profiles. Admin: {:?}, Caller: {:?}", family.admin, caller));
-                }
-                
-                // Find the member to update and check permissions first
-                let member_result = family.members.iter_mut().find(|m| m.id == request.member_id);
-                if let Some(member) = member_result {
-                    // Only allow updating ghost profiles (members without linked principals)
-                    if member.profile_principal.is_some() {
-                        return Err("Cannot update linked member profiles. Only ghost profiles can be edited.".to_string());
-                    }
-                    
-                    // Update fields if provided
-                    if let Some(full_name) = request.full_name {
-                        member.full_name = full_name;
-                    }
-                    if let Some(surname_at_birth) = request.surname_at_birth {
-                        member.surname_at_birth = surname_at_birth;
-                    }
-                    if let Some(sex) = request.sex {
-                        member.sex = sex;
-                    }
-                    if let Some(birthday) = request.birthday {
-                        member.birthday = Some(birthday);
-                    }
-                    if let Some(birth_city) = request.birth_city {
-                        member.birth_city = Some(birth_city);
-                    }
-                    if let Some(birth_country) = request.birth_country {
-                        member.birth_country = Some(birth_country);
-                    }
-                    if let Some(death_date) = request.death_date {
-                        member.death_date = Some(death_date);
-                    }
-                    if let Some(relationship_to_admin) = request.relationship_to_admin {
-                        member.relationship_to_admin = relationship_to_admin;
-                    }
-                    
-                    let updated_member = member.clone();
-                    
-                    family.updated_at = current_time;
-                    families.insert(request.family_id, family);
-                    
-                    Ok(updated_member)
-                } else {
-                    Err("Member not found in family".to_string())
-                }
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+    let mut family = get_family_roster(&request.family_id)?;
+    require_permission(&family, caller, Permission::EditMembers)?;
+
+    // Find the member to update and check permissions first
+    let member = family.members.iter_mut()
+        .find(|m| m.id == request.member_id)
+        .ok_or_else(|| BackendError::not_found("member", &request.member_id))?;
+
+    // Only allow updating ghost profiles (members without linked principals)
+    if member.profile_principal.is_some() {
+        return Err(BackendError::conflict("linked_member_not_editable"));
+    }
+
+    // Update fields if provided
+    if let Some(full_name) = request.full_name {
+        member.full_name = full_name;
+    }
+    if let Some(surname_at_birth) = request.surname_at_birth {
+        member.surname_at_birth = surname_at_birth;
+    }
+    if let Some(sex) = request.sex {
+        member.sex = sex;
+    }
+    if let Some(birthday) = request.birthday {
+        member.birthday = Some(birthday);
+    }
+    if let Some(birth_city) = request.birth_city {
+        member.birth_city = Some(birth_city);
+    }
+    if let Some(birth_country) = request.birth_country {
+        member.birth_country = Some(birth_country);
+    }
+    if let Some(death_date) = request.death_date {
+        member.death_date = Some(death_date);
+    }
+    if let Some(relationship_to_admin) = request.relationship_to_admin {
+        member.relationship_to_admin = relationship_to_admin;
+    }
+
+    let updated_member = member.clone();
+    store::save_member(&request.family_id, &updated_member)?;
+    family.updated_at = current_time;
+    store::save_family_header(&family);
+
+    Ok(updated_member)
 }
 
 #[update]
-pub fn update_member_event(request: UpdateEventRequest) -> Result<FamilyEvent, String> {
+pub fn update_member_event(request: UpdateEventRequest) -> Result<FamilyEvent, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     let current_time = api::time();
 
-    FAMILIES.with(|families| {
-        let mut families = families.borrow_mut();
-        match families.get(&request.family_id) {
-            Some(mut family) => {
-                // Check if caller is admin or event creator
-                ic_cdk::println!("DEBUG UPDATE EVENT: Family admin: {:?}, Caller: {:?}", family.admin, caller);
-                if family.admin != caller {
-                    return Err(format!("Only family admin can update events. Admin: {:?}, Caller: {:?}", family.admin, caller));
-                }
-                
-                // Find the member first
-                let member_index = family.members.iter().position(|m| m.id == request.member_id);
-                if let Some(member_idx) = member_index {
-                    let member = &mut family.members[member_idx];
-                    if let Some(event) = member.events.iter_mut().find(|e| e.id == request.event_id) {
-                        // Update event fields if provided
-                        if let Some(title) = request.title {
-                            event.title = title;
-                        }
-                        if let Some(description) = request.description {
-                            event.description = description;
-                        }
-                        let mut needs_sort = false;
-                        if let Some(event_date) = request.event_date {
-                            event.event_date = event_date;
-                            needs_sort = true;
-                        }
-                        if let Some(event_type) = request.event_type {
-                            event.event_type = event_type;
-                        }
-                        
-                        let updated_event = event.clone();
-                        
-                        // Re-sort events by date after updating if date changed
-                        if needs_sort {
-                            member.events.sort_by(|a, b| a.event_date.cmp(&b.event_date));
-                        }
-                        
-                        family.updated_at = current_time;
-                        families.insert(request.family_id, family);
-                        
-                        Ok(updated_event)
-                    } else {
-                        Err("Event not found".to_string())
-                    }
-                } else {
-                    Err("Member not found in family".to_string())
-                }
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })
+    let mut family = get_family_roster(&request.family_id)?;
+
+    // Contributors may only update the events they created
+    let can_edit_any = has_permission(&family, caller, Permission::EditAnyEvent);
+    if !can_edit_any {
+        require_permission(&family, caller, Permission::AddEvents)?;
+    }
+
+    // Find the member first
+    if !family.members.iter().any(|m| m.id == request.member_id) {
+        return Err(BackendError::not_found("member", &request.member_id));
+    }
+    let mut event = store::load_event(&family.id, &request.member_id, &request.event_id)
+        .ok_or_else(|| BackendError::not_found("event", &request.event_id))?;
+
+    if !can_edit_any && event.created_by != caller {
+        return Err(BackendError::requires(roles::minimum_role(Permission::EditAnyEvent)));
+    }
+
+    // Update event fields if provided; events are sorted by date when loaded
+    if let Some(title) = request.title {
+        event.title = title;
+    }
+    if let Some(description) = request.description {
+        event.description = description;
+    }
+    if let Some(event_date) = request.event_date {
+        event.event_date = event_date;
+    }
+    if let Some(event_type) = request.event_type {
+        event.event_type = event_type;
+    }
+
+    store::save_event(&family.id, &event)?;
+    family.updated_at = current_time;
+    store::save_family_header(&family);
+
+    Ok(event)
 }
\ No newline at end of file
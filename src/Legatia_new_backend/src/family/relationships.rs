@@ -0,0 +1,375 @@
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use std::collections::{HashSet, VecDeque};
+
+use crate::types::{
+    FamilyMember, FamilyRelationship, AddRelationshipRequest, RelationshipType, RelationshipQualifier, FamilyRole, DEV_MODE
+};
+use crate::storage::{RELATIONSHIPS, generate_id};
+use crate::error::BackendError;
+use super::{get_family_roster, has_family_access};
+use super::roles::{require_permission, Permission};
+
+#[update]
+pub fn add_family_relationship(request: AddRelationshipRequest) -> Result<FamilyRelationship, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&request.family_id)?;
+
+    require_permission(&family, caller, Permission::ManageRelationships)?;
+
+    let existing = get_relationships_internal(&request.family_id);
+    validate_relationship(&family.members, &existing, &request)?;
+
+    let relationship = FamilyRelationship {
+        id: generate_id(),
+        family_id: request.family_id.clone(),
+        from_member_id: request.from_member_id,
+        to_member_id: request.to_member_id,
+        relationship_type: request.relationship_type,
+        qualifier: request.qualifier.unwrap_or(RelationshipQualifier::Biological),
+        start_date: request.start_date,
+        end_date: request.end_date,
+        created_at: api::time(),
+        created_by: caller,
+    };
+
+    RELATIONSHIPS.with(|relationships| {
+        let mut relationships = relationships.borrow_mut();
+        let mut list = relationships.get(&request.family_id).unwrap_or_default();
+        list.0.push(relationship.clone());
+        relationships.insert(request.family_id, list);
+    });
+
+    Ok(relationship)
+}
+
+#[update]
+pub fn remove_family_relationship(family_id: String, relationship_id: String) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+
+    require_permission(&family, caller, Permission::ManageRelationships)?;
+
+    RELATIONSHIPS.with(|relationships| {
+        let mut relationships = relationships.borrow_mut();
+        let mut list = relationships.get(&family_id).unwrap_or_default();
+
+        let initial_len = list.0.len();
+        list.0.retain(|r| r.id != relationship_id);
+
+        if list.0.len() < initial_len {
+            relationships.insert(family_id, list);
+            Ok("Relationship removed successfully".to_string())
+        } else {
+            Err(BackendError::not_found("relationship", &relationship_id))
+        }
+    })
+}
+
+#[query]
+pub fn get_family_relationships(family_id: String) -> Result<Vec<FamilyRelationship>, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+
+    if !has_family_access(&family, caller) {
+        return Err(BackendError::requires(FamilyRole::Viewer));
+    }
+
+    Ok(get_relationships_internal(&family_id))
+}
+
+#[query]
+pub fn get_member_relationships(family_id: String, member_id: String) -> Result<Vec<FamilyRelationship>, BackendError> {
+    let relationships = get_family_relationships(family_id)?;
+
+    Ok(relationships.into_iter()
+        .filter(|r| r.from_member_id == member_id || r.to_member_id == member_id)
+        .collect())
+}
+
+// Internal helper functions for other modules
+pub fn get_relationships_internal(family_id: &str) -> Vec<FamilyRelationship> {
+    RELATIONSHIPS.with(|relationships| {
+        relationships.borrow()
+            .get(&family_id.to_string())
+            .unwrap_or_default()
+            .0
+    })
+}
+
+// Drop every edge touching a member, used when the member is removed from the family
+pub fn remove_member_relationships(family_id: &str, member_id: &str) {
+    RELATIONSHIPS.with(|relationships| {
+        let mut relationships = relationships.borrow_mut();
+        if let Some(mut list) = relationships.get(&family_id.to_string()) {
+            list.0.retain(|r| r.from_member_id != member_id && r.to_member_id != member_id);
+            relationships.insert(family_id.to_string(), list);
+        }
+    });
+}
+
+pub fn parents_of<'a>(relationships: &'a [FamilyRelationship], member_id: &str) -> Vec<&'a FamilyRelationship> {
+    relationships.iter()
+        .filter(|r| r.relationship_type == RelationshipType::ParentOf && r.to_member_id == member_id)
+        .collect()
+}
+
+pub fn children_of<'a>(relationships: &'a [FamilyRelationship], member_id: &str) -> Vec<&'a FamilyRelationship> {
+    relationships.iter()
+        .filter(|r| r.relationship_type == RelationshipType::ParentOf && r.from_member_id == member_id)
+        .collect()
+}
+
+pub fn spouses_of(relationships: &[FamilyRelationship], member_id: &str) -> Vec<String> {
+    relationships.iter()
+        .filter(|r| r.relationship_type == RelationshipType::SpouseOf)
+        .filter_map(|r| {
+            if r.from_member_id == member_id {
+                Some(r.to_member_id.clone())
+            } else if r.to_member_id == member_id {
+                Some(r.from_member_id.clone())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Returns true if `ancestor_id` can be reached by walking parent edges up from `member_id`
+pub fn is_ancestor(relationships: &[FamilyRelationship], ancestor_id: &str, member_id: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([member_id.to_string()]);
+
+    while let Some(current) = queue.pop_front() {
+        for edge in parents_of(relationships, &current) {
+            if edge.from_member_id == ancestor_id {
+                return true;
+            }
+            if visited.insert(edge.from_member_id.clone()) {
+                queue.push_back(edge.from_member_id.clone());
+            }
+        }
+    }
+
+    false
+}
+
+// Parses "YYYY", "YYYY-MM" or "YYYY-MM-DD" into [year, month, day]; anything else is not an ISO date
+pub fn iso_date(value: &str) -> Option<Vec<u32>> {
+    let parts: Vec<&str> = value.split('-').collect();
+    let widths = [4, 2, 2];
+    if parts.len() > widths.len() {
+        return None;
+    }
+    let fields = parts.iter().zip(widths)
+        .map(|(part, width)| (part.len() == width && part.bytes().all(|b| b.is_ascii_digit())).then(|| part.parse().ok()).flatten())
+        .collect::<Option<Vec<u32>>>()?;
+
+    if let Some(&month) = fields.get(1) {
+        if !(1..=12).contains(&month) {
+            return None;
+        }
+    }
+    if let Some(&day) = fields.get(2) {
+        let leap = fields[0] % 4 == 0 && (fields[0] % 100 != 0 || fields[0] % 400 == 0);
+        let days_in_month = match fields[1] {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        if !(1..=days_in_month).contains(&day) {
+            return None;
+        }
+    }
+    Some(fields)
+}
+
+pub fn validate_relationship(
+    members: &[FamilyMember],
+    existing: &[FamilyRelationship],
+    request: &AddRelationshipRequest,
+) -> Result<(), BackendError> {
+    if request.from_member_id == request.to_member_id {
+        return Err(BackendError::validation("to_member_id", "same_as_from_member"));
+    }
+
+    let member_exists = |id: &str| members.iter().any(|m| m.id == id);
+    if let Some(missing) = [&request.from_member_id, &request.to_member_id].into_iter().find(|id| !member_exists(id)) {
+        return Err(BackendError::not_found("member", missing));
+    }
+
+    let parse = |date: &Option<String>, field: &str| {
+        date.as_deref()
+            .map(|date| iso_date(date).ok_or_else(|| BackendError::validation(field, "invalid_date")))
+            .transpose()
+    };
+    let start = parse(&request.start_date, "start_date")?;
+    let end = parse(&request.end_date, "end_date")?;
+    if let (Some(start), Some(end)) = (start, end) {
+        // Compare only the precision both dates share, so "1990" does not end before "1990-06-01"
+        let shared = start.len().min(end.len());
+        if end[..shared] < start[..shared] {
+            return Err(BackendError::validation("end_date", "before_start_date"));
+        }
+    }
+
+    let same_pair = |r: &FamilyRelationship| match request.relationship_type {
+        RelationshipType::ParentOf => {
+            r.from_member_id == request.from_member_id && r.to_member_id == request.to_member_id
+        }
+        RelationshipType::SpouseOf => {
+            (r.from_member_id == request.from_member_id && r.to_member_id == request.to_member_id)
+                || (r.from_member_id == request.to_member_id && r.to_member_id == request.from_member_id)
+        }
+    };
+    if existing.iter().any(|r| r.relationship_type == request.relationship_type && same_pair(r)) {
+        return Err(BackendError::conflict("relationship_exists"));
+    }
+
+    if request.relationship_type == RelationshipType::ParentOf {
+        // The child must not already be an ancestor of the new parent
+        if is_ancestor(existing, &request.to_member_id, &request.from_member_id) {
+            return Err(BackendError::conflict("creates_ancestry_cycle"));
+        }
+
+        let qualifier = request.qualifier.clone().unwrap_or(RelationshipQualifier::Biological);
+        if qualifier == RelationshipQualifier::Biological {
+            let biological_parents = parents_of(existing, &request.to_member_id)
+                .into_iter()
+                .filter(|r| r.qualifier == RelationshipQualifier::Biological)
+                .count();
+            if biological_parents >= 2 {
+                return Err(BackendError::conflict("too_many_biological_parents"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::test_support::{self, member};
+    use crate::types::Family;
+
+    fn family(ids: &[&str]) -> Family {
+        test_support::family(ids.iter().map(|id| member(id, None)).collect())
+    }
+
+    fn request(from: &str, to: &str, relationship_type: RelationshipType) -> AddRelationshipRequest {
+        AddRelationshipRequest {
+            family_id: String::new(),
+            from_member_id: from.to_string(),
+            to_member_id: to.to_string(),
+            relationship_type,
+            qualifier: None,
+            start_date: None,
+            end_date: None,
+        }
+    }
+
+    fn edge(from: &str, to: &str, relationship_type: RelationshipType) -> FamilyRelationship {
+        FamilyRelationship {
+            id: format!("{}-{}", from, to),
+            family_id: String::new(),
+            from_member_id: from.to_string(),
+            to_member_id: to.to_string(),
+            relationship_type,
+            qualifier: RelationshipQualifier::Biological,
+            start_date: None,
+            end_date: None,
+            created_at: 0,
+            created_by: Principal::anonymous(),
+        }
+    }
+
+    #[test]
+    fn test_validate_relationship() {
+        let family = family(&["grandma", "mum", "dad", "kid"]);
+        let edges = vec![
+            edge("grandma", "mum", RelationshipType::ParentOf),
+            edge("mum", "kid", RelationshipType::ParentOf),
+        ];
+
+        // Valid edges
+        assert!(validate_relationship(&family.members, &edges, &request("dad", "kid", RelationshipType::ParentOf)).is_ok());
+        assert!(validate_relationship(&family.members, &edges, &request("mum", "dad", RelationshipType::SpouseOf)).is_ok());
+
+        // Invalid edges
+        assert!(validate_relationship(&family.members, &edges, &request("kid", "kid", RelationshipType::ParentOf)).is_err());
+        assert!(validate_relationship(&family.members, &edges, &request("mum", "stranger", RelationshipType::ParentOf)).is_err());
+        assert!(validate_relationship(&family.members, &edges, &request("mum", "kid", RelationshipType::ParentOf)).is_err());
+        assert!(validate_relationship(&family.members, &edges, &request("kid", "grandma", RelationshipType::ParentOf)).is_err());
+
+        let mut dated = request("mum", "dad", RelationshipType::SpouseOf);
+        dated.start_date = Some("1990-06-01".to_string());
+        dated.end_date = Some("1980-01-01".to_string());
+        assert!(validate_relationship(&family.members, &edges, &dated).is_err());
+
+        dated.end_date = Some("1990".to_string());
+        assert!(validate_relationship(&family.members, &edges, &dated).is_ok());
+        dated.end_date = Some("2001-02-29".to_string());
+        assert!(validate_relationship(&family.members, &edges, &dated).is_err());
+        dated.end_date = Some("2000-02-29".to_string());
+        assert!(validate_relationship(&family.members, &edges, &dated).is_ok());
+        dated.start_date = Some("June 1990".to_string());
+        assert!(validate_relationship(&family.members, &edges, &dated).is_err());
+    }
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(iso_date("1990"), Some(vec![1990]));
+        assert_eq!(iso_date("1990-06"), Some(vec![1990, 6]));
+        assert_eq!(iso_date("1990-06-30"), Some(vec![1990, 6, 30]));
+
+        assert_eq!(iso_date(""), None);
+        assert_eq!(iso_date("90-06-01"), None);
+        assert_eq!(iso_date("1990-6-1"), None);
+        assert_eq!(iso_date("1990-13"), None);
+        assert_eq!(iso_date("1990-06-31"), None);
+        assert_eq!(iso_date("1900-02-29"), None);
+        assert_eq!(iso_date("1990-06-01-01"), None);
+        assert_eq!(iso_date("+990-06-01"), None);
+    }
+
+    #[test]
+    fn test_biological_parent_limit() {
+        let family = family(&["mum", "dad", "stepdad", "kid"]);
+        let edges = vec![
+            edge("mum", "kid", RelationshipType::ParentOf),
+            edge("dad", "kid", RelationshipType::ParentOf),
+        ];
+
+        assert!(validate_relationship(&family.members, &edges, &request("stepdad", "kid", RelationshipType::ParentOf)).is_err());
+
+        let mut step = request("stepdad", "kid", RelationshipType::ParentOf);
+        step.qualifier = Some(RelationshipQualifier::Step);
+        assert!(validate_relationship(&family.members, &edges, &step).is_ok());
+    }
+
+    #[test]
+    fn test_spouse_is_symmetric() {
+        let family = family(&["mum", "dad"]);
+        let edges = vec![edge("mum", "dad", RelationshipType::SpouseOf)];
+
+        assert!(validate_relationship(&family.members, &edges, &request("dad", "mum", RelationshipType::SpouseOf)).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk_macros::*;
+
+use crate::types::{Family, FamilyRole, FamilyRoleAssignment, GrantRoleRequest, DEV_MODE};
+use crate::storage::{FAMILY_ROLES, PROFILES};
+use crate::error::BackendError;
+use super::{get_family_roster, link_user_family, unlink_user_family};
+
+// Actions that are gated by a caller's role in a family
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    View,
+    AddMembers,
+    EditMembers,
+    RemoveMembers,
+    AddEvents,
+    EditAnyEvent,
+    ManageRelationships,
+    ManageVisibility,
+    SendInvitations,
+    ProcessClaims,
+    ManageRoles,
+    ManageTreasury,
+}
+
+// The lowest role that allows a permission, reported when a caller lacks it
+pub fn minimum_role(permission: Permission) -> FamilyRole {
+    [FamilyRole::Viewer, FamilyRole::Contributor, FamilyRole::Editor, FamilyRole::CoAdmin]
+        .into_iter()
+        .find(|role| role_allows(*role, permission))
+        .unwrap_or(FamilyRole::Owner)
+}
+
+pub fn role_allows(role: FamilyRole, permission: Permission) -> bool {
+    use Permission::*;
+
+    match role {
+        FamilyRole::Owner | FamilyRole::CoAdmin => true,
+        FamilyRole::Editor => matches!(
+            permission,
+            View | AddMembers | EditMembers | AddEvents | EditAnyEvent | ManageRelationships
+        ),
+        FamilyRole::Contributor => matches!(permission, View | AddMembers | AddEvents),
+        FamilyRole::Viewer => permission == View,
+    }
+}
+
+// Owners hand out any role below Owner; co-admins only Editor and below
+fn can_assign(granter: FamilyRole, role: FamilyRole) -> bool {
+    match granter {
+        FamilyRole::Owner => role != FamilyRole::Owner,
+        FamilyRole::CoAdmin => !matches!(role, FamilyRole::Owner | FamilyRole::CoAdmin),
+        _ => false,
+    }
+}
+
+// A granter must be able to assign both the principal's current role and the new one
+pub fn check_assignment(granter: Option<FamilyRole>, current: Option<FamilyRole>, role: FamilyRole) -> Result<(), BackendError> {
+    let granter = match granter {
+        Some(granter) if role_allows(granter, Permission::ManageRoles) => granter,
+        _ => return Err(BackendError::requires(minimum_role(Permission::ManageRoles))),
+    };
+
+    if role == FamilyRole::Owner || current == Some(FamilyRole::Owner) {
+        return Err(BackendError::validation("role", "owner_not_assignable"));
+    }
+    if !can_assign(granter, role) || current.is_some_and(|current| !can_assign(granter, current)) {
+        return Err(BackendError::requires(FamilyRole::Owner));
+    }
+
+    Ok(())
+}
+
+#[update]
+pub fn grant_family_role(request: GrantRoleRequest) -> Result<FamilyRoleAssignment, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&request.family_id)?;
+    check_assignment(role_of(&family, caller), role_of(&family, request.principal), request.role)?;
+
+    if !PROFILES.with(|profiles| profiles.borrow().contains_key(&request.principal)) {
+        return Err(BackendError::not_found("profile", request.principal.to_text()));
+    }
+
+    let assignment = FamilyRoleAssignment {
+        principal: request.principal,
+        role: request.role,
+        granted_by: caller,
+        granted_at: api::time(),
+    };
+
+    FAMILY_ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        let mut list = roles.get(&request.family_id).unwrap_or_default();
+        list.0.retain(|a| a.principal != request.principal);
+        list.0.push(assignment.clone());
+        roles.insert(request.family_id.clone(), list);
+    });
+
+    // Make the family show up in the grantee's family list
+    link_user_family(request.principal, &request.family_id);
+
+    Ok(assignment)
+}
+
+// Remove an explicitly granted role; anyone may give up their own role
+#[update]
+pub fn revoke_family_role(family_id: String, principal: Principal) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    let current = explicit_role(&family_id, principal)
+        .ok_or(BackendError::not_found("role", principal.to_text()))?;
+
+    if caller != principal {
+        // Revoking is checked like granting the role that is being taken away
+        check_assignment(role_of(&family, caller), Some(current), current)?;
+    }
+
+    FAMILY_ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        let mut list = roles.get(&family_id).unwrap_or_default();
+        list.0.retain(|a| a.principal != principal);
+        roles.insert(family_id.clone(), list);
+    });
+
+    // Linked members keep the family in their list as viewers
+    if !is_linked_member(&family, principal) {
+        unlink_user_family(principal, &family_id);
+    }
+
+    Ok("Role revoked successfully".to_string())
+}
+
+// All roles in a family, starting with the owner
+#[query]
+pub fn get_family_roles(family_id: String) -> Result<Vec<FamilyRoleAssignment>, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    let owner = FamilyRoleAssignment {
+        principal: family.admin,
+        role: FamilyRole::Owner,
+        granted_by: family.admin,
+        granted_at: family.created_at,
+    };
+
+    let mut roles = vec![owner];
+    roles.extend(get_roles_internal(&family_id));
+    Ok(roles)
+}
+
+#[query]
+pub fn get_my_family_role(family_id: String) -> Result<FamilyRole, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    role_of(&family, caller).ok_or(BackendError::requires(FamilyRole::Viewer))
+}
+
+// Internal helper functions for other modules
+pub fn get_roles_internal(family_id: &str) -> Vec<FamilyRoleAssignment> {
+    FAMILY_ROLES.with(|roles| {
+        roles.borrow()
+            .get(&family_id.to_string())
+            .map(|list| list.0)
+            .unwrap_or_default()
+    })
+}
+
+fn explicit_role(family_id: &str, principal: Principal) -> Option<FamilyRole> {
+    get_roles_internal(family_id)
+        .into_iter()
+        .find(|a| a.principal == principal)
+        .map(|a| a.role)
+}
+
+pub fn is_linked_member(family: &Family, principal: Principal) -> bool {
+    family.members.iter().any(|member| member.profile_principal == Some(principal))
+}
+
+// Granted roles take precedence over the implicit Viewer role of linked members
+pub fn role_of(family: &Family, principal: Principal) -> Option<FamilyRole> {
+    if family.admin == principal {
+        return Some(FamilyRole::Owner);
+    }
+
+    explicit_role(&family.id, principal)
+        .or_else(|| is_linked_member(family, principal).then_some(FamilyRole::Viewer))
+}
+
+pub fn has_permission(family: &Family, principal: Principal, permission: Permission) -> bool {
+    role_of(family, principal).is_some_and(|role| role_allows(role, permission))
+}
+
+pub fn require_permission(family: &Family, principal: Principal, permission: Permission) -> Result<(), BackendError> {
+    // Any permission-checked action counts as the owner being active
+    if principal == family.admin {
+        super::succession::record_owner_activity(family, api::time());
+    }
+
+    if has_permission(family, principal, permission) {
+        Ok(())
+    } else {
+        Err(BackendError::requires(minimum_role(permission)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::test_support::{family, member, user};
+
+    fn family_with_linked_member(linked: Principal) -> Family {
+        family(vec![member("m1", Some(linked))])
+    }
+
+    fn grant(family_id: &str, principal: Principal, role: FamilyRole) {
+        FAMILY_ROLES.with(|roles| {
+            let mut roles = roles.borrow_mut();
+            let mut list = roles.get(&family_id.to_string()).unwrap_or_default();
+            list.0.push(FamilyRoleAssignment {
+                principal,
+                role,
+                granted_by: user(1),
+                granted_at: 0,
+            });
+            roles.insert(family_id.to_string(), list);
+        });
+    }
+
+    #[test]
+    fn test_role_capabilities() {
+        assert!(role_allows(FamilyRole::CoAdmin, Permission::ProcessClaims));
+        assert!(role_allows(FamilyRole::Editor, Permission::ManageRelationships));
+        assert!(!role_allows(FamilyRole::Editor, Permission::RemoveMembers));
+        assert!(!role_allows(FamilyRole::Editor, Permission::SendInvitations));
+        assert!(role_allows(FamilyRole::Contributor, Permission::AddEvents));
+        assert!(!role_allows(FamilyRole::Contributor, Permission::EditAnyEvent));
+        assert!(role_allows(FamilyRole::Viewer, Permission::View));
+        assert!(!role_allows(FamilyRole::Viewer, Permission::AddMembers));
+    }
+
+    #[test]
+    fn test_role_resolution() {
+        let family = family_with_linked_member(user(2));
+
+        assert_eq!(role_of(&family, user(1)), Some(FamilyRole::Owner));
+        assert_eq!(role_of(&family, user(2)), Some(FamilyRole::Viewer));
+        assert_eq!(role_of(&family, user(3)), None);
+
+        // A granted role overrides the implicit viewer role
+        grant("f1", user(2), FamilyRole::Editor);
+        grant("f1", user(3), FamilyRole::Contributor);
+        assert_eq!(role_of(&family, user(2)), Some(FamilyRole::Editor));
+        assert!(has_permission(&family, user(3), Permission::AddMembers));
+        assert!(require_permission(&family, user(3), Permission::ManageVisibility).is_err());
+    }
+
+    #[test]
+    fn test_assignment_rules() {
+        use FamilyRole::*;
+
+        assert!(check_assignment(Some(Owner), None, CoAdmin).is_ok());
+        assert!(check_assignment(Some(Owner), Some(CoAdmin), Viewer).is_ok());
+        assert!(check_assignment(Some(CoAdmin), Some(Viewer), Editor).is_ok());
+
+        // Co-admins cannot create or demote other co-admins
+        assert!(check_assignment(Some(CoAdmin), None, CoAdmin).is_err());
+        assert!(check_assignment(Some(CoAdmin), Some(CoAdmin), Viewer).is_err());
+
+        // Ownership is never granted through roles
+        assert!(check_assignment(Some(Owner), None, Owner).is_err());
+        assert!(check_assignment(Some(CoAdmin), Some(Owner), Viewer).is_err());
+
+        assert!(check_assignment(Some(Editor), None, Viewer).is_err());
+        assert!(check_assignment(None, None, Viewer).is_err());
+    }
+
+    #[test]
+    fn test_minimum_roles() {
+        assert_eq!(minimum_role(Permission::View), FamilyRole::Viewer);
+        assert_eq!(minimum_role(Permission::AddEvents), FamilyRole::Contributor);
+        assert_eq!(minimum_role(Permission::EditAnyEvent), FamilyRole::Editor);
+        assert_eq!(minimum_role(Permission::ManageTreasury), FamilyRole::CoAdmin);
+    }
+}
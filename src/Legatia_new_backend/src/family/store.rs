@@ -0,0 +1,413 @@
+use ic_stable_structures::storable::{Bound, Storable};
+use std::borrow::Cow;
+use std::ops::Bound::{Excluded, Unbounded};
+
+use crate::types::{Family, FamilyEvent, FamilyMember};
+use crate::error::BackendError;
+use crate::storage::{FAMILIES, FAMILY_MEMBERS, MEMBER_EVENTS};
+
+// Families are stored normalised so a write only re-serialises what changed:
+// FAMILIES holds each family without its members, FAMILY_MEMBERS holds members without
+// their events keyed by (family ID, member ID), and MEMBER_EVENTS holds events keyed
+// by (family ID, member ID, event ID). Member IDs are only unique within a family, so
+// events carry the family ID too. `load_family` puts a whole `Family` back together.
+
+// Generated IDs are well under this; longer caller-supplied IDs can't name a stored record
+const MAX_KEY_PART_LENGTH: usize = 64;
+
+fn check_key_parts(parts: &[&str]) -> Result<(), BackendError> {
+    if parts.iter().any(|part| part.len() > MAX_KEY_PART_LENGTH) {
+        return Err(BackendError::validation("id", "too_long"));
+    }
+    Ok(())
+}
+
+// Key of a record under its parent; a parent's records form one contiguous range
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChildKey {
+    pub parent: String,
+    pub id: String,
+}
+
+impl ChildKey {
+    pub fn new(parent: &str, id: &str) -> Result<Self, BackendError> {
+        check_key_parts(&[parent, id])?;
+        Ok(ChildKey { parent: parent.to_string(), id: id.to_string() })
+    }
+}
+
+impl Storable for ChildKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(1 + self.parent.len() + self.id.len());
+        bytes.push(self.parent.len() as u8);
+        bytes.extend_from_slice(self.parent.as_bytes());
+        bytes.extend_from_slice(self.id.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let parent_end = 1 + bytes[0] as usize;
+        ChildKey {
+            parent: String::from_utf8(bytes[1..parent_end].to_vec()).unwrap(),
+            id: String::from_utf8(bytes[parent_end..].to_vec()).unwrap(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: (1 + 2 * MAX_KEY_PART_LENGTH) as u32,
+        is_fixed_size: false,
+    };
+}
+
+// Key of an event under its family and member; a member's events form one contiguous range
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventKey {
+    pub family_id: String,
+    pub member_id: String,
+    pub id: String,
+}
+
+impl EventKey {
+    pub fn new(family_id: &str, member_id: &str, id: &str) -> Result<Self, BackendError> {
+        check_key_parts(&[family_id, member_id, id])?;
+        Ok(EventKey { family_id: family_id.to_string(), member_id: member_id.to_string(), id: id.to_string() })
+    }
+}
+
+impl Storable for EventKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(2 + self.family_id.len() + self.member_id.len() + self.id.len());
+        bytes.push(self.family_id.len() as u8);
+        bytes.extend_from_slice(self.family_id.as_bytes());
+        bytes.push(self.member_id.len() as u8);
+        bytes.extend_from_slice(self.member_id.as_bytes());
+        bytes.extend_from_slice(self.id.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let family_end = 1 + bytes[0] as usize;
+        let member_end = family_end + 1 + bytes[family_end] as usize;
+        EventKey {
+            family_id: String::from_utf8(bytes[1..family_end].to_vec()).unwrap(),
+            member_id: String::from_utf8(bytes[family_end + 1..member_end].to_vec()).unwrap(),
+            id: String::from_utf8(bytes[member_end..].to_vec()).unwrap(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: (2 + 3 * MAX_KEY_PART_LENGTH) as u32,
+        is_fixed_size: false,
+    };
+}
+
+// The family record alone, without members
+pub fn load_family_header(family_id: &str) -> Option<Family> {
+    FAMILIES.with(|families| families.borrow().get(&family_id.to_string()))
+}
+
+pub fn load_family(family_id: &str) -> Option<Family> {
+    let mut family = load_family_roster(family_id)?;
+    for member in &mut family.members {
+        member.events = load_events(family_id, &member.id);
+    }
+    Some(family)
+}
+
+// The family record and its members, without their events
+pub fn load_family_roster(family_id: &str) -> Option<Family> {
+    let mut family = load_family_header(family_id)?;
+    family.members = load_members(family_id);
+    Some(family)
+}
+
+pub fn family_ids() -> Vec<String> {
+    FAMILIES.with(|families| families.borrow().iter().map(|(id, _)| id).collect())
+}
+
+// Up to `limit` family IDs in key order, starting after `after`
+pub fn family_ids_after(after: &str, limit: usize) -> Vec<String> {
+    FAMILIES.with(|families| {
+        families.borrow()
+            .range((Excluded(after.to_string()), Unbounded))
+            .take(limit)
+            .map(|(id, _)| id)
+            .collect()
+    })
+}
+
+// Members in the order they were added, without their events
+fn load_members(family_id: &str) -> Vec<FamilyMember> {
+    let Ok(start) = ChildKey::new(family_id, "") else { return Vec::new() };
+    let mut members: Vec<FamilyMember> = FAMILY_MEMBERS.with(|members| {
+        members.borrow()
+            .range(start..)
+            .take_while(|(key, _)| key.parent == family_id)
+            .map(|(_, member)| member)
+            .collect()
+    });
+    members.sort_by_key(|member| member.created_at);
+    members
+}
+
+// Up to `limit` members in key order after the member `after`, without their events
+pub fn members_after(family_id: &str, after: Option<&str>, limit: usize) -> Result<Vec<FamilyMember>, BackendError> {
+    let start = ChildKey::new(family_id, after.unwrap_or(""))?;
+    Ok(FAMILY_MEMBERS.with(|members| {
+        members.borrow()
+            .range((Excluded(start), Unbounded))
+            .take_while(|(key, _)| key.parent == family_id)
+            .take(limit)
+            .map(|(_, member)| member)
+            .collect()
+    }))
+}
+
+// Up to `limit` events that `keep` accepts, in key order after the (member ID, event ID)
+// `after`; only the events of `member_id` when it is given
+pub fn events_after(
+    family_id: &str,
+    member_id: Option<&str>,
+    after: Option<(&str, &str)>,
+    limit: usize,
+    keep: impl Fn(&FamilyEvent) -> bool,
+) -> Result<Vec<FamilyEvent>, BackendError> {
+    let start = match after {
+        Some((after_member, after_event)) => EventKey::new(family_id, after_member, after_event)?,
+        None => EventKey::new(family_id, member_id.unwrap_or(""), "")?,
+    };
+    Ok(MEMBER_EVENTS.with(|events| {
+        events.borrow()
+            .range((Excluded(start), Unbounded))
+            .take_while(|(key, _)| key.family_id == family_id && member_id.is_none_or(|id| key.member_id == id))
+            .map(|(_, event)| event)
+            .filter(|event| keep(event))
+            .take(limit)
+            .collect()
+    }))
+}
+
+// A member's events by date
+pub fn load_events(family_id: &str, member_id: &str) -> Vec<FamilyEvent> {
+    let Ok(start) = EventKey::new(family_id, member_id, "") else { return Vec::new() };
+    let mut events: Vec<FamilyEvent> = MEMBER_EVENTS.with(|events| {
+        events.borrow()
+            .range(start..)
+            .take_while(|(key, _)| key.family_id == family_id && key.member_id == member_id)
+            .map(|(_, event)| event)
+            .collect()
+    });
+    events.sort_by(|a, b| a.event_date.cmp(&b.event_date));
+    events
+}
+
+pub fn load_event(family_id: &str, member_id: &str, event_id: &str) -> Option<FamilyEvent> {
+    let key = EventKey::new(family_id, member_id, event_id).ok()?;
+    MEMBER_EVENTS.with(|events| events.borrow().get(&key))
+}
+
+pub fn member_exists(family_id: &str, member_id: &str) -> bool {
+    ChildKey::new(family_id, member_id)
+        .is_ok_and(|key| FAMILY_MEMBERS.with(|members| members.borrow().contains_key(&key)))
+}
+
+// Writes the family record only; members and events are left as they are
+pub fn save_family_header(family: &Family) {
+    let header = Family { members: Vec::new(), ..family.clone() };
+    FAMILIES.with(|families| families.borrow_mut().insert(family.id.clone(), header));
+}
+
+// Writes the member record only; its events are left as they are
+pub fn save_member(family_id: &str, member: &FamilyMember) -> Result<(), BackendError> {
+    let key = ChildKey::new(family_id, &member.id)?;
+    let record = FamilyMember { events: Vec::new(), ..member.clone() };
+    FAMILY_MEMBERS.with(|members| members.borrow_mut().insert(key, record));
+    Ok(())
+}
+
+pub fn save_event(family_id: &str, event: &FamilyEvent) -> Result<(), BackendError> {
+    let key = EventKey::new(family_id, &event.member_id, &event.id)?;
+    MEMBER_EVENTS.with(|events| events.borrow_mut().insert(key, event.clone()));
+    Ok(())
+}
+
+// Removing a record that can't exist is a no-op
+pub fn remove_event(family_id: &str, member_id: &str, event_id: &str) {
+    if let Ok(key) = EventKey::new(family_id, member_id, event_id) {
+        MEMBER_EVENTS.with(|events| events.borrow_mut().remove(&key));
+    }
+}
+
+pub fn remove_member(family_id: &str, member_id: &str) {
+    if let Ok(key) = ChildKey::new(family_id, member_id) {
+        FAMILY_MEMBERS.with(|members| members.borrow_mut().remove(&key));
+    }
+    for event_id in event_ids(family_id, member_id) {
+        remove_event(family_id, member_id, &event_id);
+    }
+}
+
+// Stores a whole family, removing members and events that are no longer in it
+pub fn save_family(family: &Family) -> Result<(), BackendError> {
+    // Check every key before writing anything
+    for member in &family.members {
+        check_key_parts(&[&family.id, &member.id])?;
+        for event in &member.events {
+            check_key_parts(&[&family.id, &event.member_id, &event.id])?;
+        }
+    }
+    save_family_header(family);
+
+    for member_id in member_ids(&family.id) {
+        if !family.members.iter().any(|member| member.id == member_id) {
+            remove_member(&family.id, &member_id);
+        }
+    }
+
+    for member in &family.members {
+        save_member(&family.id, member)?;
+        for event_id in event_ids(&family.id, &member.id) {
+            if !member.events.iter().any(|event| event.id == event_id) {
+                remove_event(&family.id, &member.id, &event_id);
+            }
+        }
+        for event in &member.events {
+            save_event(&family.id, event)?;
+        }
+    }
+    Ok(())
+}
+
+fn member_ids(family_id: &str) -> Vec<String> {
+    let Ok(start) = ChildKey::new(family_id, "") else { return Vec::new() };
+    FAMILY_MEMBERS.with(|members| {
+        members.borrow()
+            .keys_range(start..)
+            .take_while(|key| key.parent == family_id)
+            .map(|key| key.id)
+            .collect()
+    })
+}
+
+fn event_ids(family_id: &str, member_id: &str) -> Vec<String> {
+    let Ok(start) = EventKey::new(family_id, member_id, "") else { return Vec::new() };
+    MEMBER_EVENTS.with(|events| {
+        events.borrow()
+            .keys_range(start..)
+            .take_while(|key| key.family_id == family_id && key.member_id == member_id)
+            .map(|key| key.id)
+            .collect()
+    })
+}
+
+// Families written before the split kept their members inline. Moves them into the
+// member and event maps; families already split have no inline members and are skipped.
+pub fn migrate_inline_members() -> u64 {
+    let mut migrated = 0;
+    for family_id in family_ids() {
+        if let Some(family) = load_family_header(&family_id) {
+            if !family.members.is_empty() {
+                save_family(&family).unwrap_or_else(|e| panic!("Failed to migrate family {}: {}", family_id, e));
+                migrated += 1;
+            }
+        }
+    }
+    migrated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::test_support::{event, family, member as base_member};
+
+    fn member(id: &str, created_at: u64, events: Vec<FamilyEvent>) -> FamilyMember {
+        FamilyMember { created_at, events, ..base_member(id, None) }
+    }
+
+
+    #[test]
+    fn test_child_key_round_trip() {
+        let key = ChildKey::new("f1", "member_1700000000000000000").unwrap();
+        assert_eq!(ChildKey::from_bytes(key.to_bytes()), key);
+        let key = EventKey::new("f1", "member_1", "event_1700000000000000000").unwrap();
+        assert_eq!(EventKey::from_bytes(key.to_bytes()), key);
+    }
+
+    #[test]
+    fn test_overlong_ids_are_rejected_not_trapped() {
+        let long_id = "m".repeat(MAX_KEY_PART_LENGTH + 1);
+        assert!(!member_exists("f1", &long_id));
+        assert!(save_member("f1", &member(&long_id, 1, vec![])).is_err());
+        assert!(save_family(&family(vec![member(&long_id, 1, vec![])])).is_err());
+        assert!(load_family_header("f1").is_none());
+        remove_member("f1", &long_id);
+    }
+
+    #[test]
+    fn test_migrate_inline_members() {
+        // A family stored the old way, with members and events inline
+        let old = family(vec![
+            member("b", 2, vec![event("e2", "b", "2001-01-01"), event("e1", "b", "1990-05-01")]),
+            member("a", 1, vec![]),
+        ]);
+        FAMILIES.with(|f| f.borrow_mut().insert(old.id.clone(), old));
+
+        assert_eq!(migrate_inline_members(), 1);
+        assert!(load_family_header("f1").unwrap().members.is_empty());
+        assert_eq!(migrate_inline_members(), 0);
+
+        let loaded = load_family("f1").unwrap();
+        assert_eq!(loaded.members.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(loaded.members[1].events.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["e1", "e2"]);
+    }
+
+    #[test]
+    fn test_save_family_removes_dropped_records() {
+        save_family(&family(vec![
+            member("a", 1, vec![event("e1", "a", "1990-01-01"), event("e2", "a", "1991-01-01")]),
+            member("b", 2, vec![event("e3", "b", "1992-01-01")]),
+        ])).unwrap();
+        save_family(&family(vec![member("a", 1, vec![event("e2", "a", "1991-01-01")])])).unwrap();
+
+        let loaded = load_family("f1").unwrap();
+        assert_eq!(loaded.members.len(), 1);
+        assert_eq!(loaded.members[0].events.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["e2"]);
+        assert!(!member_exists("f1", "b"));
+        assert!(event_ids("f1", "b").is_empty());
+    }
+
+    #[test]
+    fn test_same_member_id_in_two_families() {
+        save_family(&family(vec![member("m", 1, vec![event("e1", "m", "1990-01-01")])])).unwrap();
+        let other = Family { id: "f2".to_string(), ..family(vec![member("m", 1, vec![event("e2", "m", "1991-01-01")])]) };
+        save_family(&other).unwrap();
+
+        assert_eq!(load_family("f1").unwrap().members[0].events.len(), 1);
+        remove_member("f2", "m");
+        assert_eq!(load_family("f1").unwrap().members[0].events[0].id, "e1");
+        assert!(event_ids("f2", "m").is_empty());
+    }
+
+    #[test]
+    fn test_paging_over_stored_ranges() {
+        save_family(&family(vec![
+            member("a", 1, vec![event("e1", "a", "1990-01-01"), event("e2", "a", "1991-01-01")]),
+            member("b", 2, vec![event("e3", "b", "1992-01-01")]),
+            member("c", 3, vec![]),
+        ])).unwrap();
+        // Another family's records sort right after and must not leak in
+        let other = Family { id: "f2".to_string(), ..family(vec![member("a", 1, vec![event("e4", "a", "1993-01-01")])]) };
+        save_family(&other).unwrap();
+
+        let ids = |members: Vec<FamilyMember>| members.into_iter().map(|m| m.id).collect::<Vec<_>>();
+        assert_eq!(ids(members_after("f1", None, 2).unwrap()), vec!["a", "b"]);
+        assert_eq!(ids(members_after("f1", Some("b"), 2).unwrap()), vec!["c"]);
+
+        let listed = |events: Vec<FamilyEvent>| events.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(listed(events_after("f1", None, None, 10, |_| true).unwrap()), vec!["e1", "e2", "e3"]);
+        assert_eq!(listed(events_after("f1", None, Some(("a", "e2")), 10, |_| true).unwrap()), vec!["e3"]);
+        assert_eq!(listed(events_after("f1", Some("a"), Some(("a", "e1")), 10, |_| true).unwrap()), vec!["e2"]);
+        let later = events_after("f1", None, None, 10, |e| e.event_date.as_str() > "1991").unwrap();
+        assert_eq!(listed(later), vec!["e2", "e3"]);
+    }
+}
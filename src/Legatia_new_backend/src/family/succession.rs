@@ -0,0 +1,559 @@
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::types::{
+    Family, FamilyRole, FamilyRoleAssignment, FamilySuccession, OwnershipOffer, SuccessionVote,
+    NotificationType, DEV_MODE
+};
+use crate::storage::{FAMILY_ROLES, PROFILES, SUCCESSION};
+use crate::error::BackendError;
+use super::{store, get_family_roster, has_family_access, link_user_family, unlink_user_family};
+use super::roles::{is_linked_member, require_permission, Permission};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+pub const DEFAULT_DORMANCY_DAYS: u32 = 365;
+pub const MIN_DORMANCY_DAYS: u32 = 30;
+pub const MAX_DORMANCY_DAYS: u32 = 5 * 365;
+const OFFER_VALIDITY_DAYS: u64 = 7;
+const VOTE_DURATION_DAYS: u64 = 14;
+const SUCCESSION_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+// Families checked per timer callback, to stay well within instruction limits
+const SUCCESSION_BATCH_SIZE: usize = 50;
+
+thread_local! {
+    // The last family checked by the running sweep; None when no sweep is running. An
+    // upgrade drops it, and the next daily check starts the sweep over.
+    static SWEEP_CURSOR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// What the daily succession check should do for a family
+#[derive(Debug, PartialEq)]
+pub enum SuccessionStep {
+    Nothing,
+    AppointSuccessor(Principal),
+    OpenVote,
+    CloseVote(Option<Principal>),
+}
+
+#[update]
+pub fn offer_family_ownership(family_id: String, to: Principal) -> Result<OwnershipOffer, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_owner(&family, caller)?;
+
+    if to == caller {
+        return Err(BackendError::validation("to", "already_owner"));
+    }
+    if !PROFILES.with(|profiles| profiles.borrow().contains_key(&to)) {
+        return Err(BackendError::not_found("profile", to.to_text()));
+    }
+
+    let now = api::time();
+    let offer = OwnershipOffer {
+        to,
+        offered_at: now,
+        expires_at: now + OFFER_VALIDITY_DAYS * NANOS_PER_DAY,
+    };
+
+    let mut state = get_succession_internal(&family);
+    state.pending_offer = Some(offer.clone());
+    save_succession(&family_id, state);
+
+    crate::invitations::create_notification(
+        to,
+        "Family ownership offered".to_string(),
+        format!("You have been offered ownership of the family '{}'", family.name),
+        NotificationType::FamilyUpdate,
+        Some(format!("/family/{}", family_id)),
+        None,
+    );
+
+    Ok(offer)
+}
+
+#[update]
+pub fn accept_family_ownership(family_id: String) -> Result<Family, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    let now = api::time();
+
+    match get_succession_internal(&family).pending_offer {
+        Some(offer) if offer.to == caller && offer.expires_at > now => {}
+        Some(offer) if offer.to == caller => return Err(BackendError::conflict("ownership_offer_expired")),
+        _ => return Err(BackendError::not_found("ownership_offer", &family_id)),
+    }
+
+    // The previous owner stays on as a co-admin
+    transfer_ownership(&family_id, caller, Some(FamilyRole::CoAdmin), now)
+}
+
+// Withdraw an offer as the owner, or decline it as the recipient
+#[update]
+pub fn cancel_ownership_offer(family_id: String) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    let mut state = get_succession_internal(&family);
+
+    match &state.pending_offer {
+        Some(offer) if caller == family.admin || caller == offer.to => {}
+        Some(_) => return Err(BackendError::requires(FamilyRole::Owner)),
+        None => return Err(BackendError::not_found("ownership_offer", &family_id)),
+    }
+
+    state.pending_offer = None;
+    save_succession(&family_id, state);
+    Ok("Ownership offer cancelled".to_string())
+}
+
+// The successor inherits the family if the owner stays inactive for the dormancy period
+#[update]
+pub fn set_family_successor(family_id: String, successor: Option<Principal>) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_owner(&family, caller)?;
+
+    if let Some(successor) = successor {
+        if !is_eligible_candidate(&family, successor) {
+            return Err(BackendError::validation("successor", "not_a_family_member"));
+        }
+    }
+
+    let mut state = get_succession_internal(&family);
+    state.successor = successor;
+    save_succession(&family_id, state);
+
+    Ok(match successor {
+        Some(_) => "Successor designated".to_string(),
+        None => "Successor cleared".to_string(),
+    })
+}
+
+#[update]
+pub fn set_dormancy_period(family_id: String, days: u32) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    if !(MIN_DORMANCY_DAYS..=MAX_DORMANCY_DAYS).contains(&days) {
+        return Err(BackendError::validation("days", "out_of_range"));
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_owner(&family, caller)?;
+
+    let mut state = get_succession_internal(&family);
+    state.dormancy_period_days = days;
+    save_succession(&family_id, state);
+
+    Ok(format!("Dormancy period set to {} days", days))
+}
+
+// Linked members vote for a new owner while a succession vote is open
+#[update]
+pub fn vote_for_successor(family_id: String, candidate: Principal) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    let mut state = get_succession_internal(&family);
+    let now = api::time();
+
+    let mut vote = match state.vote.take() {
+        Some(vote) if vote.closes_at > now => vote,
+        _ => return Err(BackendError::conflict("no_open_succession_vote")),
+    };
+
+    let voters = eligible_voters(&family);
+    if !voters.contains(&caller) {
+        return Err(BackendError::forbidden());
+    }
+    if !is_eligible_candidate(&family, candidate) {
+        return Err(BackendError::validation("candidate", "not_a_family_member"));
+    }
+
+    vote.ballots.retain(|(voter, _)| *voter != caller);
+    vote.ballots.push((caller, candidate));
+
+    // A majority of eligible voters settles the vote straight away
+    if let Some(winner) = tally(&vote.ballots, &voters, false) {
+        transfer_ownership(&family_id, winner, None, now)?;
+        crate::invitations::create_notification(
+            winner,
+            "You are now the family owner".to_string(),
+            format!("Family members appointed you as owner of '{}'", family.name),
+            NotificationType::FamilyUpdate,
+            Some(format!("/family/{}", family_id)),
+            None,
+        );
+        return Ok("Vote recorded. A new owner has been appointed".to_string());
+    }
+
+    state.vote = Some(vote);
+    save_succession(&family_id, state);
+    Ok("Vote recorded".to_string())
+}
+
+// Owner activity is only seen on update calls, so an owner who just reads the family
+// counts as dormant; this lets them reset the clock and end any open succession vote
+#[update]
+pub fn confirm_family_ownership(family_id: String) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = store::load_family_header(&family_id)
+        .ok_or_else(|| BackendError::not_found("family", &family_id))?;
+    require_owner(&family, caller)?;
+
+    Ok("Ownership confirmed".to_string())
+}
+
+#[query]
+pub fn get_family_succession(family_id: String) -> Result<FamilySuccession, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    Ok(get_succession_internal(&family))
+}
+
+// Internal helper functions for other modules
+pub fn get_succession_internal(family: &Family) -> FamilySuccession {
+    SUCCESSION.with(|s| s.borrow().get(&family.id)).unwrap_or(FamilySuccession {
+        successor: None,
+        dormancy_period_days: DEFAULT_DORMANCY_DAYS,
+        // Families that predate succession tracking count from their last update
+        owner_last_active: family.updated_at,
+        pending_offer: None,
+        vote: None,
+    })
+}
+
+fn save_succession(family_id: &str, state: FamilySuccession) {
+    SUCCESSION.with(|s| s.borrow_mut().insert(family_id.to_string(), state));
+}
+
+fn require_owner(family: &Family, caller: Principal) -> Result<(), BackendError> {
+    if family.admin != caller {
+        return Err(BackendError::requires(FamilyRole::Owner));
+    }
+    record_owner_activity(family, api::time());
+    Ok(())
+}
+
+// An active owner ends any succession vote that was opened in their absence
+pub fn record_owner_activity(family: &Family, now: u64) {
+    let mut state = get_succession_internal(family);
+    state.owner_last_active = now;
+    state.vote = None;
+    save_succession(&family.id, state);
+}
+
+fn eligible_voters(family: &Family) -> Vec<Principal> {
+    let mut voters: Vec<Principal> = family.members.iter()
+        .filter_map(|member| member.profile_principal)
+        .filter(|principal| *principal != family.admin)
+        .collect();
+    voters.sort();
+    voters.dedup();
+    voters
+}
+
+fn is_eligible_candidate(family: &Family, principal: Principal) -> bool {
+    principal != family.admin && has_family_access(family, principal)
+}
+
+// Majority of eligible voters wins; once the vote has closed a clear plurality is enough
+pub fn tally(ballots: &[(Principal, Principal)], voters: &[Principal], closed: bool) -> Option<Principal> {
+    let mut counts: HashMap<Principal, usize> = HashMap::new();
+    for (voter, candidate) in ballots {
+        if voters.contains(voter) {
+            *counts.entry(*candidate).or_insert(0) += 1;
+        }
+    }
+
+    let top = counts.values().copied().max()?;
+    let leaders: Vec<Principal> = counts.iter()
+        .filter(|(_, count)| **count == top)
+        .map(|(candidate, _)| *candidate)
+        .collect();
+
+    if top * 2 > voters.len() || (closed && leaders.len() == 1) {
+        leaders.first().copied()
+    } else {
+        None
+    }
+}
+
+pub fn next_step(family: &Family, state: &FamilySuccession, now: u64) -> SuccessionStep {
+    let dormant_for = now.saturating_sub(state.owner_last_active);
+    if dormant_for < state.dormancy_period_days as u64 * NANOS_PER_DAY {
+        return SuccessionStep::Nothing;
+    }
+
+    if let Some(successor) = state.successor.filter(|s| is_eligible_candidate(family, *s)) {
+        return SuccessionStep::AppointSuccessor(successor);
+    }
+
+    match &state.vote {
+        None => SuccessionStep::OpenVote,
+        Some(vote) if vote.closes_at <= now => {
+            SuccessionStep::CloseVote(tally(&vote.ballots, &eligible_voters(family), true))
+        }
+        Some(_) => SuccessionStep::Nothing,
+    }
+}
+
+fn open_vote(now: u64) -> SuccessionVote {
+    SuccessionVote {
+        opened_at: now,
+        closes_at: now + VOTE_DURATION_DAYS * NANOS_PER_DAY,
+        ballots: Vec::new(),
+    }
+}
+
+// Hand the family to a new owner and reset its succession state
+pub fn transfer_ownership(
+    family_id: &str,
+    new_owner: Principal,
+    previous_owner_role: Option<FamilyRole>,
+    now: u64,
+) -> Result<Family, BackendError> {
+    let mut family = get_family_roster(family_id)?;
+    let previous_owner = family.admin;
+    family.admin = new_owner;
+    family.updated_at = now;
+    store::save_family_header(&family);
+
+    FAMILY_ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        let mut list = roles.get(&family_id.to_string()).unwrap_or_default();
+        list.0.retain(|a| a.principal != new_owner && a.principal != previous_owner);
+        if let Some(role) = previous_owner_role {
+            list.0.push(FamilyRoleAssignment {
+                principal: previous_owner,
+                role,
+                granted_by: new_owner,
+                granted_at: now,
+            });
+        }
+        roles.insert(family_id.to_string(), list);
+    });
+
+    link_user_family(new_owner, family_id);
+    if previous_owner_role.is_none() && !is_linked_member(&family, previous_owner) {
+        unlink_user_family(previous_owner, family_id);
+    }
+
+    let mut state = get_succession_internal(&family);
+    state.owner_last_active = now;
+    state.pending_offer = None;
+    state.vote = None;
+    if state.successor == Some(new_owner) {
+        state.successor = None;
+    }
+    save_succession(family_id, state);
+
+    Ok(family)
+}
+
+pub fn start_succession_timer() {
+    ic_cdk_timers::set_timer_interval(SUCCESSION_CHECK_INTERVAL, start_succession_sweep);
+}
+
+fn start_succession_sweep() {
+    // A sweep still working through its batches carries on from its cursor
+    if SWEEP_CURSOR.with(|cursor| cursor.borrow().is_some()) {
+        return;
+    }
+    SWEEP_CURSOR.with(|cursor| *cursor.borrow_mut() = Some(String::new()));
+    continue_succession_sweep();
+}
+
+// Checks one batch per callback and schedules the next until every family is done
+fn continue_succession_sweep() {
+    let Some(after) = SWEEP_CURSOR.with(|cursor| cursor.borrow().clone()) else { return };
+    let next = check_succession_batch(&after, api::time());
+    let more = next.is_some();
+    SWEEP_CURSOR.with(|cursor| *cursor.borrow_mut() = next);
+    if more {
+        ic_cdk_timers::set_timer(Duration::ZERO, continue_succession_sweep);
+    }
+}
+
+// Checks the families after `after`; returns where the next batch starts, if anywhere
+fn check_succession_batch(after: &str, now: u64) -> Option<String> {
+    let batch = store::family_ids_after(after, SUCCESSION_BATCH_SIZE);
+    for family_id in &batch {
+        check_family_succession(family_id, now);
+    }
+    if batch.len() < SUCCESSION_BATCH_SIZE {
+        None
+    } else {
+        batch.last().cloned()
+    }
+}
+
+// Expire a stale offer and move a dormant family through succession. Only the family
+// record and its members are loaded; the checks never look at events.
+fn check_family_succession(family_id: &str, now: u64) {
+    let Some(family) = store::load_family_roster(family_id) else { return };
+    let mut state = get_succession_internal(&family);
+    if state.pending_offer.as_ref().is_some_and(|offer| offer.expires_at <= now) {
+        state.pending_offer = None;
+        save_succession(&family.id, state.clone());
+    }
+
+    match next_step(&family, &state, now) {
+        SuccessionStep::Nothing => {}
+        SuccessionStep::AppointSuccessor(successor) | SuccessionStep::CloseVote(Some(successor)) => {
+            let _ = transfer_ownership(&family.id, successor, None, now);
+        }
+        // Without a winner the members get another round
+        SuccessionStep::OpenVote | SuccessionStep::CloseVote(None) => {
+            state.vote = Some(open_vote(now));
+            save_succession(&family.id, state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::test_support::{member, user};
+
+    // Owner user(1) with linked members user(2), user(3), user(4) and one ghost
+    fn family() -> Family {
+        Family {
+            id: "f1".to_string(),
+            admin: user(1),
+            members: vec![
+                member("m2", Some(user(2))),
+                member("m3", Some(user(3))),
+                member("m4", Some(user(4))),
+                member("ghost", None),
+            ],
+            ..Family::default()
+        }
+    }
+
+    const DORMANT: u64 = (DEFAULT_DORMANCY_DAYS as u64 + 1) * NANOS_PER_DAY;
+
+    #[test]
+    fn test_tally() {
+        let voters = vec![user(2), user(3), user(4)];
+
+        assert_eq!(tally(&[(user(2), user(3))], &voters, false), None);
+        assert_eq!(tally(&[(user(2), user(3)), (user(4), user(3))], &voters, false), Some(user(3)));
+
+        // Ballots from non-members are ignored
+        assert_eq!(tally(&[(user(2), user(3)), (user(9), user(3))], &voters, false), None);
+
+        // After closing a plurality wins, but a tie does not
+        assert_eq!(tally(&[(user(2), user(3))], &voters, true), Some(user(3)));
+        assert_eq!(tally(&[(user(2), user(3)), (user(3), user(2))], &voters, true), None);
+    }
+
+    #[test]
+    fn test_succession_steps() {
+        let family = family();
+        let mut state = get_succession_internal(&family);
+
+        assert_eq!(next_step(&family, &state, NANOS_PER_DAY), SuccessionStep::Nothing);
+        assert_eq!(next_step(&family, &state, DORMANT), SuccessionStep::OpenVote);
+
+        state.vote = Some(open_vote(DORMANT));
+        assert_eq!(next_step(&family, &state, DORMANT + NANOS_PER_DAY), SuccessionStep::Nothing);
+
+        state.vote.as_mut().unwrap().ballots.push((user(2), user(4)));
+        let closed = DORMANT + VOTE_DURATION_DAYS * NANOS_PER_DAY;
+        assert_eq!(next_step(&family, &state, closed), SuccessionStep::CloseVote(Some(user(4))));
+
+        // A designated successor skips the vote
+        state.successor = Some(user(3));
+        assert_eq!(next_step(&family, &state, DORMANT), SuccessionStep::AppointSuccessor(user(3)));
+
+        // Owner activity resets the clock and ends the vote
+        record_owner_activity(&family, DORMANT);
+        let state = get_succession_internal(&family);
+        assert!(state.vote.is_none());
+        assert_eq!(next_step(&family, &state, DORMANT + NANOS_PER_DAY), SuccessionStep::Nothing);
+    }
+
+    #[test]
+    fn test_succession_sweep_runs_in_batches() {
+        let count = SUCCESSION_BATCH_SIZE + 5;
+        for i in 0..count {
+            let family = Family { id: format!("f{:03}", i), admin: user(1), ..Family::default() };
+            store::save_family_header(&family);
+            let mut state = get_succession_internal(&family);
+            state.pending_offer = Some(OwnershipOffer { to: user(2), offered_at: 0, expires_at: 1 });
+            save_succession(&family.id, state);
+        }
+
+        let next = check_succession_batch("", 2);
+        assert_eq!(next, Some(format!("f{:03}", SUCCESSION_BATCH_SIZE - 1)));
+        let expired = |id: String| SUCCESSION.with(|s| s.borrow().get(&id).unwrap().pending_offer.is_none());
+        assert!(expired(format!("f{:03}", SUCCESSION_BATCH_SIZE - 1)));
+        assert!(!expired(format!("f{:03}", SUCCESSION_BATCH_SIZE)));
+
+        // The last batch is short and ends the sweep
+        assert_eq!(check_succession_batch(&next.unwrap(), 2), None);
+        assert!((0..count).all(|i| expired(format!("f{:03}", i))));
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        let family = family();
+        store::save_family(&family).unwrap();
+        let mut state = get_succession_internal(&family);
+        state.successor = Some(user(2));
+        state.vote = Some(open_vote(0));
+        save_succession("f1", state);
+
+        let updated = transfer_ownership("f1", user(2), Some(FamilyRole::CoAdmin), 5).unwrap();
+        assert_eq!(updated.admin, user(2));
+        assert_eq!(super::super::roles::role_of(&updated, user(1)), Some(FamilyRole::CoAdmin));
+
+        let state = get_succession_internal(&updated);
+        assert_eq!(state.successor, None);
+        assert!(state.vote.is_none());
+        assert_eq!(state.owner_last_active, 5);
+    }
+}
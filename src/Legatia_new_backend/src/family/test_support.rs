@@ -0,0 +1,53 @@
+// Shared fixtures for the family module tests
+
+use candid::Principal;
+
+use crate::types::{Family, FamilyEvent, FamilyMember};
+
+pub fn user(id: u8) -> Principal {
+    Principal::from_slice(&[id])
+}
+
+// Member named after its id, created by user(1), optionally linked to a profile
+pub fn member(id: &str, linked: Option<Principal>) -> FamilyMember {
+    FamilyMember {
+        id: id.to_string(),
+        profile_principal: linked,
+        full_name: id.to_string(),
+        surname_at_birth: String::new(),
+        sex: String::new(),
+        birthday: None,
+        birth_city: None,
+        birth_country: None,
+        death_date: None,
+        relationship_to_admin: "other".to_string(),
+        events: Vec::new(),
+        created_at: 0,
+        created_by: user(1),
+        media_ids: None,
+    }
+}
+
+pub fn event(id: &str, member_id: &str, date: &str) -> FamilyEvent {
+    FamilyEvent {
+        id: id.to_string(),
+        member_id: member_id.to_string(),
+        title: String::new(),
+        description: String::new(),
+        event_date: date.to_string(),
+        event_type: "other".to_string(),
+        created_at: 0,
+        created_by: user(1),
+        media_ids: None,
+    }
+}
+
+// Family "f1" administered by user(1)
+pub fn family(members: Vec<FamilyMember>) -> Family {
+    Family {
+        id: "f1".to_string(),
+        admin: user(1),
+        members,
+        ..Family::default()
+    }
+}
@@ -0,0 +1,598 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api;
+use ic_cdk::api::call::RejectionCode;
+use ic_cdk_macros::*;
+
+use crate::types::{
+    FamilyProposal, FamilyTreasury, LedgerAccount, Page, PageRequest, TreasurySpendRequest, TreasuryTotals,
+    TreasuryTransaction, TreasuryTransactionKind, TreasuryTransactionStatus, DEV_MODE
+};
+use crate::storage::{TREASURY_BY_FAMILY, TREASURY_TOTALS, TREASURY_TRANSACTIONS, generate_id};
+use crate::indexes::{page_newest_first, treasury_transaction_key};
+use crate::pagination::MAX_PAGE_SIZE;
+use crate::rewards::{reward_config, LedgerError};
+use crate::error::BackendError;
+use super::get_family_roster;
+use super::roles::{require_permission, Permission};
+
+// The ledger rejects memos over 32 bytes; the first 16 carry the transaction ID
+const TX_ID_MEMO_BYTES: usize = 16;
+const MAX_MEMO_BYTES: usize = 32 - TX_ID_MEMO_BYTES;
+// A pending transfer this old lost its reply, e.g. because the callback trapped
+const STALE_PENDING_NANOS: u64 = 60 * 60 * 1_000_000_000;
+// Marks treasury subaccounts so they never collide with other derived subaccounts
+const TREASURY_SUBACCOUNT_TAG: u8 = b'F';
+
+#[derive(CandidType, Deserialize)]
+struct LedgerTransferArgs {
+    from_subaccount: Option<Vec<u8>>,
+    to: LedgerAccount,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct LedgerTransferFromArgs {
+    spender_subaccount: Option<Vec<u8>>,
+    from: LedgerAccount,
+    to: LedgerAccount,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+// Mirror of the ledger's ICRC-2 TransferFromError type
+#[derive(CandidType, Deserialize, Debug)]
+enum LedgerTransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u64 },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+// What the backend can tell about a ledger transfer after the call returns
+#[derive(Debug, PartialEq)]
+enum TransferOutcome {
+    Completed(Option<u64>), // Block index; None when the ledger reports an earlier identical transfer
+    Rejected(String),
+    Unknown(String), // The transfer may have gone through; resend it to find out
+}
+
+trait LedgerReply: std::fmt::Debug {
+    fn is_duplicate(&self) -> bool;
+    fn is_too_old(&self) -> bool;
+}
+
+impl LedgerReply for LedgerError {
+    fn is_duplicate(&self) -> bool {
+        matches!(self, LedgerError::Duplicate { .. })
+    }
+
+    fn is_too_old(&self) -> bool {
+        matches!(self, LedgerError::TooOld)
+    }
+}
+
+impl LedgerReply for LedgerTransferFromError {
+    fn is_duplicate(&self) -> bool {
+        matches!(self, LedgerTransferFromError::Duplicate { .. })
+    }
+
+    fn is_too_old(&self) -> bool {
+        matches!(self, LedgerTransferFromError::TooOld)
+    }
+}
+
+#[query]
+pub fn get_family_treasury(family_id: String) -> Result<FamilyTreasury, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    let totals = treasury_totals(&family_id);
+    Ok(FamilyTreasury {
+        account: treasury_account(&family_id)?,
+        family_id,
+        total_deposited: totals.deposited,
+        total_spent: totals.spent,
+        total_unconfirmed: totals.unconfirmed,
+    })
+}
+
+// Deposits and spends, newest first
+#[query]
+pub fn get_family_treasury_transactions(family_id: String, page: PageRequest) -> Result<Page<TreasuryTransaction>, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    transactions_of(&family_id, &page)
+}
+
+// Pull LGT the caller has approved for this canister (ICRC-2) into the treasury
+#[update]
+pub async fn deposit_to_family_treasury(
+    family_id: String,
+    amount: u64,
+    from_subaccount: Option<Vec<u8>>,
+) -> Result<TreasuryTransaction, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    if amount == 0 {
+        return Err(BackendError::validation("amount", "must_be_positive"));
+    }
+    ledger_canister()?;
+    let from = LedgerAccount { owner: caller, subaccount: from_subaccount };
+
+    let tx = new_transaction(&family_id, TreasuryTransactionKind::Deposit, from, amount, None, caller, None, api::time());
+    send_transfer(tx).await
+}
+
+// Owners and co-admins can pay out of the treasury directly
+#[update]
+pub async fn spend_from_family_treasury(request: TreasurySpendRequest) -> Result<TreasuryTransaction, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&request.family_id)?;
+    require_permission(&family, caller, Permission::ManageTreasury)?;
+    validate_spend(&request)?;
+
+    let tx = new_transaction(
+        &request.family_id,
+        TreasuryTransactionKind::Spend,
+        request.to,
+        request.amount,
+        request.memo,
+        caller,
+        None,
+        api::time(),
+    );
+    send_transfer(tx).await
+}
+
+// Resends a transfer whose outcome is unknown. It carries the same memo and
+// created_at_time, so the ledger deduplicates it if the first attempt went through.
+#[update]
+pub async fn retry_treasury_transaction(family_id: String, transaction_id: String) -> Result<TreasuryTransaction, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    let mut tx = TREASURY_TRANSACTIONS.with(|txs| txs.borrow().get(&transaction_id))
+        .filter(|tx| tx.family_id == family_id)
+        .ok_or_else(|| BackendError::not_found("treasury_transaction", &transaction_id))?;
+    // Depositors can retry their own deposits
+    if !(tx.kind == TreasuryTransactionKind::Deposit && tx.initiated_by == caller) {
+        require_permission(&family, caller, Permission::ManageTreasury)?;
+    }
+    if !can_retry(&tx, api::time()) {
+        return Err(BackendError::conflict("transaction_outcome_known"));
+    }
+
+    // Marked before awaiting so a second retry can't overlap this one
+    let old = tx.clone();
+    tx.status = TreasuryTransactionStatus::Pending;
+    tx.updated_at = api::time();
+    save_transaction(&tx, Some(&old));
+    send_transfer(tx).await
+}
+
+// Internal helper functions for other modules
+pub fn treasury_account(family_id: &str) -> Result<LedgerAccount, BackendError> {
+    Ok(LedgerAccount {
+        owner: api::id(),
+        subaccount: Some(family_subaccount(family_id)?),
+    })
+}
+
+// Tag, ID length, ID bytes, zero padding
+pub fn family_subaccount(family_id: &str) -> Result<Vec<u8>, BackendError> {
+    let id = family_id.as_bytes();
+    if id.is_empty() || id.len() > 30 {
+        return Err(BackendError::validation("family_id", "unsupported"));
+    }
+
+    let mut subaccount = vec![0u8; 32];
+    subaccount[0] = TREASURY_SUBACCOUNT_TAG;
+    subaccount[1] = id.len() as u8;
+    subaccount[2..2 + id.len()].copy_from_slice(id);
+    Ok(subaccount)
+}
+
+pub fn validate_spend(request: &TreasurySpendRequest) -> Result<(), BackendError> {
+    if request.amount == 0 {
+        return Err(BackendError::validation("amount", "must_be_positive"));
+    }
+    if request.to.owner == Principal::anonymous() {
+        return Err(BackendError::validation("to", "anonymous"));
+    }
+    if request.to.subaccount.as_ref().is_some_and(|s| s.len() != 32) {
+        return Err(BackendError::validation("to", "invalid_subaccount"));
+    }
+    if request.memo.as_ref().is_some_and(|m| m.len() > MAX_MEMO_BYTES) {
+        return Err(BackendError::validation("memo", "too_long"));
+    }
+    Ok(())
+}
+
+// What a transaction adds to its family's totals: completed deposits and spends, or
+// the amount of a transfer not yet confirmed either way. Failed transfers moved nothing.
+pub fn contribution(tx: &TreasuryTransaction) -> TreasuryTotals {
+    match (&tx.status, &tx.kind) {
+        (TreasuryTransactionStatus::Completed, TreasuryTransactionKind::Deposit) => TreasuryTotals { deposited: tx.amount, ..Default::default() },
+        (TreasuryTransactionStatus::Completed, TreasuryTransactionKind::Spend) => TreasuryTotals { spent: tx.amount, ..Default::default() },
+        (TreasuryTransactionStatus::Failed, _) => TreasuryTotals::default(),
+        _ => TreasuryTotals { unconfirmed: tx.amount, ..Default::default() },
+    }
+}
+
+pub fn treasury_totals(family_id: &str) -> TreasuryTotals {
+    TREASURY_TOTALS.with(|totals| totals.borrow().get(&family_id.to_string()).unwrap_or_default())
+}
+
+// Writes a transaction and moves its amount between the family's totals; `old` is
+// None for new transactions
+pub fn save_transaction(tx: &TreasuryTransaction, old: Option<&TreasuryTransaction>) {
+    let removed = old.map(contribution).unwrap_or_default();
+    let added = contribution(tx);
+    TREASURY_TOTALS.with(|totals| {
+        let mut totals = totals.borrow_mut();
+        let current = totals.get(&tx.family_id).unwrap_or_default();
+        let updated = TreasuryTotals {
+            deposited: current.deposited.saturating_sub(removed.deposited).saturating_add(added.deposited),
+            spent: current.spent.saturating_sub(removed.spent).saturating_add(added.spent),
+            unconfirmed: current.unconfirmed.saturating_sub(removed.unconfirmed).saturating_add(added.unconfirmed),
+        };
+        totals.insert(tx.family_id.clone(), updated);
+    });
+    if old.is_none() {
+        TREASURY_BY_FAMILY.with(|index| index.borrow_mut().insert(treasury_transaction_key(tx), ()));
+    }
+    TREASURY_TRANSACTIONS.with(|txs| txs.borrow_mut().insert(tx.id.clone(), tx.clone()));
+}
+
+// Indexes transactions and sums totals for transactions recorded before either existed
+pub fn rebuild_treasury_indexes() -> u64 {
+    let transactions: Vec<TreasuryTransaction> = TREASURY_TRANSACTIONS.with(|txs| txs.borrow().iter().map(|(_, tx)| tx).collect());
+    for tx in &transactions {
+        save_transaction(tx, None);
+    }
+    transactions.len() as u64
+}
+
+pub fn can_retry(tx: &TreasuryTransaction, now: u64) -> bool {
+    match tx.status {
+        TreasuryTransactionStatus::Unknown => true,
+        TreasuryTransactionStatus::Pending => now.saturating_sub(tx.updated_at) >= STALE_PENDING_NANOS,
+        _ => false,
+    }
+}
+
+pub fn transactions_of(family_id: &str, page: &PageRequest) -> Result<Page<TreasuryTransaction>, BackendError> {
+    let ids = TREASURY_BY_FAMILY.with(|index| page_newest_first(&index.borrow(), family_id.as_bytes(), 0, page, MAX_PAGE_SIZE))?;
+    let items = TREASURY_TRANSACTIONS.with(|txs| {
+        let txs = txs.borrow();
+        ids.items.iter().filter_map(|id| txs.get(id)).collect()
+    });
+    Ok(Page { items, next_cursor: ids.next_cursor })
+}
+
+// Called when a SpendTreasury proposal passes; the transfer finishes asynchronously
+pub fn queue_governed_spend(proposal: &FamilyProposal, request: &TreasurySpendRequest, now: u64) -> Result<String, BackendError> {
+    validate_spend(request)?;
+    ledger_canister()?;
+
+    let tx = new_transaction(
+        &proposal.family_id,
+        TreasuryTransactionKind::Spend,
+        request.to.clone(),
+        request.amount,
+        request.memo.clone(),
+        proposal.proposer,
+        Some(proposal.id.clone()),
+        now,
+    );
+    let tx_id = tx.id.clone();
+    ic_cdk::spawn(async move {
+        let _ = send_transfer(tx).await;
+    });
+    Ok(format!("Treasury payment {} submitted to the ledger", tx_id))
+}
+
+fn ledger_canister() -> Result<Principal, BackendError> {
+    reward_config().token_canister.ok_or(BackendError::conflict("ledger_not_configured"))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn new_transaction(
+    family_id: &str,
+    kind: TreasuryTransactionKind,
+    counterparty: LedgerAccount,
+    amount: u64,
+    memo: Option<String>,
+    initiated_by: Principal,
+    proposal_id: Option<String>,
+    now: u64,
+) -> TreasuryTransaction {
+    let tx = TreasuryTransaction {
+        id: generate_id(),
+        family_id: family_id.to_string(),
+        kind,
+        counterparty,
+        amount,
+        memo,
+        initiated_by,
+        proposal_id,
+        status: TreasuryTransactionStatus::Pending,
+        block_index: None,
+        error: None,
+        created_at: now,
+        updated_at: now,
+    };
+    save_transaction(&tx, None);
+    tx
+}
+
+// Unknown outcomes are kept, not failed, so the caller can see them and retry
+fn finish_transaction(id: &str, outcome: TransferOutcome, now: u64) -> Result<TreasuryTransaction, BackendError> {
+    let old = TREASURY_TRANSACTIONS.with(|txs| txs.borrow().get(&id.to_string()))
+        .ok_or_else(|| BackendError::not_found("treasury_transaction", id))?;
+    let mut tx = old.clone();
+    let (status, error) = match &outcome {
+        TransferOutcome::Completed(block_index) => {
+            tx.block_index = *block_index;
+            (TreasuryTransactionStatus::Completed, None)
+        }
+        TransferOutcome::Rejected(error) => (TreasuryTransactionStatus::Failed, Some(error.clone())),
+        TransferOutcome::Unknown(error) => (TreasuryTransactionStatus::Unknown, Some(error.clone())),
+    };
+    tx.status = status;
+    tx.error = error;
+    tx.updated_at = now;
+    save_transaction(&tx, Some(&old));
+
+    match outcome {
+        TransferOutcome::Rejected(error) => Err(BackendError::external(error)),
+        _ => Ok(tx),
+    }
+}
+
+// Transaction ID, zero-padded, then the caller's memo. Together with created_at_time
+// this makes each transfer unique to the ledger, and identical when it is resent.
+fn ledger_memo(tx: &TreasuryTransaction) -> Vec<u8> {
+    let mut memo = vec![0u8; TX_ID_MEMO_BYTES];
+    let id = tx.id.as_bytes();
+    let len = id.len().min(TX_ID_MEMO_BYTES);
+    memo[..len].copy_from_slice(&id[..len]);
+    if let Some(text) = &tx.memo {
+        memo.extend_from_slice(text.as_bytes());
+    }
+    memo
+}
+
+fn transfer_outcome<E: LedgerReply>(result: Result<(Result<u64, E>,), (RejectionCode, String)>) -> TransferOutcome {
+    match result {
+        Ok((Ok(block_index),)) => TransferOutcome::Completed(Some(block_index)),
+        // The first attempt went through
+        Ok((Err(error),)) if error.is_duplicate() => TransferOutcome::Completed(None),
+        Ok((Err(error),)) if error.is_too_old() => {
+            TransferOutcome::Unknown("Too old to resend; check the ledger for this transfer".to_string())
+        }
+        Ok((Err(error),)) => TransferOutcome::Rejected(format!("Ledger rejected transfer: {:?}", error)),
+        // The ledger never ran the call or rolled it back
+        Err((code @ (RejectionCode::DestinationInvalid | RejectionCode::CanisterReject | RejectionCode::CanisterError), message)) => {
+            TransferOutcome::Rejected(format!("Call to ledger failed ({:?}): {}", code, message))
+        }
+        Err((code, message)) => TransferOutcome::Unknown(format!("Call to ledger may not have finished ({:?}): {}", code, message)),
+    }
+}
+
+async fn send_transfer(tx: TreasuryTransaction) -> Result<TreasuryTransaction, BackendError> {
+    let outcome = match (ledger_canister(), treasury_account(&tx.family_id)) {
+        (Ok(ledger), Ok(treasury)) => match tx.kind {
+            TreasuryTransactionKind::Deposit => {
+                let args = LedgerTransferFromArgs {
+                    spender_subaccount: None,
+                    from: tx.counterparty.clone(),
+                    to: treasury,
+                    amount: Nat::from(tx.amount),
+                    fee: None,
+                    memo: Some(ledger_memo(&tx)),
+                    created_at_time: Some(tx.created_at),
+                };
+                transfer_outcome(ic_cdk::call::<_, (Result<u64, LedgerTransferFromError>,)>(ledger, "icrc2_transfer_from", (args,)).await)
+            }
+            TreasuryTransactionKind::Spend => {
+                let args = LedgerTransferArgs {
+                    from_subaccount: treasury.subaccount,
+                    to: tx.counterparty.clone(),
+                    amount: Nat::from(tx.amount),
+                    fee: None,
+                    memo: Some(ledger_memo(&tx)),
+                    created_at_time: Some(tx.created_at),
+                };
+                transfer_outcome(ic_cdk::call::<_, (Result<u64, LedgerError>,)>(ledger, "icrc1_transfer", (args,)).await)
+            }
+        },
+        (Err(error), _) | (_, Err(error)) => TransferOutcome::Rejected(error.to_string()),
+    };
+
+    finish_transaction(&tx.id, outcome, api::time())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::family::test_support::user;
+
+    fn transaction(id: &str, kind: TreasuryTransactionKind, amount: u64, status: TreasuryTransactionStatus) -> TreasuryTransaction {
+        TreasuryTransaction {
+            id: id.to_string(),
+            family_id: "f1".to_string(),
+            kind,
+            counterparty: LedgerAccount { owner: user(1), subaccount: None },
+            amount,
+            memo: None,
+            initiated_by: user(1),
+            proposal_id: None,
+            status,
+            block_index: None,
+            error: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_family_subaccounts() {
+        let a = family_subaccount("1a2b").unwrap();
+        let b = family_subaccount("1a2b0").unwrap();
+
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, b);
+        assert_eq!(a[0], TREASURY_SUBACCOUNT_TAG);
+        assert!(family_subaccount("").is_err());
+        assert!(family_subaccount(&"f".repeat(31)).is_err());
+    }
+
+    #[test]
+    fn test_totals_count_completed_transfers() {
+        use TreasuryTransactionKind::*;
+        use TreasuryTransactionStatus::*;
+
+        for tx in [
+            transaction("d1", Deposit, 1_000, Completed),
+            transaction("d2", Deposit, 500, Failed),
+            transaction("s1", Spend, 300, Completed),
+            transaction("s2", Spend, 200, Pending),
+            transaction("s3", Spend, 50, Unknown),
+        ] {
+            save_transaction(&tx, None);
+        }
+        assert_eq!(treasury_totals("f1"), TreasuryTotals { deposited: 1_000, spent: 300, unconfirmed: 250 });
+
+        // Settling a transfer moves its amount out of the unconfirmed total
+        finish_transaction("s3", TransferOutcome::Completed(Some(1)), 1).unwrap();
+        assert!(finish_transaction("s2", TransferOutcome::Rejected("rejected".to_string()), 1).is_err());
+        assert_eq!(treasury_totals("f1"), TreasuryTotals { deposited: 1_000, spent: 350, unconfirmed: 0 });
+    }
+
+    #[test]
+    fn test_transactions_are_paged_by_family() {
+        for (id, created_at) in [("t1", 1), ("t2", 2), ("t3", 3)] {
+            let tx = transaction(id, TreasuryTransactionKind::Deposit, 10, TreasuryTransactionStatus::Completed);
+            save_transaction(&TreasuryTransaction { created_at, ..tx }, None);
+        }
+        let other = transaction("t4", TreasuryTransactionKind::Deposit, 10, TreasuryTransactionStatus::Completed);
+        save_transaction(&TreasuryTransaction { family_id: "f2".to_string(), ..other }, None);
+
+        let first = transactions_of("f1", &PageRequest { cursor: None, limit: Some(2) }).unwrap();
+        assert_eq!(first.items.iter().map(|tx| tx.id.as_str()).collect::<Vec<_>>(), vec!["t3", "t2"]);
+        let rest = transactions_of("f1", &PageRequest { cursor: first.next_cursor, limit: Some(2) }).unwrap();
+        assert_eq!(rest.items.iter().map(|tx| tx.id.as_str()).collect::<Vec<_>>(), vec!["t1"]);
+        assert_eq!(rest.next_cursor, None);
+    }
+
+    #[test]
+    fn test_finish_transaction() {
+        let tx = transaction("t1", TreasuryTransactionKind::Spend, 100, TreasuryTransactionStatus::Pending);
+        save_transaction(&tx, None);
+
+        let unknown = finish_transaction("t1", TransferOutcome::Unknown("timed out".to_string()), 5).unwrap();
+        assert_eq!(unknown.status, TreasuryTransactionStatus::Unknown);
+        assert!(can_retry(&unknown, 5));
+
+        let done = finish_transaction("t1", TransferOutcome::Completed(Some(7)), 6).unwrap();
+        assert_eq!(done.status, TreasuryTransactionStatus::Completed);
+        assert_eq!((done.block_index, done.error.as_deref()), (Some(7), None));
+        assert!(!can_retry(&done, 6));
+
+        assert!(finish_transaction("t1", TransferOutcome::Rejected("rejected".to_string()), 7).is_err());
+        let failed = TREASURY_TRANSACTIONS.with(|txs| txs.borrow().get(&"t1".to_string())).unwrap();
+        assert_eq!(failed.status, TreasuryTransactionStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("rejected"));
+    }
+
+    #[test]
+    fn test_transfer_outcomes() {
+        let reply = |result| transfer_outcome::<LedgerError>(result);
+
+        assert_eq!(reply(Ok((Ok(3),))), TransferOutcome::Completed(Some(3)));
+        assert_eq!(reply(Ok((Err(LedgerError::Duplicate { duplicate_of: 3 }),))), TransferOutcome::Completed(None));
+        assert!(matches!(reply(Ok((Err(LedgerError::TooOld),))), TransferOutcome::Unknown(_)));
+        assert!(matches!(reply(Ok((Err(LedgerError::InsufficientFunds { balance: Nat::from(0u8) }),))), TransferOutcome::Rejected(_)));
+        assert!(matches!(reply(Err((RejectionCode::CanisterError, String::new()))), TransferOutcome::Rejected(_)));
+        assert!(matches!(reply(Err((RejectionCode::Unknown, String::new()))), TransferOutcome::Unknown(_)));
+        assert!(matches!(reply(Err((RejectionCode::SysTransient, String::new()))), TransferOutcome::Unknown(_)));
+    }
+
+    #[test]
+    fn test_stale_pending_transfers_can_be_retried() {
+        let tx = transaction("t1", TreasuryTransactionKind::Deposit, 100, TreasuryTransactionStatus::Pending);
+        assert!(!can_retry(&tx, STALE_PENDING_NANOS - 1));
+        assert!(can_retry(&tx, STALE_PENDING_NANOS));
+    }
+
+    #[test]
+    fn test_ledger_memo_starts_with_the_transaction_id() {
+        let mut tx = transaction("a1b2", TreasuryTransactionKind::Spend, 100, TreasuryTransactionStatus::Pending);
+        tx.memo = Some("Archive".to_string());
+        let memo = ledger_memo(&tx);
+
+        assert_eq!(&memo[..4], b"a1b2");
+        assert_eq!(&memo[4..TX_ID_MEMO_BYTES], &[0; 12]);
+        assert_eq!(&memo[TX_ID_MEMO_BYTES..], b"Archive");
+        tx.memo = Some("x".repeat(MAX_MEMO_BYTES));
+        assert_eq!(ledger_memo(&tx).len(), 32);
+    }
+
+    #[test]
+    fn test_validate_spend() {
+        let request = |amount, memo: Option<&str>| TreasurySpendRequest {
+            family_id: "f1".to_string(),
+            to: LedgerAccount { owner: user(2), subaccount: None },
+            amount,
+            memo: memo.map(str::to_string),
+        };
+
+        assert!(validate_spend(&request(100, Some("Archive storage"))).is_ok());
+        assert!(validate_spend(&request(0, None)).is_err());
+        assert!(validate_spend(&request(100, Some(&"x".repeat(MAX_MEMO_BYTES + 1)))).is_err());
+
+        let mut anonymous = request(100, None);
+        anonymous.to.owner = Principal::anonymous();
+        assert!(validate_spend(&anonymous).is_err());
+    }
+}
@@ -0,0 +1,1045 @@
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use std::collections::HashMap;
+
+use crate::types::{
+    Family, FamilyMember, FamilyEvent, FamilyRelationship, AddRelationshipRequest, RelationshipType,
+    RelationshipQualifier, CreateFamilyRequest, ImportGedcomRequest, GedcomImportReport,
+    GedcomSkippedRecord, FamilyRole, DEV_MODE
+};
+use crate::storage::{RELATIONSHIPS, generate_id};
+use crate::error::BackendError;
+use crate::family::{has_family_access, store};
+use crate::family::relationships::{validate_relationship, get_relationships_internal, iso_date};
+
+// Stays under the IC ingress message limit
+pub const MAX_GEDCOM_SIZE: usize = 2_000_000;
+
+// Tags that carry linkage or bookkeeping data we either derive elsewhere or don't need
+const IGNORED_TAGS: &[&str] = &["FAMC", "FAMS", "CHAN", "CREA", "RIN", "UID", "_UID", "RFN", "AFN", "REFN", "EXID"];
+
+#[update]
+pub fn import_gedcom(request: ImportGedcomRequest) -> Result<GedcomImportReport, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    if request.gedcom.len() > MAX_GEDCOM_SIZE {
+        return Err(BackendError::validation("gedcom", "too_large"));
+    }
+
+    // Parse everything up front so a malformed file never leaves a half-created family behind
+    let import = build_import(&request.gedcom, caller, api::time(), &mut generate_id)?;
+    if import.members.is_empty() {
+        return Err(BackendError::validation("gedcom", "no_individuals"));
+    }
+
+    let mut family = crate::family::create_family_internal(caller, CreateFamilyRequest {
+        name: request.name,
+        description: request.description,
+        is_visible: request.is_visible,
+    })?;
+
+    let events_imported = import.members.iter().map(|m| m.events.len() as u32).sum();
+    let members_imported = import.members.len() as u32;
+    let relationships_imported = import.relationships.len() as u32;
+
+    // The family is new, so each record is written once with nothing to reconcile
+    for member in &import.members {
+        store::save_member(&family.id, member)?;
+        for event in &member.events {
+            store::save_event(&family.id, event)?;
+        }
+    }
+    family.members = import.members;
+
+    let relationships = import.relationships.into_iter()
+        .map(|mut r| {
+            r.family_id = family.id.clone();
+            r
+        })
+        .collect();
+    RELATIONSHIPS.with(|r| {
+        r.borrow_mut().insert(family.id.clone(), crate::types::FamilyRelationshipList(relationships));
+    });
+
+    Ok(GedcomImportReport {
+        family,
+        gedcom_version: import.version,
+        members_imported,
+        events_imported,
+        relationships_imported,
+        skipped_records: import.skipped_records,
+    })
+}
+
+#[query]
+pub fn export_gedcom(family_id: String) -> Result<String, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = crate::family::get_family_internal(&family_id)?;
+    if !has_family_access(&family, caller) {
+        return Err(BackendError::requires(FamilyRole::Viewer));
+    }
+
+    Ok(export_family(&family, &get_relationships_internal(&family_id)))
+}
+
+// Parsed GEDCOM line with its substructures
+#[derive(Debug, Default)]
+struct GedcomNode {
+    line: u64,
+    xref: Option<String>,
+    tag: String,
+    value: String,
+    children: Vec<GedcomNode>,
+}
+
+impl GedcomNode {
+    fn child(&self, tag: &str) -> Option<&GedcomNode> {
+        self.children.iter().find(|c| c.tag == tag)
+    }
+
+    fn child_value(&self, tag: &str) -> Option<&str> {
+        self.child(tag).map(|c| c.value.as_str()).filter(|v| !v.is_empty())
+    }
+}
+
+struct GedcomImport {
+    version: Option<String>,
+    members: Vec<FamilyMember>,
+    relationships: Vec<FamilyRelationship>,
+    skipped_records: Vec<GedcomSkippedRecord>,
+}
+
+fn parse_gedcom(input: &str) -> Result<Vec<GedcomNode>, BackendError> {
+    let malformed = |line: u64, reason: &str| BackendError::malformed("gedcom", line, reason);
+
+    let mut records: Vec<GedcomNode> = Vec::new();
+    let mut stack: Vec<GedcomNode> = Vec::new();
+
+    for (index, raw_line) in input.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line_number = index as u64 + 1;
+        let line = raw_line.trim_start().trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+
+        let (level, rest) = line.split_once(' ')
+            .ok_or_else(|| malformed(line_number, "invalid_line"))?;
+        let level: usize = level.parse()
+            .map_err(|_| malformed(line_number, "invalid_level"))?;
+
+        let (xref, rest) = if rest.starts_with('@') {
+            let (xref, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+            (Some(xref.trim_matches('@').to_string()), rest)
+        } else {
+            (None, rest)
+        };
+        let (tag, value) = rest.split_once(' ').unwrap_or((rest, ""));
+
+        // Close every structure at or below this level
+        while stack.len() > level {
+            let node = stack.pop().unwrap_or_default();
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => records.push(node),
+            }
+        }
+        if stack.len() != level {
+            return Err(malformed(line_number, "skipped_level"));
+        }
+
+        // Continuation lines extend the value of their parent
+        if tag == "CONT" || tag == "CONC" {
+            let parent = stack.last_mut()
+                .ok_or_else(|| malformed(line_number, "continuation_without_parent"))?;
+            if tag == "CONT" {
+                parent.value.push('\n');
+            }
+            parent.value.push_str(value);
+            continue;
+        }
+
+        stack.push(GedcomNode {
+            line: line_number,
+            xref,
+            tag: tag.to_uppercase(),
+            value: value.replace("@@", "@"),
+            children: Vec::new(),
+        });
+    }
+
+    while let Some(node) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => records.push(node),
+        }
+    }
+
+    if records.first().map(|r| r.tag.as_str()) != Some("HEAD") {
+        return Err(malformed(records.first().map_or(0, |r| r.line), "missing_head"));
+    }
+
+    Ok(records)
+}
+
+fn build_import(
+    input: &str,
+    caller: Principal,
+    now: u64,
+    next_id: &mut dyn FnMut() -> String,
+) -> Result<GedcomImport, BackendError> {
+    let records = parse_gedcom(input)?;
+
+    let mut version = None;
+    let mut members: Vec<FamilyMember> = Vec::new();
+    let mut relationships: Vec<FamilyRelationship> = Vec::new();
+    let mut skipped_records = Vec::new();
+    let mut member_ids: HashMap<String, usize> = HashMap::new(); // GEDCOM xref -> index into members
+    let mut pedigrees: HashMap<(String, String), String> = HashMap::new(); // (child xref, family xref) -> PEDI
+
+    let mut skip = |node: &GedcomNode, xref: &Option<String>, reason: &str| {
+        skipped_records.push(GedcomSkippedRecord {
+            line: node.line,
+            xref: xref.clone(),
+            tag: node.tag.clone(),
+            reason: reason.to_string(),
+        });
+    };
+
+    // Individuals first, so family records can resolve their pointers
+    for record in &records {
+        match record.tag.as_str() {
+            "HEAD" => {
+                version = record.child("GEDC")
+                    .and_then(|gedc| gedc.child_value("VERS"))
+                    .map(|v| v.to_string());
+            }
+            "INDI" => {
+                let member_id = next_id();
+                let mut member = FamilyMember {
+                    id: member_id.clone(),
+                    profile_principal: None,
+                    full_name: String::new(),
+                    surname_at_birth: String::new(),
+                    sex: String::new(),
+                    birthday: None,
+                    birth_city: None,
+                    birth_country: None,
+                    death_date: None,
+                    relationship_to_admin: "Other".to_string(),
+                    events: Vec::new(),
+                    created_at: now,
+                    created_by: caller,
+                    media_ids: None,
+                };
+
+                for child in &record.children {
+                    match child.tag.as_str() {
+                        "NAME" if member.full_name.is_empty() => {
+                            let (full_name, surname) = parse_name(child);
+                            member.full_name = full_name;
+                            member.surname_at_birth = surname;
+                        }
+                        "NAME" => skip(child, &record.xref, "Alternate names are not supported"),
+                        "SEX" => {
+                            member.sex = match child.value.trim() {
+                                "M" => "Male",
+                                "F" => "Female",
+                                "X" => "Other",
+                                _ => "",
+                            }.to_string();
+                        }
+                        "FAMC" => {
+                            if let (Some(xref), Some(pedigree)) = (&record.xref, child.child_value("PEDI")) {
+                                pedigrees.insert(
+                                    (xref.clone(), child.value.trim_matches('@').to_string()),
+                                    pedigree.to_lowercase(),
+                                );
+                            }
+                        }
+                        tag if IGNORED_TAGS.contains(&tag) => {}
+                        tag => match gedcom_event_type(tag) {
+                            Some(_) => {
+                                let event = build_event(child, &member_id, caller, now, next_id());
+                                if tag == "BIRT" {
+                                    if !event.event_date.is_empty() {
+                                        member.birthday = Some(event.event_date.clone());
+                                    }
+                                    if let Some(place) = child.child_value("PLAC") {
+                                        let (city, country) = split_place(place);
+                                        member.birth_city = city;
+                                        member.birth_country = country;
+                                    }
+                                }
+                                if tag == "DEAT" && !event.event_date.is_empty() {
+                                    member.death_date = Some(event.event_date.clone());
+                                }
+                                member.events.push(event);
+                            }
+                            None => skip(child, &record.xref, "Individual attribute is not supported"),
+                        },
+                    }
+                }
+
+                if member.full_name.is_empty() {
+                    member.full_name = "Unknown".to_string();
+                }
+                member.events.sort_by(|a, b| a.event_date.cmp(&b.event_date));
+
+                if let Some(xref) = &record.xref {
+                    member_ids.insert(xref.clone(), members.len());
+                }
+                members.push(member);
+            }
+            "FAM" | "TRLR" => {}
+            _ => skip(record, &record.xref, "Record type is not supported"),
+        }
+    }
+
+    for record in records.iter().filter(|r| r.tag == "FAM") {
+        let resolve = |node: &GedcomNode| -> Option<usize> {
+            member_ids.get(node.value.trim_matches('@')).copied()
+        };
+
+        let mut partners: Vec<usize> = Vec::new();
+        let mut children: Vec<(usize, String)> = Vec::new(); // (member index, child xref)
+        let mut events: Vec<&GedcomNode> = Vec::new();
+
+        for child in &record.children {
+            match child.tag.as_str() {
+                "HUSB" | "WIFE" | "CHIL" if child.value == "@VOID@" => {}
+                "HUSB" | "WIFE" => match resolve(child) {
+                    Some(index) => partners.push(index),
+                    None => skip(child, &record.xref, "Pointer to an unknown individual"),
+                },
+                "CHIL" => match resolve(child) {
+                    Some(index) => children.push((index, child.value.trim_matches('@').to_string())),
+                    None => skip(child, &record.xref, "Pointer to an unknown individual"),
+                },
+                tag if IGNORED_TAGS.contains(&tag) => {}
+                tag if gedcom_event_type(tag).is_some() => events.push(child),
+                _ => skip(child, &record.xref, "Family attribute is not supported"),
+            }
+        }
+
+        // Family events belong to both partners
+        for event_node in &events {
+            for &index in &partners {
+                let event = build_event(event_node, &members[index].id, caller, now, next_id());
+                members[index].events.push(event);
+                members[index].events.sort_by(|a, b| a.event_date.cmp(&b.event_date));
+            }
+        }
+
+        let mut candidates: Vec<AddRelationshipRequest> = Vec::new();
+        if let [first, second] = partners[..] {
+            // Approximate dates ("ABT 1925") stay on the events but can't be ordered, so the relationship is kept without them
+            let date_of = |tag: &str| {
+                events.iter()
+                    .find(|e| e.tag == tag)
+                    .and_then(|e| event_date(e))
+                    .filter(|date| iso_date(date).is_some())
+            };
+            candidates.push(AddRelationshipRequest {
+                family_id: String::new(),
+                from_member_id: members[first].id.clone(),
+                to_member_id: members[second].id.clone(),
+                relationship_type: RelationshipType::SpouseOf,
+                qualifier: None,
+                start_date: date_of("MARR"),
+                end_date: date_of("DIV"),
+            });
+        }
+        for &partner in &partners {
+            for (child, child_xref) in &children {
+                let pedigree = record.xref.as_ref()
+                    .and_then(|fam| pedigrees.get(&(child_xref.clone(), fam.clone())));
+                let qualifier = match pedigree.map(|p| p.as_str()) {
+                    Some("adopted") | Some("foster") => RelationshipQualifier::Adoptive,
+                    _ => RelationshipQualifier::Biological,
+                };
+                candidates.push(AddRelationshipRequest {
+                    family_id: String::new(),
+                    from_member_id: members[partner].id.clone(),
+                    to_member_id: members[*child].id.clone(),
+                    relationship_type: RelationshipType::ParentOf,
+                    qualifier: Some(qualifier),
+                    start_date: None,
+                    end_date: None,
+                });
+            }
+        }
+
+        for candidate in candidates {
+            if let Err(reason) = validate_relationship(&members, &relationships, &candidate) {
+                skip(record, &record.xref, &reason.to_string());
+                continue;
+            }
+            relationships.push(FamilyRelationship {
+                id: next_id(),
+                family_id: String::new(),
+                from_member_id: candidate.from_member_id,
+                to_member_id: candidate.to_member_id,
+                relationship_type: candidate.relationship_type,
+                qualifier: candidate.qualifier.unwrap_or(RelationshipQualifier::Biological),
+                start_date: candidate.start_date,
+                end_date: candidate.end_date,
+                created_at: now,
+                created_by: caller,
+            });
+        }
+    }
+
+    Ok(GedcomImport {
+        version,
+        members,
+        relationships,
+        skipped_records,
+    })
+}
+
+// Maps a GEDCOM event tag to our event type and a readable title
+fn gedcom_event_type(tag: &str) -> Option<(&'static str, &'static str)> {
+    let mapped = match tag {
+        "BIRT" => ("Birth", "Birth"),
+        "DEAT" => ("Death", "Death"),
+        "MARR" => ("Marriage", "Marriage"),
+        "DIV" => ("Divorce", "Divorce"),
+        "EDUC" => ("Education", "Education"),
+        "GRAD" => ("Education", "Graduation"),
+        "OCCU" => ("Career", "Occupation"),
+        "RETI" => ("Career", "Retirement"),
+        "IMMI" => ("Immigration", "Immigration"),
+        "EMIG" => ("Immigration", "Emigration"),
+        "NATU" => ("Immigration", "Naturalization"),
+        "_MILT" | "_MILI" => ("Military", "Military service"),
+        "ENGA" => ("Other", "Engagement"),
+        "CHR" => ("Other", "Christening"),
+        "BAPM" => ("Other", "Baptism"),
+        "CONF" => ("Other", "Confirmation"),
+        "BARM" => ("Other", "Bar mitzvah"),
+        "BASM" => ("Other", "Bat mitzvah"),
+        "ADOP" => ("Other", "Adoption"),
+        "RESI" => ("Other", "Residence"),
+        "CENS" => ("Other", "Census"),
+        "BURI" => ("Other", "Burial"),
+        "CREM" => ("Other", "Cremation"),
+        "PROB" => ("Other", "Probate"),
+        "WILL" => ("Other", "Will"),
+        "EVEN" => ("Other", "Event"),
+        _ => return None,
+    };
+    Some(mapped)
+}
+
+fn build_event(node: &GedcomNode, member_id: &str, caller: Principal, now: u64, event_id: String) -> FamilyEvent {
+    let (event_type, label) = gedcom_event_type(&node.tag).unwrap_or(("Other", "Event"));
+
+    // EVEN carries its own type; attributes like OCCU carry a value ("Farmer")
+    let title = match (node.child_value("TYPE"), node.value.trim()) {
+        (Some(kind), _) => kind.to_string(),
+        (None, value) if !value.is_empty() && value != "Y" => format!("{}: {}", label, value),
+        _ => label.to_string(),
+    };
+
+    let mut description = Vec::new();
+    if let Some(place) = node.child_value("PLAC") {
+        description.push(format!("Place: {}", place));
+    }
+    if let Some(note) = node.child_value("NOTE") {
+        description.push(note.to_string());
+    }
+
+    FamilyEvent {
+        id: event_id,
+        member_id: member_id.to_string(),
+        title,
+        description: description.join("\n"),
+        event_date: event_date(node).unwrap_or_default(),
+        event_type: event_type.to_string(),
+        created_at: now,
+        created_by: caller,
+        media_ids: None,
+    }
+}
+
+// GEDCOM 7 free-text dates are an empty DATE with a PHRASE
+fn event_date(node: &GedcomNode) -> Option<String> {
+    let date = node.child("DATE")?;
+    match date.value.trim() {
+        "" => date.child_value("PHRASE").map(|p| p.to_string()),
+        value => Some(convert_date(value)),
+    }
+}
+
+// "John Michael /Smith/ Jr." -> ("John Michael Smith Jr.", "Smith")
+fn parse_name(node: &GedcomNode) -> (String, String) {
+    let full_name = node.value.replace('/', " ").split_whitespace().collect::<Vec<_>>().join(" ");
+    let surname = node.child_value("SURN")
+        .map(|s| s.to_string())
+        .or_else(|| node.value.split('/').nth(1).map(|s| s.trim().to_string()))
+        .unwrap_or_default();
+
+    let full_name = if full_name.is_empty() {
+        let given = node.child_value("GIVN").unwrap_or_default();
+        format!("{} {}", given, surname).trim().to_string()
+    } else {
+        full_name
+    };
+
+    (full_name, surname)
+}
+
+// "Springfield, Sangamon, Illinois, USA" -> (Some("Springfield"), Some("USA"))
+fn split_place(place: &str) -> (Option<String>, Option<String>) {
+    let parts: Vec<&str> = place.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+    match parts.as_slice() {
+        [] => (None, None),
+        [only] => (Some(only.to_string()), None),
+        [first, .., last] => (Some(first.to_string()), Some(last.to_string())),
+    }
+}
+
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+
+// Exact Gregorian dates become ISO strings; approximate, ranged or impossible dates ("ABT 1850", "31 FEB 1900") are kept verbatim
+fn convert_date(value: &str) -> String {
+    let value = value.trim();
+    let value = value.strip_prefix("@#DGREGORIAN@")
+        .or_else(|| value.strip_prefix("GREGORIAN"))
+        .unwrap_or(value)
+        .trim();
+
+    let month = |m: &str| MONTHS.iter().position(|name| name.eq_ignore_ascii_case(m)).map(|i| i as u32 + 1);
+    let parts: Vec<&str> = value.split_whitespace().collect();
+
+    let iso = match parts.as_slice() {
+        [day, m, year] => match (day.parse::<u32>(), month(m), year.parse::<u32>()) {
+            (Ok(day), Some(m), Ok(year)) => Some(format!("{:04}-{:02}-{:02}", year, m, day)),
+            _ => None,
+        },
+        [m, year] => match (month(m), year.parse::<u32>()) {
+            (Some(m), Ok(year)) => Some(format!("{:04}-{:02}", year, m)),
+            _ => None,
+        },
+        [year] => year.parse::<u32>().ok().map(|year| format!("{:04}", year)),
+        _ => None,
+    };
+
+    iso.filter(|iso| iso_date(iso).is_some()).unwrap_or_else(|| value.to_string())
+}
+
+// Line-oriented GEDCOM 7 writer handling continuation lines and '@' escaping
+struct GedcomWriter {
+    output: String,
+}
+
+impl GedcomWriter {
+    fn line(&mut self, level: u32, tag: &str, value: &str) {
+        let mut lines = value.split('\n');
+        let first = lines.next().unwrap_or_default();
+        self.write(level, tag, first);
+        for continuation in lines {
+            self.write(level + 1, "CONT", continuation);
+        }
+    }
+
+    fn write(&mut self, level: u32, tag: &str, value: &str) {
+        let escaped = match value.strip_prefix('@') {
+            Some(rest) => format!("@@{}", rest),
+            None => value.to_string(),
+        };
+        if escaped.is_empty() {
+            self.output.push_str(&format!("{} {}\n", level, tag));
+        } else {
+            self.output.push_str(&format!("{} {} {}\n", level, tag, escaped));
+        }
+    }
+
+    fn record(&mut self, xref: &str, tag: &str) {
+        self.output.push_str(&format!("0 @{}@ {}\n", xref, tag));
+    }
+
+    fn pointer(&mut self, level: u32, tag: &str, xref: &str) {
+        self.output.push_str(&format!("{} {} @{}@\n", level, tag, xref));
+    }
+
+    fn date(&mut self, level: u32, date: &str) {
+        match gedcom_date(date) {
+            Some(value) => self.line(level, "DATE", &value),
+            None => {
+                self.line(level, "DATE", "");
+                self.line(level + 1, "PHRASE", date);
+            }
+        }
+    }
+}
+
+// Partner slots and children of one exported FAM record
+#[derive(Default)]
+struct ExportFamily {
+    partners: Vec<usize>,
+    children: Vec<(usize, RelationshipQualifier)>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+}
+
+fn export_family(family: &Family, relationships: &[FamilyRelationship]) -> String {
+    let index_of: HashMap<&str, usize> = family.members.iter()
+        .enumerate()
+        .map(|(i, m)| (m.id.as_str(), i))
+        .collect();
+    let is_spouse_pair = |a: usize, b: usize| {
+        relationships.iter().any(|r| {
+            r.relationship_type == RelationshipType::SpouseOf
+                && ((index_of.get(r.from_member_id.as_str()) == Some(&a) && index_of.get(r.to_member_id.as_str()) == Some(&b))
+                    || (index_of.get(r.from_member_id.as_str()) == Some(&b) && index_of.get(r.to_member_id.as_str()) == Some(&a)))
+        })
+    };
+
+    // FAM records keyed by their sorted partner indices
+    let mut families: Vec<ExportFamily> = Vec::new();
+    let family_index = |families: &mut Vec<ExportFamily>, mut partners: Vec<usize>| -> usize {
+        partners.sort_unstable();
+        match families.iter().position(|f| f.partners == partners) {
+            Some(i) => i,
+            None => {
+                families.push(ExportFamily { partners, ..ExportFamily::default() });
+                families.len() - 1
+            }
+        }
+    };
+
+    for r in relationships.iter().filter(|r| r.relationship_type == RelationshipType::SpouseOf) {
+        if let (Some(&a), Some(&b)) = (index_of.get(r.from_member_id.as_str()), index_of.get(r.to_member_id.as_str())) {
+            let i = family_index(&mut families, vec![a, b]);
+            families[i].start_date = r.start_date.clone();
+            families[i].end_date = r.end_date.clone();
+        }
+    }
+
+    for (child, _) in family.members.iter().enumerate() {
+        let mut parents: Vec<(usize, RelationshipQualifier)> = relationships.iter()
+            .filter(|r| r.relationship_type == RelationshipType::ParentOf && index_of.get(r.to_member_id.as_str()) == Some(&child))
+            .filter_map(|r| index_of.get(r.from_member_id.as_str()).map(|&p| (p, r.qualifier.clone())))
+            .collect();
+
+        // Parents who are partners share one FAM; anyone else gets a single-parent FAM
+        while let Some((parent, qualifier)) = parents.pop() {
+            let partner = parents.iter().position(|(other, _)| is_spouse_pair(parent, *other));
+            let group = match partner {
+                Some(p) => vec![parent, parents.remove(p).0],
+                None => vec![parent],
+            };
+            let i = family_index(&mut families, group);
+            families[i].children.push((child, qualifier));
+        }
+    }
+
+    let mut writer = GedcomWriter { output: String::new() };
+    writer.line(0, "HEAD", "");
+    writer.line(1, "GEDC", "");
+    writer.line(2, "VERS", "7.0");
+    writer.line(1, "SOUR", "LEGATIA");
+    writer.line(2, "NAME", "Legatia");
+    if !family.name.is_empty() {
+        writer.line(1, "NOTE", &family.name);
+    }
+
+    for (index, member) in family.members.iter().enumerate() {
+        writer.record(&format!("I{}", index + 1), "INDI");
+
+        // Marriages and divorces already written on a FAM record are not repeated on the individual
+        let family_events: Vec<(&str, &str)> = families.iter()
+            .filter(|fam| fam.partners.contains(&index))
+            .flat_map(|fam| {
+                let marriage = fam.start_date.as_deref().map(|d| ("marriage", d));
+                let divorce = fam.end_date.as_deref().map(|d| ("divorce", d));
+                marriage.into_iter().chain(divorce)
+            })
+            .collect();
+        export_member(&mut writer, member, &family_events);
+
+        for (f, fam) in families.iter().enumerate() {
+            if fam.partners.contains(&index) {
+                writer.pointer(1, "FAMS", &format!("F{}", f + 1));
+            }
+        }
+        for (f, fam) in families.iter().enumerate() {
+            if let Some((_, qualifier)) = fam.children.iter().find(|(c, _)| *c == index) {
+                writer.pointer(1, "FAMC", &format!("F{}", f + 1));
+                match qualifier {
+                    RelationshipQualifier::Biological => writer.line(2, "PEDI", "BIRTH"),
+                    RelationshipQualifier::Adoptive => writer.line(2, "PEDI", "ADOPTED"),
+                    RelationshipQualifier::Step => {
+                        writer.line(2, "PEDI", "OTHER");
+                        writer.line(3, "PHRASE", "Step");
+                    }
+                }
+            }
+        }
+    }
+
+    for (f, fam) in families.iter().enumerate() {
+        writer.record(&format!("F{}", f + 1), "FAM");
+        for (slot, &partner) in fam.partners.iter().enumerate() {
+            let tag = match (slot, family.members[partner].sex.to_lowercase().as_str()) {
+                (_, "female" | "f") => "WIFE",
+                (_, "male" | "m") => "HUSB",
+                (0, _) => "HUSB",
+                _ => "WIFE",
+            };
+            writer.pointer(1, tag, &format!("I{}", partner + 1));
+        }
+        for (child, _) in &fam.children {
+            writer.pointer(1, "CHIL", &format!("I{}", child + 1));
+        }
+        if let Some(date) = &fam.start_date {
+            writer.line(1, "MARR", "");
+            writer.date(2, date);
+        }
+        if let Some(date) = &fam.end_date {
+            writer.line(1, "DIV", "");
+            writer.date(2, date);
+        }
+    }
+
+    writer.line(0, "TRLR", "");
+    writer.output
+}
+
+fn export_member(writer: &mut GedcomWriter, member: &FamilyMember, family_events: &[(&str, &str)]) {
+    // Wrap the birth surname in slashes when it appears in the full name, otherwise record it separately
+    let surname = member.surname_at_birth.trim();
+    match member.full_name.rfind(surname).filter(|_| !surname.is_empty()) {
+        Some(at) => {
+            let name = format!("{}/{}/{}", &member.full_name[..at], surname, &member.full_name[at + surname.len()..]);
+            writer.line(1, "NAME", name.trim());
+        }
+        None => {
+            writer.line(1, "NAME", &member.full_name);
+            if !surname.is_empty() {
+                writer.line(2, "SURN", surname);
+            }
+        }
+    }
+
+    let sex = match member.sex.to_lowercase().as_str() {
+        "male" | "m" => "M",
+        "female" | "f" => "F",
+        "other" | "x" => "X",
+        _ => "U",
+    };
+    writer.line(1, "SEX", sex);
+
+    let birth_place = [&member.birth_city, &member.birth_country]
+        .iter()
+        .filter_map(|p| p.as_deref())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let has_birth_event = member.events.iter().any(|e| e.event_type.eq_ignore_ascii_case("birth"));
+    let has_death_event = member.events.iter().any(|e| e.event_type.eq_ignore_ascii_case("death"));
+
+    if !has_birth_event && (member.birthday.is_some() || !birth_place.is_empty()) {
+        writer.line(1, "BIRT", "");
+        if let Some(birthday) = member.birthday.as_deref().filter(|d| !d.is_empty()) {
+            writer.date(2, birthday);
+        }
+        if !birth_place.is_empty() {
+            writer.line(2, "PLAC", &birth_place);
+        }
+    }
+    if let (false, Some(death_date)) = (has_death_event, &member.death_date) {
+        writer.line(1, "DEAT", "");
+        if !death_date.is_empty() {
+            writer.date(2, death_date);
+        }
+    }
+
+    for event in &member.events {
+        let event_type = event.event_type.to_lowercase();
+        if family_events.contains(&(event_type.as_str(), event.event_date.as_str())) {
+            continue;
+        }
+
+        let (tag, default_title) = match event_type.as_str() {
+            "birth" => ("BIRT", "Birth"),
+            "death" => ("DEAT", "Death"),
+            "education" => ("EDUC", "Education"),
+            "career" => ("OCCU", "Occupation"),
+            "immigration" => ("IMMI", "Immigration"),
+            _ => ("EVEN", ""),
+        };
+
+        match tag {
+            // Attributes carry their text as the payload
+            "EDUC" | "OCCU" => {
+                let prefix = format!("{}: ", default_title);
+                writer.line(1, tag, event.title.strip_prefix(&prefix).unwrap_or(&event.title));
+            }
+            "EVEN" => {
+                writer.line(1, tag, "");
+                writer.line(2, "TYPE", &event.title);
+            }
+            _ => {
+                writer.line(1, tag, "");
+                if event.title != default_title {
+                    writer.line(2, "TYPE", &event.title);
+                }
+            }
+        }
+
+        // Fall back to the member's own birth/death data for the matching event
+        let date = match tag {
+            "BIRT" if event.event_date.is_empty() => member.birthday.clone().unwrap_or_default(),
+            "DEAT" if event.event_date.is_empty() => member.death_date.clone().unwrap_or_default(),
+            _ => event.event_date.clone(),
+        };
+        if !date.is_empty() {
+            writer.date(2, &date);
+        }
+
+        let mut description = event.description.as_str();
+        let place = match description.strip_prefix("Place: ") {
+            Some(rest) => {
+                let (place, remainder) = rest.split_once('\n').unwrap_or((rest, ""));
+                description = remainder;
+                place.to_string()
+            }
+            None if tag == "BIRT" => birth_place.clone(),
+            None => String::new(),
+        };
+        if !place.is_empty() {
+            writer.line(2, "PLAC", &place);
+        }
+        if !description.trim().is_empty() {
+            writer.line(2, "NOTE", description);
+        }
+    }
+}
+
+const DATE_MODIFIERS: &[&str] = &["ABT", "CAL", "EST", "BEF", "AFT", "BET", "FROM", "TO"];
+
+// ISO dates become "12 JAN 1900"; GEDCOM-style dates pass through; anything else needs a PHRASE
+fn gedcom_date(date: &str) -> Option<String> {
+    let parts: Vec<&str> = date.trim().split('-').collect();
+    let month = |m: &str| m.parse::<usize>().ok().filter(|m| (1..=12).contains(m)).map(|m| MONTHS[m - 1]);
+
+    match parts.as_slice() {
+        [year, m, day] if year.len() == 4 => {
+            if let (Ok(year), Some(m), Ok(day)) = (year.parse::<u32>(), month(m), day.parse::<u32>()) {
+                return Some(format!("{} {} {}", day, m, year));
+            }
+        }
+        [year, m] if year.len() == 4 => {
+            if let (Ok(year), Some(m)) = (year.parse::<u32>(), month(m)) {
+                return Some(format!("{} {}", m, year));
+            }
+        }
+        [year] if year.parse::<u32>().is_ok() => return Some(year.to_string()),
+        _ => {}
+    }
+
+    let first = date.split_whitespace().next().unwrap_or_default().to_uppercase();
+    if DATE_MODIFIERS.contains(&first.as_str()) {
+        return Some(date.trim().to_uppercase());
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "0 HEAD
+1 GEDC
+2 VERS 5.5.1
+1 CHAR UTF-8
+0 @I1@ INDI
+1 NAME John /Smith/
+1 SEX M
+1 BIRT
+2 DATE 12 JAN 1900
+2 PLAC Springfield, Illinois, USA
+1 OCCU Farmer
+1 FAMS @F1@
+0 @I2@ INDI
+1 NAME Mary /Jones/
+1 SEX F
+1 DEAT
+2 DATE ABT 1970
+0 @I3@ INDI
+1 NAME Anne /Smith/
+1 FAMC @F1@
+2 PEDI adopted
+1 NOTE Loved gardening
+2 CONT and birds
+0 @F1@ FAM
+1 HUSB @I1@
+1 WIFE @I2@
+1 CHIL @I3@
+1 CHIL @I9@
+1 MARR
+2 DATE 1 JUN 1925
+0 @S1@ SOUR
+1 TITL Parish register
+0 TRLR
+";
+
+    fn import(input: &str) -> Result<GedcomImport, BackendError> {
+        let mut counter = 0;
+        let mut next_id = || {
+            counter += 1;
+            format!("id{}", counter)
+        };
+        build_import(input, Principal::anonymous(), 0, &mut next_id)
+    }
+
+    #[test]
+    fn test_convert_date() {
+        assert_eq!(convert_date("12 JAN 1900"), "1900-01-12");
+        assert_eq!(convert_date("jun 1925"), "1925-06");
+        assert_eq!(convert_date("1850"), "1850");
+        assert_eq!(convert_date("@#DGREGORIAN@ 3 MAR 1901"), "1901-03-03");
+        assert_eq!(convert_date("ABT 1970"), "ABT 1970");
+        assert_eq!(convert_date("BET 1900 AND 1910"), "BET 1900 AND 1910");
+        assert_eq!(convert_date("31 FEB 1900"), "31 FEB 1900");
+    }
+
+    #[test]
+    fn test_import_members_and_events() {
+        let result = import(SAMPLE).unwrap();
+        assert_eq!(result.version, Some("5.5.1".to_string()));
+        assert_eq!(result.members.len(), 3);
+
+        let john = &result.members[0];
+        assert_eq!(john.full_name, "John Smith");
+        assert_eq!(john.surname_at_birth, "Smith");
+        assert_eq!(john.sex, "Male");
+        assert_eq!(john.birthday, Some("1900-01-12".to_string()));
+        assert_eq!(john.birth_city, Some("Springfield".to_string()));
+        assert_eq!(john.birth_country, Some("USA".to_string()));
+        let titles: Vec<&str> = john.events.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, vec!["Occupation: Farmer", "Birth", "Marriage"]);
+
+        let mary = &result.members[1];
+        assert_eq!(mary.death_date, Some("ABT 1970".to_string()));
+        assert_eq!(mary.events.iter().filter(|e| e.event_type == "Marriage").count(), 1);
+    }
+
+    #[test]
+    fn test_import_relationships() {
+        let result = import(SAMPLE).unwrap();
+        let anne_id = &result.members[2].id;
+
+        let spouse = result.relationships.iter()
+            .find(|r| r.relationship_type == RelationshipType::SpouseOf)
+            .unwrap();
+        assert_eq!(spouse.start_date, Some("1925-06-01".to_string()));
+
+        let parents: Vec<&FamilyRelationship> = result.relationships.iter()
+            .filter(|r| r.relationship_type == RelationshipType::ParentOf && &r.to_member_id == anne_id)
+            .collect();
+        assert_eq!(parents.len(), 2);
+        assert!(parents.iter().all(|r| r.qualifier == RelationshipQualifier::Adoptive));
+    }
+
+    #[test]
+    fn test_import_keeps_relationships_with_approximate_dates() {
+        let input = SAMPLE
+            .replace("1 MARR\n2 DATE 1 JUN 1925", "1 MARR\n2 DATE ABT 1925\n1 DIV\n2 DATE 31 FEB 1950");
+        let result = import(&input).unwrap();
+
+        let spouse = result.relationships.iter()
+            .find(|r| r.relationship_type == RelationshipType::SpouseOf)
+            .unwrap();
+        assert_eq!(spouse.start_date, None);
+        assert_eq!(spouse.end_date, None);
+        assert!(result.skipped_records.iter().all(|s| s.tag != "FAM"));
+
+        let dates: Vec<&str> = result.members[0].events.iter().map(|e| e.event_date.as_str()).collect();
+        assert!(dates.contains(&"ABT 1925"));
+        assert!(dates.contains(&"31 FEB 1950"));
+    }
+
+    #[test]
+    fn test_import_reports_unmapped_records() {
+        let result = import(SAMPLE).unwrap();
+        let skipped: Vec<(&str, &str)> = result.skipped_records.iter()
+            .map(|s| (s.tag.as_str(), s.reason.as_str()))
+            .collect();
+
+        assert!(skipped.contains(&("NOTE", "Individual attribute is not supported")));
+        assert!(skipped.contains(&("CHIL", "Pointer to an unknown individual")));
+        assert!(skipped.contains(&("SOUR", "Record type is not supported")));
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        assert!(matches!(import("not a gedcom file"), Err(BackendError::Malformed { line: 1, .. })));
+        assert!(matches!(import("0 @I1@ INDI\n1 NAME John\n"), Err(BackendError::Malformed { line: 1, .. })));
+        assert_eq!(import("0 HEAD\n2 GEDC\n").err(), Some(BackendError::malformed("gedcom", 2, "skipped_level")));
+    }
+
+    #[test]
+    fn test_gedcom_date() {
+        assert_eq!(gedcom_date("1900-01-12"), Some("12 JAN 1900".to_string()));
+        assert_eq!(gedcom_date("1925-06"), Some("JUN 1925".to_string()));
+        assert_eq!(gedcom_date("1850"), Some("1850".to_string()));
+        assert_eq!(gedcom_date("abt 1970"), Some("ABT 1970".to_string()));
+        assert_eq!(gedcom_date("sometime in spring"), None);
+    }
+
+    #[test]
+    fn test_export_structure() {
+        let imported = import(SAMPLE).unwrap();
+        let family = Family {
+            name: "Smith".to_string(),
+            members: imported.members,
+            ..Family::default()
+        };
+        let output = export_family(&family, &imported.relationships);
+
+        assert!(output.starts_with("0 HEAD\n1 GEDC\n2 VERS 7.0\n"));
+        assert!(output.ends_with("0 TRLR\n"));
+        assert!(output.contains("0 @I1@ INDI\n1 NAME John /Smith/\n1 SEX M\n"));
+        assert!(output.contains("1 BIRT\n2 DATE 12 JAN 1900\n2 PLAC Springfield, Illinois, USA\n"));
+        assert!(output.contains("1 OCCU Farmer\n"));
+        assert!(output.contains("1 DEAT\n2 DATE ABT 1970\n"));
+        assert!(output.contains("1 FAMC @F1@\n2 PEDI ADOPTED\n"));
+        assert!(output.contains("0 @F1@ FAM\n1 HUSB @I1@\n1 WIFE @I2@\n1 CHIL @I3@\n1 MARR\n2 DATE 1 JUN 1925\n"));
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let imported = import(SAMPLE).unwrap();
+        let family = Family {
+            members: imported.members,
+            ..Family::default()
+        };
+        let reimported = import(&export_family(&family, &imported.relationships)).unwrap();
+
+        assert_eq!(reimported.version, Some("7.0".to_string()));
+        assert_eq!(reimported.members.len(), family.members.len());
+        assert_eq!(reimported.relationships.len(), imported.relationships.len());
+        for (before, after) in family.members.iter().zip(&reimported.members) {
+            assert_eq!(before.full_name, after.full_name);
+            assert_eq!(before.surname_at_birth, after.surname_at_birth);
+            assert_eq!(before.birthday, after.birthday);
+            assert_eq!(before.death_date, after.death_date);
+            assert_eq!(before.events.len(), after.events.len());
+        }
+        assert!(reimported.skipped_records.is_empty());
+    }
+}
@@ -1,12 +1,23 @@
 use candid::Principal;
 use ic_cdk::api;
 use ic_cdk_macros::*;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use std::thread::LocalKey;
 
 use crate::types::{
     UserProfile, FamilyMember, GhostProfileMatch, ClaimRequest, ClaimStatus, 
-    ProcessClaimRequest, DEV_MODE
+    ProcessClaimRequest, RewardActivity, ClaimFilter, Page, PageRequest, DEV_MODE
 };
-use crate::storage::{PROFILES, FAMILIES, USER_FAMILIES, CLAIM_REQUESTS, generate_id};
+use crate::storage::{PROFILES, USER_FAMILIES, CLAIM_REQUESTS, CLAIMS_BY_FAMILY, CLAIMS_BY_REQUESTER, Memory, generate_id};
+use crate::indexes::{
+    claim_family_key, claim_requester_key, claim_status, ids_with_status, reindex, IndexKey, CLAIM_STATUSES
+};
+use crate::rewards::{linked_member_subject, queue_reward};
+use crate::error::BackendError;
+use crate::pagination::{newest_first, within, MAX_PAGE_SIZE};
+use crate::family::{has_family_access, store};
+use crate::family::roles::{has_permission, minimum_role, Permission};
 
 // Helper function to calculate similarity between profiles
 fn calculate_similarity(user_profile: &UserProfile, ghost_member: &FamilyMember) -> u8 {
@@ -45,20 +56,18 @@ fn calculate_similarity(user_profile: &UserProfile, ghost_member: &FamilyMember)
     }
 
     // Return percentage (score out of total possible)
-    if total_checks == 0 {
-        0
-    } else {
-        (score * 100) / total_checks
-    }
+    (score as u16 * 100)
+        .checked_div(total_checks as u16)
+        .unwrap_or(0) as u8
 }
 
 // Find ghost profiles that match the user's profile
 #[query]
-pub fn find_matching_ghost_profiles() -> Result<Vec<GhostProfileMatch>, String> {
+pub fn find_matching_ghost_profiles() -> Result<Vec<GhostProfileMatch>, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     // Get user's profile
@@ -69,59 +78,57 @@ pub fn find_matching_ghost_profiles() -> Result<Vec<GhostProfileMatch>, String>
 
     let user_profile = match user_profile {
         Some(profile) => profile,
-        None => return Err("User profile not found".to_string()),
+        None => return Err(BackendError::not_found("profile", caller.to_text())),
     };
 
     let mut matches = Vec::new();
 
     // Search through all families for ghost profiles
-    FAMILIES.with(|families| {
-        let families = families.borrow();
+    for family_id in store::family_ids() {
+        let Some(family) = store::load_family(&family_id) else { continue };
+
+        // Skip families where user is already a member/admin
+        if has_family_access(&family, caller) {
+            continue;
+        }
         
-        for (family_id, family) in families.iter() {
-            // Skip families where user is already a member/admin
-            if family.admin == caller {
-                continue;
-            }
-            
-            // Skip families that are not visible for ghost profile matching
-            if !family.is_visible {
-                continue;
-            }
-            
-            // Look for ghost profiles (members without profile_principal)
-            for member in &family.members {
-                if member.profile_principal.is_none() {
-                    let similarity = calculate_similarity(&user_profile, member);
-                    
-                    // Only include matches with high similarity (70% or above)
-                    if similarity >= 70 {
-                        matches.push(GhostProfileMatch {
-                            family_id: family_id.clone(),
-                            member_id: member.id.clone(),
-                            family_name: family.name.clone(),
-                            ghost_profile_name: member.full_name.clone(),
-                            similarity_score: similarity,
-                        });
-                    }
+        // Skip families that are not visible for ghost profile matching
+        if !family.is_visible {
+            continue;
+        }
+        
+        // Look for ghost profiles (members without profile_principal)
+        for member in &family.members {
+            if member.profile_principal.is_none() {
+                let similarity = calculate_similarity(&user_profile, member);
+                
+                // Only include matches with high similarity (70% or above)
+                if similarity >= 70 {
+                    matches.push(GhostProfileMatch {
+                        family_id: family_id.clone(),
+                        member_id: member.id.clone(),
+                        family_name: family.name.clone(),
+                        ghost_profile_name: member.full_name.clone(),
+                        similarity_score: similarity,
+                    });
                 }
             }
         }
-    });
+    }
 
     // Sort by similarity score (highest first)
-    matches.sort_by(|a, b| b.similarity_score.cmp(&a.similarity_score));
+    matches.sort_by_key(|m| std::cmp::Reverse(m.similarity_score));
 
     Ok(matches)
 }
 
 // Submit a claim request for a ghost profile
 #[update]
-pub fn submit_ghost_profile_claim(family_id: String, member_id: String) -> Result<ClaimRequest, String> {
+pub fn submit_ghost_profile_claim(family_id: String, member_id: String) -> Result<ClaimRequest, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     // Get user's profile
@@ -132,43 +139,24 @@ pub fn submit_ghost_profile_claim(family_id: String, member_id: String) -> Resul
 
     let user_profile = match user_profile {
         Some(profile) => profile,
-        None => return Err("User profile not found".to_string()),
+        None => return Err(BackendError::not_found("profile", caller.to_text())),
     };
 
     // Verify the ghost member exists and is actually a ghost profile
-    let ghost_member = FAMILIES.with(|families| {
-        let families = families.borrow();
-        match families.get(&family_id) {
-            Some(family) => {
-                match family.members.iter().find(|m| m.id == member_id) {
-                    Some(member) => {
-                        if member.profile_principal.is_some() {
-                            return Err("This profile is already claimed".to_string());
-                        }
-                        Ok(member.clone())
-                    }
-                    None => Err("Member not found in family".to_string()),
-                }
-            }
-            None => Err("Family not found".to_string()),
-        }
-    })?;
+    let family = store::load_family(&family_id)
+        .ok_or_else(|| BackendError::not_found("family", &family_id))?;
+    let ghost_member = family.members.into_iter()
+        .find(|m| m.id == member_id)
+        .ok_or_else(|| BackendError::not_found("member", &member_id))?;
+    if ghost_member.profile_principal.is_some() {
+        return Err(BackendError::conflict("already_claimed"));
+    }
 
     // Check if there's already a pending claim for this ghost profile
-    let existing_claim = CLAIM_REQUESTS.with(|claims| {
-        let claims = claims.borrow();
-        for (_, claim) in claims.iter() {
-            if claim.family_id == family_id 
-                && claim.member_id == member_id 
-                && matches!(claim.status, ClaimStatus::Pending) {
-                return Some(claim);
-            }
-        }
-        None
-    });
+    let pending = claims_with_status(&CLAIMS_BY_FAMILY, family_id.as_bytes(), &[claim_status(&ClaimStatus::Pending)]);
 
-    if existing_claim.is_some() {
-        return Err("There is already a pending claim for this ghost profile".to_string());
+    if pending.iter().any(|claim| claim.member_id == member_id) {
+        return Err(BackendError::conflict("claim_pending"));
     }
 
     // Create the claim request
@@ -181,70 +169,53 @@ pub fn submit_ghost_profile_claim(family_id: String, member_id: String) -> Resul
         family_id: family_id.clone(),
         member_id: member_id.clone(),
         requester_profile: user_profile,
-        ghost_member: ghost_member,
+        ghost_member,
         created_at: current_time,
         status: ClaimStatus::Pending,
     };
 
     // Store the claim request
-    CLAIM_REQUESTS.with(|claims| {
-        let mut claims = claims.borrow_mut();
-        claims.insert(claim_id, claim_request.clone());
-    });
+    save_claim(claim_request.clone());
 
     Ok(claim_request)
 }
 
-// Get pending claim requests for a family admin
+// Get pending claim requests for a family admin; the filter's status is ignored
 #[query]
-pub fn get_pending_claims_for_admin() -> Result<Vec<ClaimRequest>, String> {
+pub fn get_pending_claims_for_admin(filter: ClaimFilter, page: PageRequest) -> Result<Page<ClaimRequest>, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
-    let mut admin_claims = Vec::new();
-
-    // Get all families where user is admin
-    let admin_families = FAMILIES.with(|families| {
-        let families = families.borrow();
-        let mut family_ids = Vec::new();
-        
-        for (family_id, family) in families.iter() {
-            if family.admin == caller {
-                family_ids.push(family_id);
-            }
-        }
-        
-        family_ids
+    // Families the caller belongs to where they may process claims
+    let family_ids = USER_FAMILIES.with(|user_families| {
+        user_families.borrow().get(&caller).unwrap_or_default().0
     });
+    let admin_families: Vec<String> = family_ids.into_iter()
+        .filter(|id| filter.family_id.as_ref().is_none_or(|wanted| wanted == id))
+        .filter(|id| store::load_family(id).is_some_and(|family| has_permission(&family, caller, Permission::ProcessClaims)))
+        .collect();
 
     // Get pending claims for those families
-    CLAIM_REQUESTS.with(|claims| {
-        let claims = claims.borrow();
-        
-        for (_, claim) in claims.iter() {
-            if admin_families.contains(&claim.family_id) 
-                && matches!(claim.status, ClaimStatus::Pending) {
-                admin_claims.push(claim);
-            }
-        }
-    });
-
-    // Sort by creation time (newest first)
-    admin_claims.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-
-    Ok(admin_claims)
+    let pending = claim_status(&ClaimStatus::Pending);
+    let admin_claims = admin_families.iter()
+        .flat_map(|family_id| claims_with_status(&CLAIMS_BY_FAMILY, family_id.as_bytes(), &[pending]))
+        .filter(|claim| claim_matches(claim, &filter))
+        .collect();
+
+    // Newest first
+    newest_first(admin_claims, |c| (c.created_at, c.id.clone()), &page, MAX_PAGE_SIZE)
 }
 
 // Process a claim request (approve/reject)
 #[update]
-pub fn process_ghost_profile_claim(request: ProcessClaimRequest) -> Result<String, String> {
+pub fn process_ghost_profile_claim(request: ProcessClaimRequest) -> Result<String, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     // Get and verify the claim request
@@ -255,25 +226,20 @@ pub fn process_ghost_profile_claim(request: ProcessClaimRequest) -> Result<Strin
 
     let mut claim = match claim {
         Some(c) => c,
-        None => return Err("Claim request not found".to_string()),
+        None => return Err(BackendError::not_found("claim", &request.claim_id)),
     };
 
-    // Verify caller is the admin of the family
-    let is_admin = FAMILIES.with(|families| {
-        let families = families.borrow();
-        match families.get(&claim.family_id) {
-            Some(family) => family.admin == caller,
-            None => false,
-        }
-    });
+    // Verify caller may process claims for the family
+    let is_admin = store::load_family(&claim.family_id)
+        .is_some_and(|family| has_permission(&family, caller, Permission::ProcessClaims));
 
     if !is_admin {
-        return Err("Only family admin can process claim requests".to_string());
+        return Err(BackendError::requires(minimum_role(Permission::ProcessClaims)));
     }
 
     // Check if claim is still pending
     if !matches!(claim.status, ClaimStatus::Pending) {
-        return Err("This claim request has already been processed".to_string());
+        return Err(BackendError::conflict("already_processed"));
     }
 
     // Update claim status
@@ -281,18 +247,25 @@ pub fn process_ghost_profile_claim(request: ProcessClaimRequest) -> Result<Strin
 
     // If approved, link the ghost profile to the user
     if request.approve {
-        FAMILIES.with(|families| {
-            let mut families = families.borrow_mut();
-            if let Some(mut family) = families.get(&claim.family_id) {
-                // Find and update the ghost member
-                if let Some(member) = family.members.iter_mut().find(|m| m.id == claim.member_id) {
-                    member.profile_principal = Some(claim.requester);
-                    family.updated_at = api::time();
+        if let Some(mut family) = store::load_family(&claim.family_id) {
+            // Find and update the ghost member
+            if let Some(member) = family.members.iter_mut().find(|m| m.id == claim.member_id) {
+                member.profile_principal = Some(claim.requester);
+                store::save_member(&claim.family_id, member)?;
+                let recorded_by = member.created_by;
+                family.updated_at = api::time();
+                store::save_family_header(&family);
+
+                // Credit whoever recorded the ghost, unless they claimed themselves
+                if recorded_by != claim.requester {
+                    queue_reward(
+                        recorded_by,
+                        RewardActivity::MemberAdded,
+                        &linked_member_subject(&claim.family_id, claim.requester),
+                    );
                 }
-                
-                families.insert(claim.family_id.clone(), family);
             }
-        });
+        }
 
         // Add the family to user's family list
         USER_FAMILIES.with(|user_families| {
@@ -305,16 +278,18 @@ pub fn process_ghost_profile_claim(request: ProcessClaimRequest) -> Result<Strin
         });
     }
 
+    // The claimant is rewarded for connecting their account to the tree
+    if request.approve {
+        queue_reward(claim.requester, RewardActivity::ClaimApproved, &request.claim_id);
+    }
+
     // Update the claim request
-    CLAIM_REQUESTS.with(|claims| {
-        let mut claims = claims.borrow_mut();
-        claims.insert(request.claim_id.clone(), claim);
-    });
+    save_claim(claim);
 
     let result_msg = if request.approve {
-        format!("Ghost profile claim approved. User has been linked to the family member.")
+        "Ghost profile claim approved. User has been linked to the family member.".to_string()
     } else {
-        format!("Ghost profile claim rejected.")
+        "Ghost profile claim rejected.".to_string()
     };
 
     Ok(result_msg)
@@ -322,27 +297,70 @@ pub fn process_ghost_profile_claim(request: ProcessClaimRequest) -> Result<Strin
 
 // Get claim requests for a specific user
 #[query]
-pub fn get_my_claim_requests() -> Result<Vec<ClaimRequest>, String> {
+pub fn get_my_claim_requests(filter: ClaimFilter, page: PageRequest) -> Result<Page<ClaimRequest>, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
-    let mut user_claims = Vec::new();
+    let statuses: Vec<u8> = match &filter.status {
+        Some(status) => vec![claim_status(status)],
+        None => CLAIM_STATUSES.iter().map(claim_status).collect(),
+    };
+    let user_claims = claims_with_status(&CLAIMS_BY_REQUESTER, caller.as_slice(), &statuses).into_iter()
+        .filter(|claim| claim_matches(claim, &filter))
+        .collect();
 
+    // Newest first
+    newest_first(user_claims, |c| (c.created_at, c.id.clone()), &page, MAX_PAGE_SIZE)
+}
+
+// Claim writes go through here so both claim indexes stay in step
+fn save_claim(claim: ClaimRequest) {
+    let requester_key = claim_requester_key(&claim);
+    let family_key = claim_family_key(&claim);
+    let previous = CLAIM_REQUESTS.with(|claims| claims.borrow_mut().insert(claim.id.clone(), claim));
+    CLAIMS_BY_REQUESTER.with(|index| {
+        reindex(&mut index.borrow_mut(), previous.as_ref().map(claim_requester_key), requester_key);
+    });
+    CLAIMS_BY_FAMILY.with(|index| {
+        reindex(&mut index.borrow_mut(), previous.as_ref().map(claim_family_key), family_key);
+    });
+}
+
+fn claims_with_status(
+    index: &'static LocalKey<RefCell<StableBTreeMap<IndexKey, (), Memory>>>,
+    owner: &[u8],
+    statuses: &[u8],
+) -> Vec<ClaimRequest> {
+    let ids: Vec<String> = index.with(|index| {
+        let index = index.borrow();
+        statuses.iter().flat_map(|status| ids_with_status(&index, owner, *status)).collect()
+    });
     CLAIM_REQUESTS.with(|claims| {
         let claims = claims.borrow();
-        
-        for (_, claim) in claims.iter() {
-            if claim.requester == caller {
-                user_claims.push(claim);
-            }
-        }
-    });
+        ids.iter().filter_map(|id| claims.get(id)).collect()
+    })
+}
 
-    // Sort by creation time (newest first)
-    user_claims.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+// Indexes added after launch start empty; fill them from the existing claims once
+pub fn rebuild_claim_indexes() -> u64 {
+    let mut indexed = 0;
+    if CLAIMS_BY_REQUESTER.with(|index| index.borrow().is_empty()) {
+        CLAIM_REQUESTS.with(|claims| {
+            for (_, claim) in claims.borrow().iter() {
+                CLAIMS_BY_REQUESTER.with(|index| index.borrow_mut().insert(claim_requester_key(&claim), ()));
+                CLAIMS_BY_FAMILY.with(|index| index.borrow_mut().insert(claim_family_key(&claim), ()));
+                indexed += 1;
+            }
+        });
+    }
+    indexed
+}
 
-    Ok(user_claims)
+// Family and time filters; callers decide how the status filter applies
+fn claim_matches(claim: &ClaimRequest, filter: &ClaimFilter) -> bool {
+    filter.family_id.as_ref().is_none_or(|id| &claim.family_id == id)
+        && within(claim.created_at, filter.created_after, filter.created_before)
 }
\ No newline at end of file
@@ -0,0 +1,343 @@
+// Serves family media over the HTTP gateway at /media/<media ID>?token=<access token>
+//
+// Responses are private and uncertified, so clients use the canister's raw domain.
+// Each response carries at most one stored chunk; the rest of the requested range
+// follows through http_request_streaming_callback.
+use candid::Principal;
+use ic_cdk::api;
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk_macros::*;
+use sha2::{Digest, Sha256};
+
+use crate::types::{
+    HeaderField, HttpRequest, HttpResponse, MediaAccessGrant, MediaAccessToken, MediaAsset, MediaStatus,
+    StreamingCallback, StreamingCallbackHttpResponse, StreamingCallbackToken, StreamingStrategy, DEV_MODE
+};
+use crate::storage::MEDIA_ACCESS_GRANTS;
+use crate::error::BackendError;
+use crate::family::get_family_roster;
+use crate::family::media::{get_media_internal, read_chunk, CHUNK_SIZE};
+use crate::family::roles::{has_permission, require_permission, Permission};
+
+const ACCESS_TOKEN_TTL: u64 = 60 * 60 * 1_000_000_000; // 1 hour
+const MEDIA_PATH_PREFIX: &str = "/media/";
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    Partial { start: u64, end: u64 }, // end is exclusive
+    Unsatisfiable,
+}
+
+// One token covers every media asset of a family until it expires
+#[update]
+pub async fn create_media_access_token(family_id: String) -> Result<MediaAccessToken, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    let family = get_family_roster(&family_id)?;
+    require_permission(&family, caller, Permission::View)?;
+
+    let (random,) = raw_rand().await
+        .map_err(|(code, message)| BackendError::external(format!("raw_rand failed ({:?}): {}", code, message)))?;
+    let token = to_hex(&random);
+
+    let now = api::time();
+    let grant = MediaAccessGrant {
+        family_id: family_id.clone(),
+        principal: caller,
+        expires_at: now + ACCESS_TOKEN_TTL,
+    };
+    prune_expired_grants(now);
+    MEDIA_ACCESS_GRANTS.with(|grants| grants.borrow_mut().insert(token_key(&token), grant.clone()));
+
+    Ok(MediaAccessToken {
+        token,
+        family_id,
+        expires_at: grant.expires_at,
+    })
+}
+
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    let head = match request.method.to_ascii_uppercase().as_str() {
+        "GET" => false,
+        "HEAD" => true,
+        _ => return error_response(405, "Method not allowed"),
+    };
+
+    let (media_id, access_token) = match parse_media_url(&request.url) {
+        Some((media_id, Some(token))) => (media_id, token),
+        Some((_, None)) => return error_response(401, "Access token required"),
+        None => return error_response(404, "Not found"),
+    };
+
+    let asset = match authorize(&media_id, &access_token, api::time()) {
+        Ok(asset) => asset,
+        Err((status, message)) => return error_response(status, message),
+    };
+
+    let range_header = header_value(&request.headers, "range");
+    let (status, start, end) = match parse_range(range_header, asset.size) {
+        ByteRange::Full => (200, 0, asset.size),
+        ByteRange::Partial { start, end } => (206, start, end),
+        ByteRange::Unsatisfiable => {
+            let mut response = error_response(416, "Range not satisfiable");
+            response.headers.push(("Content-Range".to_string(), format!("bytes */{}", asset.size)));
+            return response;
+        }
+    };
+
+    let mut headers = vec![
+        ("Content-Type".to_string(), asset.mime_type.clone()),
+        ("Content-Length".to_string(), (end - start).to_string()),
+        ("Accept-Ranges".to_string(), "bytes".to_string()),
+        ("Cache-Control".to_string(), "private, max-age=3600".to_string()),
+        ("X-Content-Type-Options".to_string(), "nosniff".to_string()),
+    ];
+    if status == 206 {
+        headers.push(("Content-Range".to_string(), format!("bytes {}-{}/{}", start, end - 1, asset.size)));
+    }
+
+    if head {
+        return HttpResponse { status_code: status, headers, body: Vec::new(), streaming_strategy: None };
+    }
+
+    let token = StreamingCallbackToken { media_id, access_token, start, end };
+    match read_range(&token) {
+        Ok((body, next)) => HttpResponse {
+            status_code: status,
+            headers,
+            body,
+            streaming_strategy: next.map(|token| StreamingStrategy::Callback {
+                callback: StreamingCallback::new(api::id(), "http_request_streaming_callback".to_string()),
+                token,
+            }),
+        },
+        Err(message) => error_response(500, &message),
+    }
+}
+
+// Tokens come back from the gateway unverified, so access is checked again on every call
+#[query]
+pub fn http_request_streaming_callback(token: StreamingCallbackToken) -> StreamingCallbackHttpResponse {
+    let asset = authorize(&token.media_id, &token.access_token, api::time())
+        .unwrap_or_else(|(_, message)| ic_cdk::trap(message));
+    if token.start >= token.end || token.end > asset.size {
+        ic_cdk::trap("Invalid streaming token");
+    }
+
+    match read_range(&token) {
+        Ok((body, token)) => StreamingCallbackHttpResponse { body, token },
+        Err(message) => ic_cdk::trap(&message),
+    }
+}
+
+fn authorize(media_id: &str, access_token: &str, now: u64) -> Result<MediaAsset, (u16, &'static str)> {
+    let grant = MEDIA_ACCESS_GRANTS.with(|grants| grants.borrow().get(&token_key(access_token)))
+        .ok_or((403, "Invalid access token"))?;
+    if grant.expires_at <= now {
+        return Err((403, "Access token expired"));
+    }
+
+    let asset = get_media_internal(media_id).map_err(|_| (404, "Not found"))?;
+    if asset.family_id != grant.family_id {
+        return Err((403, "Access token does not cover this media"));
+    }
+    let family = get_family_roster(&grant.family_id).map_err(|_| (404, "Not found"))?;
+    if !has_permission(&family, grant.principal, Permission::View) {
+        return Err((403, "Access to this family has been revoked"));
+    }
+    if asset.status != MediaStatus::Ready {
+        return Err((404, "Not found"));
+    }
+    Ok(asset)
+}
+
+// Returns the bytes from token.start up to the end of its chunk, and a token for the rest
+fn read_range(token: &StreamingCallbackToken) -> Result<(Vec<u8>, Option<StreamingCallbackToken>), String> {
+    let index = (token.start / CHUNK_SIZE) as u32;
+    let offset = (token.start % CHUNK_SIZE) as usize;
+    let chunk = read_chunk(&token.media_id, index)?;
+
+    let wanted = (token.end - token.start) as usize;
+    let slice_end = chunk.len().min(offset + wanted);
+    if offset >= slice_end {
+        return Err("Media chunk is shorter than expected".to_string());
+    }
+    let body = chunk[offset..slice_end].to_vec();
+
+    let next_start = token.start + body.len() as u64;
+    let next = (next_start < token.end).then(|| StreamingCallbackToken { start: next_start, ..token.clone() });
+    Ok((body, next))
+}
+
+fn parse_media_url(url: &str) -> Option<(String, Option<String>)> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let media_id = path.strip_prefix(MEDIA_PATH_PREFIX)?;
+    if media_id.is_empty() || media_id.contains('/') {
+        return None;
+    }
+
+    let token = query.split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .filter(|token| !token.is_empty());
+    Some((media_id.to_string(), token.map(str::to_string)))
+}
+
+// Single ranges only; headers we cannot interpret are ignored and the whole file is sent
+fn parse_range(header: Option<&str>, size: u64) -> ByteRange {
+    let spec = match header.and_then(|h| h.trim().strip_prefix("bytes=")) {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return ByteRange::Full,
+    };
+    let (first, last) = match spec.split_once('-') {
+        Some(parts) => parts,
+        None => return ByteRange::Full,
+    };
+
+    match (first.parse::<u64>(), last.parse::<u64>()) {
+        // bytes=-N: the final N bytes
+        (Err(_), Ok(suffix)) if first.is_empty() => {
+            if suffix == 0 {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial { start: size.saturating_sub(suffix), end: size }
+            }
+        }
+        (Ok(start), Err(_)) if last.is_empty() => {
+            if start >= size {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial { start, end: size }
+            }
+        }
+        (Ok(start), Ok(last)) if start <= last => {
+            if start >= size {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial { start, end: size.min(last.saturating_add(1)) }
+            }
+        }
+        _ => ByteRange::Full,
+    }
+}
+
+fn header_value<'a>(headers: &'a [HeaderField], name: &str) -> Option<&'a str> {
+    headers.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn error_response(status_code: u16, message: &str) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![("Content-Type".to_string(), "text/plain; charset=utf-8".to_string())],
+        body: message.as_bytes().to_vec(),
+        streaming_strategy: None,
+    }
+}
+
+// Only token hashes are stored, so a leaked grant table does not leak usable tokens
+fn token_key(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn prune_expired_grants(now: u64) {
+    MEDIA_ACCESS_GRANTS.with(|grants| {
+        let mut grants = grants.borrow_mut();
+        let expired: Vec<String> = grants.iter()
+            .filter(|(_, grant)| grant.expires_at <= now)
+            .map(|(key, _)| key)
+            .collect();
+        for key in expired {
+            grants.remove(&key);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MEDIA_CHUNKS;
+    use crate::family::media::chunk_key;
+
+    #[test]
+    fn test_parse_media_url() {
+        assert_eq!(
+            parse_media_url("/media/abc123?token=ff00&w=200"),
+            Some(("abc123".to_string(), Some("ff00".to_string())))
+        );
+        assert_eq!(parse_media_url("/media/abc123"), Some(("abc123".to_string(), None)));
+        assert_eq!(parse_media_url("/media/abc123?token="), Some(("abc123".to_string(), None)));
+        assert_eq!(parse_media_url("/media/"), None);
+        assert_eq!(parse_media_url("/media/a/b?token=1"), None);
+        assert_eq!(parse_media_url("/index.html"), None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None, 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), ByteRange::Partial { start: 0, end: 10 });
+        assert_eq!(parse_range(Some("bytes=90-"), 100), ByteRange::Partial { start: 90, end: 100 });
+        assert_eq!(parse_range(Some("bytes=-10"), 100), ByteRange::Partial { start: 90, end: 100 });
+        assert_eq!(parse_range(Some("bytes=-500"), 100), ByteRange::Partial { start: 0, end: 100 });
+        assert_eq!(parse_range(Some("bytes=50-500"), 100), ByteRange::Partial { start: 50, end: 100 });
+        assert_eq!(parse_range(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+
+        // Malformed and multi-part ranges fall back to the whole file
+        assert_eq!(parse_range(Some("bytes=9-0"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("items=0-1"), 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_read_range_streams_across_chunks() {
+        let first = vec![1u8; CHUNK_SIZE as usize];
+        MEDIA_CHUNKS.with(|chunks| {
+            let mut chunks = chunks.borrow_mut();
+            chunks.insert(chunk_key("m1", 0), first);
+            chunks.insert(chunk_key("m1", 1), vec![2, 3, 4, 5]);
+        });
+
+        let token = StreamingCallbackToken {
+            media_id: "m1".to_string(),
+            access_token: "t".to_string(),
+            start: CHUNK_SIZE - 2,
+            end: CHUNK_SIZE + 3,
+        };
+        let (body, next) = read_range(&token).unwrap();
+        assert_eq!(body, vec![1, 1]);
+
+        let next = next.unwrap();
+        assert_eq!(next.start, CHUNK_SIZE);
+        let (body, last) = read_range(&next).unwrap();
+        assert_eq!(body, vec![2, 3, 4]);
+        assert!(last.is_none());
+    }
+
+    #[test]
+    fn test_expired_grants_are_pruned() {
+        MEDIA_ACCESS_GRANTS.with(|grants| {
+            let mut grants = grants.borrow_mut();
+            for (token, expires_at) in [("old", 10), ("new", 30)] {
+                let grant = MediaAccessGrant { family_id: "f1".to_string(), principal: Principal::anonymous(), expires_at };
+                grants.insert(token_key(token), grant);
+            }
+        });
+
+        prune_expired_grants(20);
+        assert!(MEDIA_ACCESS_GRANTS.with(|grants| grants.borrow().get(&token_key("old"))).is_none());
+        assert!(MEDIA_ACCESS_GRANTS.with(|grants| grants.borrow().get(&token_key("new"))).is_some());
+        assert_eq!(authorize("m1", "old", 20).err(), Some((403, "Invalid access token")));
+        assert_eq!(authorize("m1", "new", 30).err(), Some((403, "Access token expired")));
+    }
+}
@@ -0,0 +1,238 @@
+use ic_stable_structures::storable::{Bound, Storable};
+use ic_stable_structures::{Memory, StableBTreeMap};
+use std::borrow::Cow;
+
+use crate::types::{
+    ClaimRequest, ClaimStatus, FamilyInvitation, InvitationStatus, MediaAsset, MediaStatus, Notification, Page, PageRequest,
+    RewardRecord, TreasuryTransaction,
+};
+use crate::error::BackendError;
+use crate::pagination::{page_limit, parse_time_cursor, take_page, time_cursor};
+
+// Principals are at most 29 bytes. Reward idempotency keys, which embed a principal
+// in text form, are the longest ids.
+const MAX_OWNER_LENGTH: usize = 64;
+const MAX_ID_LENGTH: usize = 128;
+
+// Secondary index entry. Entries sort by owner (a principal or family ID), then status,
+// then creation time, so one owner's records in one status are a contiguous range.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IndexKey {
+    pub owner: Vec<u8>,
+    pub status: u8,
+    pub created_at: u64,
+    pub id: String,
+}
+
+impl IndexKey {
+    pub fn new(owner: &[u8], status: u8, created_at: u64, id: &str) -> Self {
+        assert!(owner.len() <= MAX_OWNER_LENGTH && id.len() <= MAX_ID_LENGTH, "Index key too long");
+        IndexKey { owner: owner.to_vec(), status, created_at, id: id.to_string() }
+    }
+}
+
+impl Storable for IndexKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(10 + self.owner.len() + self.id.len());
+        bytes.push(self.owner.len() as u8);
+        bytes.extend_from_slice(&self.owner);
+        bytes.push(self.status);
+        bytes.extend_from_slice(&self.created_at.to_be_bytes());
+        bytes.extend_from_slice(self.id.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let owner_end = 1 + bytes[0] as usize;
+        let mut created_at = [0u8; 8];
+        created_at.copy_from_slice(&bytes[owner_end + 1..owner_end + 9]);
+        IndexKey {
+            owner: bytes[1..owner_end].to_vec(),
+            status: bytes[owner_end],
+            created_at: u64::from_be_bytes(created_at),
+            id: String::from_utf8(bytes[owner_end + 9..].to_vec()).unwrap(),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: (10 + MAX_OWNER_LENGTH + MAX_ID_LENGTH) as u32,
+        is_fixed_size: false,
+    };
+}
+
+// Ids of one owner's records in one status, oldest first
+pub fn ids_with_status<M: Memory>(index: &StableBTreeMap<IndexKey, (), M>, owner: &[u8], status: u8) -> Vec<String> {
+    index.keys_range(IndexKey::new(owner, status, 0, "")..)
+        .take_while(|key| key.owner == owner && key.status == status)
+        .map(|key| key.id)
+        .collect()
+}
+
+// One page of an owner's ids in one status, newest first, read straight off the index.
+// The cursor is the (created_at, id) of the last entry returned.
+pub fn page_newest_first<M: Memory>(
+    index: &StableBTreeMap<IndexKey, (), M>,
+    owner: &[u8],
+    status: u8,
+    page: &PageRequest,
+    max: u32,
+) -> Result<Page<String>, BackendError> {
+    let end = match &page.cursor {
+        Some(cursor) => {
+            let (created_at, id) = parse_time_cursor(cursor)?;
+            if id.len() > MAX_ID_LENGTH {
+                return Err(BackendError::validation("cursor", "malformed"));
+            }
+            IndexKey::new(owner, status, created_at, &id)
+        }
+        None => IndexKey::new(owner, status + 1, 0, ""),
+    };
+    let keys = index.keys_range(IndexKey::new(owner, status, 0, "")..end).rev();
+    let page = take_page(keys, page_limit(page, max), |key| time_cursor(key.created_at, &key.id));
+    Ok(Page { items: page.items.into_iter().map(|key| key.id).collect(), next_cursor: page.next_cursor })
+}
+
+pub fn count_with_status<M: Memory>(index: &StableBTreeMap<IndexKey, (), M>, owner: &[u8], status: u8) -> u64 {
+    index.keys_range(IndexKey::new(owner, status, 0, "")..)
+        .take_while(|key| key.owner == owner && key.status == status)
+        .count() as u64
+}
+
+// Moves a record's entry after a write; `old` is None for new records
+pub fn reindex<M: Memory>(index: &mut StableBTreeMap<IndexKey, (), M>, old: Option<IndexKey>, new: IndexKey) {
+    if let Some(old) = old {
+        if old != new {
+            index.remove(&old);
+        }
+    }
+    index.insert(new, ());
+}
+
+// Notifications by recipient and read state
+pub const UNREAD: u8 = 0;
+pub const READ: u8 = 1;
+
+pub fn notification_key(notification: &Notification) -> IndexKey {
+    let status = if notification.read { READ } else { UNREAD };
+    IndexKey::new(notification.recipient.as_slice(), status, notification.created_at, &notification.id)
+}
+
+// Invitations by invitee and status
+pub const INVITATION_STATUSES: [InvitationStatus; 4] =
+    [InvitationStatus::Pending, InvitationStatus::Accepted, InvitationStatus::Declined, InvitationStatus::Expired];
+
+pub fn invitation_status(status: &InvitationStatus) -> u8 {
+    match status {
+        InvitationStatus::Pending => 0,
+        InvitationStatus::Accepted => 1,
+        InvitationStatus::Declined => 2,
+        InvitationStatus::Expired => 3,
+    }
+}
+
+pub fn invitation_key(invitation: &FamilyInvitation) -> IndexKey {
+    IndexKey::new(invitation.invitee.as_slice(), invitation_status(&invitation.status), invitation.created_at, &invitation.id)
+}
+
+// Claims by requester, and by family for admins, with their status
+pub const CLAIM_STATUSES: [ClaimStatus; 4] =
+    [ClaimStatus::Pending, ClaimStatus::Approved, ClaimStatus::Rejected, ClaimStatus::Expired];
+
+pub fn claim_status(status: &ClaimStatus) -> u8 {
+    match status {
+        ClaimStatus::Pending => 0,
+        ClaimStatus::Approved => 1,
+        ClaimStatus::Rejected => 2,
+        ClaimStatus::Expired => 3,
+    }
+}
+
+pub fn claim_requester_key(claim: &ClaimRequest) -> IndexKey {
+    IndexKey::new(claim.requester.as_slice(), claim_status(&claim.status), claim.created_at, &claim.id)
+}
+
+pub fn claim_family_key(claim: &ClaimRequest) -> IndexKey {
+    IndexKey::new(claim.family_id.as_bytes(), claim_status(&claim.status), claim.created_at, &claim.id)
+}
+
+// Rewards by recipient and treasury transactions by family; both have a single status
+pub fn reward_recipient_key(record: &RewardRecord) -> IndexKey {
+    IndexKey::new(record.recipient.as_slice(), 0, record.created_at, &record.idempotency_key)
+}
+
+pub fn treasury_transaction_key(tx: &TreasuryTransaction) -> IndexKey {
+    IndexKey::new(tx.family_id.as_bytes(), 0, tx.created_at, &tx.id)
+}
+
+// Media by family, by uploader and status, and open uploads by last activity.
+// The family and activity indexes have a single status.
+pub const UPLOADING: u8 = 0;
+pub const READY: u8 = 1;
+
+pub fn media_status(status: &MediaStatus) -> u8 {
+    match status {
+        MediaStatus::Uploading => UPLOADING,
+        MediaStatus::Ready => READY,
+    }
+}
+
+pub fn family_media_key(asset: &MediaAsset) -> IndexKey {
+    IndexKey::new(asset.family_id.as_bytes(), 0, asset.created_at, &asset.id)
+}
+
+pub fn uploader_media_key(asset: &MediaAsset) -> IndexKey {
+    IndexKey::new(asset.uploaded_by.as_slice(), media_status(&asset.status), asset.created_at, &asset.id)
+}
+
+pub fn open_upload_key(asset: &MediaAsset) -> Option<IndexKey> {
+    (asset.status == MediaStatus::Uploading).then(|| IndexKey::new(&[], 0, asset.updated_at, &asset.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    #[test]
+    fn test_index_key_round_trip() {
+        let key = IndexKey::new(&[1, 2, 3], 2, 1_700_000_000_000_000_000, "invitation_42");
+        assert_eq!(IndexKey::from_bytes(key.to_bytes()), key);
+    }
+
+    #[test]
+    fn test_status_ranges_are_per_owner() {
+        let mut index = StableBTreeMap::new(DefaultMemoryImpl::default());
+        for (owner, status, time, id) in [(&b"alice"[..], UNREAD, 3, "n3"), (b"alice", UNREAD, 1, "n1"),
+            (b"alice", READ, 2, "n2"), (b"alicia", UNREAD, 4, "n4"), (b"bob", UNREAD, 5, "n5")] {
+            reindex(&mut index, None, IndexKey::new(owner, status, time, id));
+        }
+
+        assert_eq!(ids_with_status(&index, b"alice", UNREAD), vec!["n1", "n3"]);
+        assert_eq!(count_with_status(&index, b"alice", READ), 1);
+
+        // Marking n1 read moves its entry between ranges
+        reindex(&mut index, Some(IndexKey::new(b"alice", UNREAD, 1, "n1")), IndexKey::new(b"alice", READ, 1, "n1"));
+        assert_eq!(ids_with_status(&index, b"alice", UNREAD), vec!["n3"]);
+        assert_eq!(ids_with_status(&index, b"alice", READ), vec!["n1", "n2"]);
+        assert_eq!(count_with_status(&index, b"carol", UNREAD), 0);
+    }
+
+    #[test]
+    fn test_pages_come_straight_off_the_index() {
+        let mut index = StableBTreeMap::new(DefaultMemoryImpl::default());
+        for (owner, status, time, id) in [(&b"alice"[..], 0, 1, "r1"), (b"alice", 0, 2, "r2"), (b"alice", 0, 3, "r3"),
+            (b"alice", 1, 4, "other_status"), (b"alicia", 0, 5, "other_owner")] {
+            reindex(&mut index, None, IndexKey::new(owner, status, time, id));
+        }
+        let page = |cursor| PageRequest { cursor, limit: Some(2) };
+
+        let first = page_newest_first(&index, b"alice", 0, &page(None), 100).unwrap();
+        assert_eq!(first.items, vec!["r3", "r2"]);
+        let rest = page_newest_first(&index, b"alice", 0, &page(first.next_cursor), 100).unwrap();
+        assert_eq!(rest.items, vec!["r1"]);
+        assert_eq!(rest.next_cursor, None);
+
+        let too_long = Some(format!("1:{}", "x".repeat(MAX_ID_LENGTH + 1)));
+        assert!(page_newest_first(&index, b"alice", 0, &page(too_long), 100).is_err());
+    }
+}
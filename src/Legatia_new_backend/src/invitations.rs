@@ -1,26 +1,17 @@
 use ic_cdk::api;
 use candid::{Principal};
-use ic_stable_structures::StableBTreeMap;
-use std::cell::RefCell;
 use crate::types::*;
-use crate::MEMORY_MANAGER;
-
-use crate::storage::Memory;
-
-// Thread-local storage for invitations and notifications
-thread_local! {
-    static INVITATIONS: RefCell<StableBTreeMap<String, FamilyInvitation, Memory>> = RefCell::new(
-        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(crate::INVITATIONS_MEMORY_ID)))
-    );
-    
-    static NOTIFICATIONS: RefCell<StableBTreeMap<String, Notification, Memory>> = RefCell::new(
-        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(crate::NOTIFICATIONS_MEMORY_ID)))
-    );
-    
-    static USER_SEARCH_INDEX: RefCell<StableBTreeMap<String, UserSearchResult, Memory>> = RefCell::new(
-        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(crate::USER_SEARCH_MEMORY_ID)))
-    );
-}
+use crate::error::BackendError;
+use crate::pagination::{newest_first, within, MAX_PAGE_SIZE};
+use crate::indexes::{
+    count_with_status, ids_with_status, invitation_key, invitation_status, notification_key, reindex,
+    INVITATION_STATUSES, READ, UNREAD
+};
+use crate::storage::{
+    INVITATIONS, NOTIFICATIONS, USER_SEARCH_INDEX, INVITATIONS_BY_INVITEE, NOTIFICATIONS_BY_RECIPIENT
+};
+use crate::family::roles::{require_permission, Permission};
+use crate::rewards::{linked_member_subject, queue_reward};
 
 // Helper function to generate unique ID
 fn generate_id(prefix: &str) -> String {
@@ -28,7 +19,7 @@ fn generate_id(prefix: &str) -> String {
 }
 
 // Helper function to create notification
-fn create_notification(
+pub(crate) fn create_notification(
     recipient: Principal,
     title: String,
     message: String,
@@ -49,13 +40,74 @@ fn create_notification(
         metadata,
     };
     
-    NOTIFICATIONS.with(|n| {
-        n.borrow_mut().insert(notification_id.clone(), notification);
-    });
+    save_notification(notification);
     
     notification_id
 }
 
+// Notification and invitation writes go through these so the secondary indexes stay in step
+fn save_notification(notification: Notification) {
+    let key = notification_key(&notification);
+    let previous = NOTIFICATIONS.with(|n| n.borrow_mut().insert(notification.id.clone(), notification));
+    NOTIFICATIONS_BY_RECIPIENT.with(|index| {
+        reindex(&mut index.borrow_mut(), previous.as_ref().map(notification_key), key);
+    });
+}
+
+fn save_invitation(invitation: FamilyInvitation) {
+    let key = invitation_key(&invitation);
+    let previous = INVITATIONS.with(|i| i.borrow_mut().insert(invitation.id.clone(), invitation));
+    INVITATIONS_BY_INVITEE.with(|index| {
+        reindex(&mut index.borrow_mut(), previous.as_ref().map(invitation_key), key);
+    });
+}
+
+fn notifications_with_status(recipient: Principal, statuses: &[u8]) -> Vec<Notification> {
+    let ids: Vec<String> = NOTIFICATIONS_BY_RECIPIENT.with(|index| {
+        let index = index.borrow();
+        statuses.iter().flat_map(|status| ids_with_status(&index, recipient.as_slice(), *status)).collect()
+    });
+    NOTIFICATIONS.with(|n| {
+        let n = n.borrow();
+        ids.iter().filter_map(|id| n.get(id)).collect()
+    })
+}
+
+fn invitations_with_status(invitee: Principal, statuses: &[u8]) -> Vec<FamilyInvitation> {
+    let ids: Vec<String> = INVITATIONS_BY_INVITEE.with(|index| {
+        let index = index.borrow();
+        statuses.iter().flat_map(|status| ids_with_status(&index, invitee.as_slice(), *status)).collect()
+    });
+    INVITATIONS.with(|i| {
+        let i = i.borrow();
+        ids.iter().filter_map(|id| i.get(id)).collect()
+    })
+}
+
+// Indexes added after launch start empty; fill them from the existing records once
+pub fn rebuild_indexes() -> u64 {
+    let mut indexed = 0;
+    if NOTIFICATIONS_BY_RECIPIENT.with(|index| index.borrow().is_empty()) {
+        NOTIFICATIONS.with(|n| NOTIFICATIONS_BY_RECIPIENT.with(|index| {
+            let mut index = index.borrow_mut();
+            for (_, notification) in n.borrow().iter() {
+                index.insert(notification_key(&notification), ());
+                indexed += 1;
+            }
+        }));
+    }
+    if INVITATIONS_BY_INVITEE.with(|index| index.borrow().is_empty()) {
+        INVITATIONS.with(|i| INVITATIONS_BY_INVITEE.with(|index| {
+            let mut index = index.borrow_mut();
+            for (_, invitation) in i.borrow().iter() {
+                index.insert(invitation_key(&invitation), ());
+                indexed += 1;
+            }
+        }));
+    }
+    indexed
+}
+
 // User Search Functions
 pub fn update_user_search_index(profile: &UserProfile, principal: Principal) {
     let search_result = UserSearchResult {
@@ -79,11 +131,9 @@ pub fn update_user_search_index(profile: &UserProfile, principal: Principal) {
     });
 }
 
-pub fn search_users(query: String) -> Result<Vec<UserSearchResult>, String> {
+pub fn search_users(query: String) -> Result<Vec<UserSearchResult>, BackendError> {
     // Validate search query
-    if let Err(_validation_error) = crate::validation::validate_search_query(&query) {
-        return Err("Invalid search query format".to_string());
-    }
+    crate::validation::validate_search_query(&query)?;
     
     let query_lower = query.to_lowercase();
     let mut results = Vec::new();
@@ -124,14 +174,12 @@ pub fn search_users(query: String) -> Result<Vec<UserSearchResult>, String> {
 }
 
 // Family Invitation Functions
-pub fn send_family_invitation(request: SendInvitationRequest) -> Result<String, String> {
+pub fn send_family_invitation(request: SendInvitationRequest) -> Result<String, BackendError> {
     let caller = api::caller();
     
-    // Verify the caller is admin of the family
-    let family = crate::family::get_family_internal(&request.family_id)?;
-    if family.admin != caller {
-        return Err("Only family admin can send invitations".to_string());
-    }
+    // Verify the caller may invite on behalf of the family
+    let family = crate::family::get_family_roster(&request.family_id)?;
+    require_permission(&family, caller, Permission::SendInvitations)?;
     
     // Find the target user
     let target_user = USER_SEARCH_INDEX.with(|index| {
@@ -140,27 +188,21 @@ pub fn send_family_invitation(request: SendInvitationRequest) -> Result<String,
     
     let target_user = match target_user {
         Some(user) => user,
-        None => return Err("User not found".to_string()),
+        None => return Err(BackendError::not_found("user", &request.user_id)),
     };
     
     // Check if user is already a family member
     if family.members.iter().any(|member| {
         member.profile_principal == Some(target_user.user_principal)
     }) {
-        return Err("User is already a member of this family".to_string());
+        return Err(BackendError::conflict("already_member"));
     }
     
     // Check for existing pending invitation
-    let existing_invitation = INVITATIONS.with(|invitations| {
-        invitations.borrow().iter().find(|(_, inv)| {
-            inv.family_id == request.family_id && 
-            inv.invitee == target_user.user_principal && 
-            matches!(inv.status, InvitationStatus::Pending)
-        }).map(|(_, inv)| inv)
-    });
+    let pending = invitations_with_status(target_user.user_principal, &[invitation_status(&InvitationStatus::Pending)]);
     
-    if existing_invitation.is_some() {
-        return Err("Pending invitation already exists for this user".to_string());
+    if pending.iter().any(|inv| inv.family_id == request.family_id) {
+        return Err(BackendError::conflict("invitation_pending"));
     }
     
     // Get inviter profile for name
@@ -183,9 +225,7 @@ pub fn send_family_invitation(request: SendInvitationRequest) -> Result<String,
     };
     
     // Store invitation
-    INVITATIONS.with(|invitations| {
-        invitations.borrow_mut().insert(invitation_id.clone(), invitation);
-    });
+    save_invitation(invitation);
     
     // Create notification for invitee
     let notification_title = format!("Family Invitation from {}", family.name);
@@ -209,22 +249,22 @@ pub fn send_family_invitation(request: SendInvitationRequest) -> Result<String,
     Ok(invitation_id)
 }
 
-pub fn process_family_invitation(request: ProcessInvitationRequest) -> Result<String, String> {
+pub fn process_family_invitation(request: ProcessInvitationRequest) -> Result<String, BackendError> {
     let caller = api::caller();
     
     // Get the invitation
     let mut invitation = INVITATIONS.with(|invitations| {
         invitations.borrow().get(&request.invitation_id)
-    }).ok_or("Invitation not found".to_string())?;
+    }).ok_or_else(|| BackendError::not_found("invitation", &request.invitation_id))?;
     
     // Verify caller is the invitee
     if invitation.invitee != caller {
-        return Err("You can only respond to your own invitations".to_string());
+        return Err(BackendError::forbidden());
     }
     
     // Check if invitation is still pending
     if !matches!(invitation.status, InvitationStatus::Pending) {
-        return Err("Invitation has already been processed".to_string());
+        return Err(BackendError::conflict("already_processed"));
     }
     
     // Process the response
@@ -249,7 +289,7 @@ pub fn process_family_invitation(request: ProcessInvitationRequest) -> Result<St
         };
         
         // Add member with linked profile
-        let mut family = crate::family::get_family_internal(&invitation.family_id)?;
+        let mut family = crate::family::get_family_roster(&invitation.family_id)?;
         let member_id = crate::family::generate_member_id();
         
         let family_member = FamilyMember {
@@ -266,16 +306,23 @@ pub fn process_family_invitation(request: ProcessInvitationRequest) -> Result<St
             events: Vec::new(),
             created_at: api::time(),
             created_by: invitation.inviter, // Credit the inviter
+            media_ids: None,
         };
         
-        family.members.push(family_member);
         family.updated_at = api::time();
         
         // Update family
-        crate::family::update_family_internal(family)?;
+        crate::family::store::save_member(&family.id, &family_member)?;
+        crate::family::store::save_family_header(&family);
         
         // Update user's family list
         crate::profile::add_user_to_family(caller, invitation.family_id.clone())?;
+
+        queue_reward(
+            invitation.inviter,
+            RewardActivity::MemberAdded,
+            &linked_member_subject(&invitation.family_id, caller),
+        );
         
         // Notify family admin of acceptance
         create_notification(
@@ -306,110 +353,136 @@ pub fn process_family_invitation(request: ProcessInvitationRequest) -> Result<St
     }
     
     // Update invitation status
-    INVITATIONS.with(|invitations| {
-        invitations.borrow_mut().insert(request.invitation_id.clone(), invitation);
-    });
+    save_invitation(invitation);
     
     let status = if request.accept { "accepted" } else { "declined" };
     Ok(format!("Invitation {}", status))
 }
 
-pub fn get_my_invitations() -> Result<Vec<FamilyInvitation>, String> {
+pub fn get_my_invitations(filter: InvitationFilter, page: PageRequest) -> Result<Page<FamilyInvitation>, BackendError> {
     let caller = api::caller();
     
-    let invitations = INVITATIONS.with(|invitations| {
-        invitations.borrow()
-            .iter()
-            .filter(|(_, inv)| inv.invitee == caller)
-            .map(|(_, inv)| inv)
-            .collect::<Vec<_>>()
-    });
+    let statuses: Vec<u8> = match &filter.status {
+        Some(status) => vec![invitation_status(status)],
+        None => INVITATION_STATUSES.iter().map(invitation_status).collect(),
+    };
+    let invitations = invitations_with_status(caller, &statuses).into_iter()
+        .filter(|inv| invitation_matches(inv, &filter))
+        .collect();
     
-    Ok(invitations)
+    newest_first(invitations, |inv| (inv.created_at, inv.id.clone()), &page, MAX_PAGE_SIZE)
 }
 
-pub fn get_sent_invitations() -> Result<Vec<FamilyInvitation>, String> {
+pub fn get_sent_invitations(filter: InvitationFilter, page: PageRequest) -> Result<Page<FamilyInvitation>, BackendError> {
     let caller = api::caller();
     
     let invitations = INVITATIONS.with(|invitations| {
         invitations.borrow()
             .iter()
-            .filter(|(_, inv)| inv.inviter == caller)
+            .filter(|(_, inv)| inv.inviter == caller && invitation_matches(inv, &filter))
             .map(|(_, inv)| inv)
             .collect::<Vec<_>>()
     });
     
-    Ok(invitations)
+    newest_first(invitations, |inv| (inv.created_at, inv.id.clone()), &page, MAX_PAGE_SIZE)
+}
+
+fn invitation_matches(invitation: &FamilyInvitation, filter: &InvitationFilter) -> bool {
+    filter.status.as_ref().is_none_or(|status| &invitation.status == status)
+        && filter.family_id.as_ref().is_none_or(|id| &invitation.family_id == id)
+        && within(invitation.created_at, filter.created_after, filter.created_before)
 }
 
 // Notification Functions
-pub fn get_my_notifications() -> Result<Vec<Notification>, String> {
+pub fn get_my_notifications(filter: NotificationFilter, page: PageRequest) -> Result<Page<Notification>, BackendError> {
     let caller = api::caller();
     
-    let notifications = NOTIFICATIONS.with(|notifications| {
-        notifications.borrow()
-            .iter()
-            .filter(|(_, notif)| notif.recipient == caller)
-            .map(|(_, notif)| notif)
-            .collect::<Vec<_>>()
-    });
+    let statuses: &[u8] = if filter.unread_only == Some(true) { &[UNREAD] } else { &[UNREAD, READ] };
+    let notifications = notifications_with_status(caller, statuses).into_iter()
+        .filter(|notif| notification_matches(notif, &filter))
+        .collect();
     
-    Ok(notifications)
+    newest_first(notifications, |notif| (notif.created_at, notif.id.clone()), &page, MAX_PAGE_SIZE)
+}
+
+fn notification_matches(notification: &Notification, filter: &NotificationFilter) -> bool {
+    filter.notification_type.as_ref().is_none_or(|t| &notification.notification_type == t)
+        && within(notification.created_at, filter.created_after, filter.created_before)
 }
 
-pub fn get_unread_notification_count() -> Result<u64, String> {
+pub fn get_unread_notification_count() -> Result<u64, BackendError> {
     let caller = api::caller();
     
-    let count = NOTIFICATIONS.with(|notifications| {
-        notifications.borrow()
-            .iter()
-            .filter(|(_, notif)| notif.recipient == caller && !notif.read)
-            .count() as u64
-    });
-    
-    Ok(count)
+    Ok(NOTIFICATIONS_BY_RECIPIENT.with(|index| {
+        count_with_status(&index.borrow(), caller.as_slice(), UNREAD)
+    }))
 }
 
-pub fn mark_notification_read(notification_id: String) -> Result<String, String> {
+pub fn mark_notification_read(notification_id: String) -> Result<String, BackendError> {
     let caller = api::caller();
     
     let mut notification = NOTIFICATIONS.with(|notifications| {
         notifications.borrow().get(&notification_id)
-    }).ok_or("Notification not found".to_string())?;
+    }).ok_or_else(|| BackendError::not_found("notification", &notification_id))?;
     
     if notification.recipient != caller {
-        return Err("You can only mark your own notifications as read".to_string());
+        return Err(BackendError::forbidden());
     }
     
     notification.read = true;
-    
-    NOTIFICATIONS.with(|notifications| {
-        notifications.borrow_mut().insert(notification_id, notification);
-    });
+    save_notification(notification);
     
     Ok("Notification marked as read".to_string())
 }
 
-pub fn mark_all_notifications_read() -> Result<String, String> {
+pub fn mark_all_notifications_read() -> Result<String, BackendError> {
     let caller = api::caller();
-    let mut count = 0;
+    let unread = notifications_with_status(caller, &[UNREAD]);
+    let count = unread.len();
     
-    NOTIFICATIONS.with(|notifications| {
-        let mut borrowed = notifications.borrow_mut();
-        let mut updates = Vec::new();
-        
-        for (id, mut notif) in borrowed.iter() {
-            if notif.recipient == caller && !notif.read {
-                notif.read = true;
-                updates.push((id, notif));
-                count += 1;
-            }
-        }
-        
-        for (id, notif) in updates {
-            borrowed.insert(id, notif);
-        }
-    });
+    for mut notif in unread {
+        notif.read = true;
+        save_notification(notif);
+    }
     
     Ok(format!("Marked {} notifications as read", count))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::pending_migrations;
+
+    fn notification(id: u64, recipient: Principal) -> Notification {
+        Notification {
+            id: format!("notification_{}", id),
+            recipient,
+            title: "Title".to_string(),
+            message: "Message".to_string(),
+            notification_type: NotificationType::SystemAlert,
+            created_at: id,
+            read: false,
+            action_url: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_writes_after_migrations_keep_records_intact() {
+        let recipient = Principal::from_slice(&[1; 29]);
+        for id in 0..50 {
+            NOTIFICATIONS.with(|n| n.borrow_mut().insert(format!("notification_{}", id), notification(id, recipient)));
+        }
+
+        for migration in pending_migrations(0).unwrap() {
+            (migration.run)();
+        }
+        for id in 50..100 {
+            save_notification(notification(id, recipient));
+        }
+
+        let stored: Vec<Notification> = NOTIFICATIONS.with(|n| n.borrow().iter().map(|(_, n)| n).collect());
+        assert_eq!(stored.len(), 100);
+        assert!(stored.iter().all(|n| n.recipient == recipient && n.title == "Title"));
+        assert_eq!(notifications_with_status(recipient, &[UNREAD]).len(), 100);
+    }
+}
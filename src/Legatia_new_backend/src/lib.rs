@@ -1,39 +1,68 @@
+#![allow(non_snake_case)]
+
 use candid::Principal;
 use ic_cdk::api;
 use ic_cdk_macros::*;
-use ic_stable_structures::memory_manager::MemoryId;
-
-// Memory IDs for stable storage
-pub const INVITATIONS_MEMORY_ID: MemoryId = MemoryId::new(4);
-pub const NOTIFICATIONS_MEMORY_ID: MemoryId = MemoryId::new(5);
-pub const USER_SEARCH_MEMORY_ID: MemoryId = MemoryId::new(6);
 
 // Re-export memory manager
 pub use storage::MEMORY_MANAGER;
 
 // Module declarations
 mod types;
+mod error;
 mod storage;
 mod profile;
 mod family;
 mod ghost;
 mod invitations;
 mod validation;
+mod gedcom;
+mod rewards;
+mod http;
+mod pagination;
+mod indexes;
+mod schema;
 
 // Re-export types for Candid interface
 pub use types::*;
+pub use error::BackendError;
 
 // Re-export functions for Candid interface
 pub use profile::{create_profile, update_profile, get_profile, create_profile_with_ghost_check, update_profile_with_ghost_check};
 pub use family::{
-    create_family, get_user_families, get_family, add_family_member, 
+    create_family, get_user_families, get_family, get_family_members, get_family_events, add_family_member, 
     remove_family_member, add_member_event, get_member_events_chronological,
     toggle_family_visibility, update_family_member, update_member_event
 };
+pub use family::relationships::{
+    add_family_relationship, remove_family_relationship, get_family_relationships,
+    get_member_relationships
+};
+pub use family::kinship::{get_ancestors, get_descendants, get_relationship_path};
+pub use family::roles::{grant_family_role, revoke_family_role, get_family_roles, get_my_family_role};
+pub use family::succession::{
+    offer_family_ownership, accept_family_ownership, cancel_ownership_offer, set_family_successor,
+    set_dormancy_period, vote_for_successor, confirm_family_ownership, get_family_succession
+};
+pub use family::governance::{
+    create_family_proposal, vote_on_family_proposal, get_family_proposals,
+    get_family_governance_config
+};
+pub use family::treasury::{
+    get_family_treasury, get_family_treasury_transactions, deposit_to_family_treasury, spend_from_family_treasury,
+    retry_treasury_transaction
+};
+pub use family::media::{
+    begin_media_upload, upload_media_chunk, finish_media_upload, get_media, get_family_media,
+    get_media_chunk, attach_media, detach_media, delete_media
+};
+pub use http::{create_media_access_token, http_request, http_request_streaming_callback};
+pub use gedcom::{import_gedcom, export_gedcom};
 pub use ghost::{
     find_matching_ghost_profiles, submit_ghost_profile_claim, get_pending_claims_for_admin,
     process_ghost_profile_claim, get_my_claim_requests
 };
+pub use rewards::{set_reward_config, get_reward_config, get_my_rewards, retry_failed_rewards};
 // Import invitation functions for internal use
 use invitations::{
     search_users as search_users_impl, send_family_invitation as send_family_invitation_impl, 
@@ -50,9 +79,24 @@ ic_cdk::export_candid!();
 
 #[init]
 fn init() {
+    schema::mark_current();
+    rewards::start_reward_timer();
+    family::succession::start_succession_timer();
+    family::media::start_media_cleanup_timer();
     ic_cdk::println!("Family tree backend initialized");
 }
 
+#[post_upgrade]
+fn post_upgrade() {
+    // Traps, rolling the upgrade back, if stored data can't be migrated
+    schema::run_migrations();
+
+    // Timers do not survive upgrades
+    rewards::start_reward_timer();
+    family::succession::start_succession_timer();
+    family::media::start_media_cleanup_timer();
+}
+
 #[query]
 fn whoami() -> Principal {
     api::caller()
@@ -64,56 +108,56 @@ fn test_simple() -> String {
 }
 
 #[query]
-fn search_users(query: String) -> Result<Vec<UserSearchResult>, String> {
+fn search_users(query: String) -> Result<Vec<UserSearchResult>, BackendError> {
     search_users_impl(query)
 }
 
 #[update]
-fn send_family_invitation(request: SendInvitationRequest) -> Result<String, String> {
+fn send_family_invitation(request: SendInvitationRequest) -> Result<String, BackendError> {
     send_family_invitation_impl(request)
 }
 
 #[update]
-fn process_family_invitation(request: ProcessInvitationRequest) -> Result<String, String> {
+fn process_family_invitation(request: ProcessInvitationRequest) -> Result<String, BackendError> {
     process_family_invitation_impl(request)
 }
 
 #[query]
-fn get_my_invitations() -> Result<Vec<FamilyInvitation>, String> {
-    get_my_invitations_impl()
+fn get_my_invitations(filter: InvitationFilter, page: PageRequest) -> Result<Page<FamilyInvitation>, BackendError> {
+    get_my_invitations_impl(filter, page)
 }
 
 #[query]
-fn get_sent_invitations() -> Result<Vec<FamilyInvitation>, String> {
-    get_sent_invitations_impl()
+fn get_sent_invitations(filter: InvitationFilter, page: PageRequest) -> Result<Page<FamilyInvitation>, BackendError> {
+    get_sent_invitations_impl(filter, page)
 }
 
 #[query]
-fn get_my_notifications() -> Result<Vec<Notification>, String> {
-    get_my_notifications_impl()
+fn get_my_notifications(filter: NotificationFilter, page: PageRequest) -> Result<Page<Notification>, BackendError> {
+    get_my_notifications_impl(filter, page)
 }
 
 #[query]
-fn get_unread_notification_count() -> Result<u64, String> {
+fn get_unread_notification_count() -> Result<u64, BackendError> {
     get_unread_notification_count_impl()
 }
 
 #[update]
-fn mark_notification_read(notification_id: String) -> Result<String, String> {
+fn mark_notification_read(notification_id: String) -> Result<String, BackendError> {
     mark_notification_read_impl(notification_id)
 }
 
 #[update]
-fn mark_all_notifications_read() -> Result<String, String> {
+fn mark_all_notifications_read() -> Result<String, BackendError> {
     mark_all_notifications_read_impl()
 }
 
 // Test function to check if basic invitation functions work
 #[query]
-fn test_search_users(query: String) -> Result<Vec<String>, String> {
+fn test_search_users(query: String) -> Result<Vec<String>, BackendError> {
     // Simple test version
     if query.len() < 2 {
-        return Err("Query too short".to_string());
+        return Err(BackendError::validation("query", "too_short"));
     }
     Ok(vec![format!("Test user for query: {}", query)])
 }
@@ -0,0 +1,129 @@
+use crate::types::{Page, PageRequest};
+use crate::error::BackendError;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+// Whole families can be large, so they come in smaller pages than other records
+pub const MAX_FAMILY_PAGE_SIZE: u32 = 10;
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+// Clamps the requested page size to 1..=max
+pub fn page_limit(page: &PageRequest, max: u32) -> usize {
+    page.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, max) as usize
+}
+
+pub fn within(created_at: u64, after: Option<u64>, before: Option<u64>) -> bool {
+    after.is_none_or(|after| created_at > after) && before.is_none_or(|before| created_at < before)
+}
+
+// Pages through records newest first. The cursor is the (created_at, id) of the last
+// record returned, so records added or removed between calls don't shift later pages.
+pub fn newest_first<T>(
+    mut items: Vec<T>,
+    key: impl Fn(&T) -> (u64, String),
+    page: &PageRequest,
+    max: u32,
+) -> Result<Page<T>, BackendError> {
+    items.sort_by_key(|item| std::cmp::Reverse(key(item)));
+    let start = match &page.cursor {
+        Some(cursor) => {
+            let cursor = parse_time_cursor(cursor)?;
+            items.iter().position(|item| key(item) < cursor).unwrap_or(items.len())
+        }
+        None => 0,
+    };
+    Ok(take_page(items.into_iter().skip(start), page_limit(page, max), |item| {
+        let (created_at, id) = key(item);
+        time_cursor(created_at, &id)
+    }))
+}
+
+// Pages through records in their stored order. The cursor is the id of the last
+// record returned and must still exist.
+pub fn after_id<T>(
+    items: Vec<T>,
+    id: impl Fn(&T) -> &str,
+    page: &PageRequest,
+    max: u32,
+) -> Result<Page<T>, BackendError> {
+    let start = match &page.cursor {
+        Some(cursor) => items.iter()
+            .position(|item| id(item) == cursor)
+            .map(|index| index + 1)
+            .ok_or_else(|| BackendError::validation("cursor", "unknown"))?,
+        None => 0,
+    };
+    Ok(take_page(items.into_iter().skip(start), page_limit(page, max), |item| id(item).to_string()))
+}
+
+pub fn take_page<T>(items: impl Iterator<Item = T>, limit: usize, cursor_of: impl Fn(&T) -> String) -> Page<T> {
+    let mut items: Vec<T> = items.take(limit + 1).collect();
+    let next_cursor = if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(cursor_of)
+    } else {
+        None
+    };
+    Page { items, next_cursor }
+}
+
+pub fn time_cursor(created_at: u64, id: &str) -> String {
+    format!("{}:{}", created_at, id)
+}
+
+pub fn parse_time_cursor(cursor: &str) -> Result<(u64, String), BackendError> {
+    cursor.split_once(':')
+        .and_then(|(created_at, id)| Some((created_at.parse().ok()?, id.to_string())))
+        .ok_or_else(|| BackendError::validation("cursor", "malformed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(cursor: Option<String>, limit: u32) -> PageRequest {
+        PageRequest { cursor, limit: Some(limit) }
+    }
+
+    #[test]
+    fn test_newest_first_pages_are_stable() {
+        let records: Vec<(u64, String)> = (1..=5).map(|t| (t * 10, format!("r{}", t))).collect();
+        let key = |r: &(u64, String)| r.clone();
+
+        let first = newest_first(records.clone(), key, &page(None, 2), MAX_PAGE_SIZE).unwrap();
+        assert_eq!(first.items.iter().map(|r| r.0).collect::<Vec<_>>(), vec![50, 40]);
+
+        // A record added after the first page doesn't reappear or shift the next one
+        let mut grown = records.clone();
+        grown.push((60, "r6".to_string()));
+        let second = newest_first(grown, key, &page(first.next_cursor, 2), MAX_PAGE_SIZE).unwrap();
+        assert_eq!(second.items.iter().map(|r| r.0).collect::<Vec<_>>(), vec![30, 20]);
+
+        let last = newest_first(records, key, &page(second.next_cursor, 2), MAX_PAGE_SIZE).unwrap();
+        assert_eq!(last.items.iter().map(|r| r.0).collect::<Vec<_>>(), vec![10]);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn test_after_id_and_limits() {
+        let ids: Vec<String> = (0..5).map(|i| format!("m{}", i)).collect();
+
+        let first = after_id(ids.clone(), |id| id.as_str(), &page(None, 3), MAX_PAGE_SIZE).unwrap();
+        assert_eq!(first.items, vec!["m0", "m1", "m2"]);
+        let rest = after_id(ids.clone(), |id| id.as_str(), &page(first.next_cursor, 3), MAX_PAGE_SIZE).unwrap();
+        assert_eq!(rest.items, vec!["m3", "m4"]);
+        assert_eq!(rest.next_cursor, None);
+
+        assert!(after_id(ids.clone(), |id| id.as_str(), &page(Some("gone".to_string()), 3), MAX_PAGE_SIZE).is_err());
+        assert_eq!(page_limit(&page(None, 0), MAX_PAGE_SIZE), 1);
+        assert_eq!(page_limit(&page(None, 500), MAX_FAMILY_PAGE_SIZE), 10);
+        assert_eq!(page_limit(&PageRequest::default(), MAX_PAGE_SIZE), 20);
+    }
+
+    #[test]
+    fn test_within_is_exclusive() {
+        assert!(within(5, Some(4), Some(6)));
+        assert!(!within(5, Some(5), None));
+        assert!(!within(5, None, Some(5)));
+        assert!(within(5, None, None));
+    }
+}
@@ -2,31 +2,25 @@ use candid::Principal;
 use ic_cdk::api;
 use ic_cdk_macros::*;
 
-use crate::types::{UserProfile, CreateProfileRequest, UpdateProfileRequest, GhostProfileMatch, DEV_MODE};
+use crate::types::{UserProfile, CreateProfileRequest, UpdateProfileRequest, GhostProfileMatch, RewardActivity, DEV_MODE};
 use crate::storage::PROFILES;
+use crate::error::BackendError;
 use crate::ghost::find_matching_ghost_profiles;
+use crate::rewards::queue_reward;
 
 #[update]
-pub fn create_profile(request: CreateProfileRequest) -> Result<UserProfile, String> {
+pub fn create_profile(request: CreateProfileRequest) -> Result<UserProfile, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     // Validate input fields
-    if let Err(_) = crate::validation::validate_name(&request.full_name, "full_name") {
-        return Err("Invalid full name format".to_string());
-    }
-    if let Err(_) = crate::validation::validate_name(&request.surname_at_birth, "surname_at_birth") {
-        return Err("Invalid surname format".to_string());
-    }
-    if let Err(_) = crate::validation::validate_name(&request.birth_city, "birth_city") {
-        return Err("Invalid birth city format".to_string());
-    }
-    if let Err(_) = crate::validation::validate_name(&request.birth_country, "birth_country") {
-        return Err("Invalid birth country format".to_string());
-    }
+    crate::validation::validate_name(&request.full_name, "full_name")?;
+    crate::validation::validate_name(&request.surname_at_birth, "surname_at_birth")?;
+    crate::validation::validate_name(&request.birth_city, "birth_city")?;
+    crate::validation::validate_name(&request.birth_country, "birth_country")?;
 
     let current_time = api::time();
     
@@ -48,23 +42,27 @@ pub fn create_profile(request: CreateProfileRequest) -> Result<UserProfile, Stri
     PROFILES.with(|profiles| {
         let mut profiles = profiles.borrow_mut();
         if profiles.contains_key(&caller) {
-            return Err("Profile already exists".to_string());
+            return Err(BackendError::conflict("profile_exists"));
         }
         profiles.insert(caller, profile.clone());
         
         // Update search index
         crate::invitations::update_user_search_index(&profile, caller);
         
-        Ok(profile)
-    })
+        Ok(())
+    })?;
+
+    queue_reward(caller, RewardActivity::ProfileCreated, &caller.to_text());
+
+    Ok(profile)
 }
 
 #[update]
-pub fn update_profile(request: UpdateProfileRequest) -> Result<UserProfile, String> {
+pub fn update_profile(request: UpdateProfileRequest) -> Result<UserProfile, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     let current_time = api::time();
@@ -99,35 +97,35 @@ pub fn update_profile(request: UpdateProfileRequest) -> Result<UserProfile, Stri
                 
                 Ok(profile)
             }
-            None => Err("Profile not found".to_string()),
+            None => Err(BackendError::not_found("profile", caller.to_text())),
         }
     })
 }
 
 #[query]
-pub fn get_profile() -> Result<UserProfile, String> {
+pub fn get_profile() -> Result<UserProfile, BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     PROFILES.with(|profiles| {
         let profiles = profiles.borrow();
         match profiles.get(&caller) {
             Some(profile) => Ok(profile),
-            None => Err("Profile not found".to_string()),
+            None => Err(BackendError::not_found("profile", caller.to_text())),
         }
     })
 }
 
 // Create profile and return both profile and potential ghost matches
 #[update]
-pub fn create_profile_with_ghost_check(request: CreateProfileRequest) -> Result<(UserProfile, Vec<GhostProfileMatch>), String> {
+pub fn create_profile_with_ghost_check(request: CreateProfileRequest) -> Result<(UserProfile, Vec<GhostProfileMatch>), BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     let current_time = api::time();
@@ -151,12 +149,14 @@ pub fn create_profile_with_ghost_check(request: CreateProfileRequest) -> Result<
     PROFILES.with(|profiles| {
         let mut profiles = profiles.borrow_mut();
         if profiles.contains_key(&caller) {
-            return Err("Profile already exists".to_string());
+            return Err(BackendError::conflict("profile_exists"));
         }
         profiles.insert(caller, profile.clone());
         Ok(())
     })?;
 
+    queue_reward(caller, RewardActivity::ProfileCreated, &caller.to_text());
+
     // Find matching ghost profiles after creating the profile
     let ghost_matches = find_matching_ghost_profiles().unwrap_or_default();
 
@@ -165,11 +165,11 @@ pub fn create_profile_with_ghost_check(request: CreateProfileRequest) -> Result<
 
 // Update profile and return potential ghost matches
 #[update] 
-pub fn update_profile_with_ghost_check(request: UpdateProfileRequest) -> Result<(UserProfile, Vec<GhostProfileMatch>), String> {
+pub fn update_profile_with_ghost_check(request: UpdateProfileRequest) -> Result<(UserProfile, Vec<GhostProfileMatch>), BackendError> {
     let caller = api::caller();
     
     if !DEV_MODE && caller == Principal::anonymous() {
-        return Err("Authentication required".to_string());
+        return Err(BackendError::Unauthenticated);
     }
 
     let current_time = api::time();
@@ -200,7 +200,7 @@ pub fn update_profile_with_ghost_check(request: UpdateProfileRequest) -> Result<
                 profiles.insert(caller, profile.clone());
                 Ok(profile)
             }
-            None => Err("Profile not found".to_string()),
+            None => Err(BackendError::not_found("profile", caller.to_text())),
         }
     })?;
 
@@ -223,15 +223,15 @@ fn generate_unique_user_id(full_name: &str, surname_at_birth: &str) -> String {
 }
 
 // Internal helper functions for other modules
-pub fn get_profile_internal(principal: Principal) -> Result<UserProfile, String> {
+pub fn get_profile_internal(principal: Principal) -> Result<UserProfile, BackendError> {
     PROFILES.with(|profiles| {
         profiles.borrow()
             .get(&principal)
-            .ok_or("Profile not found".to_string())
+            .ok_or_else(|| BackendError::not_found("profile", principal.to_text()))
     })
 }
 
-pub fn add_user_to_family(principal: Principal, family_id: String) -> Result<(), String> {
+pub fn add_user_to_family(principal: Principal, family_id: String) -> Result<(), BackendError> {
     use crate::storage::USER_FAMILIES;
     use crate::types::UserFamilyList;
     
@@ -0,0 +1,423 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api;
+use ic_cdk_macros::*;
+use std::time::Duration;
+
+use crate::types::{LedgerAccount, Page, PageRequest, RewardActivity, RewardConfig, RewardRecord, RewardStatus, DEV_MODE};
+use crate::storage::{REWARDS, REWARDS_BY_RECIPIENT, REWARD_CONFIG};
+use crate::indexes::{page_newest_first, reward_recipient_key};
+use crate::pagination::MAX_PAGE_SIZE;
+use crate::error::BackendError;
+
+// A reward that keeps failing is parked as Failed after this many attempts
+const MAX_REWARD_ATTEMPTS: u32 = 5;
+// How often pending rewards are retried
+const REWARD_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+// Rewards paid per processing run, to stay well within instruction limits
+const REWARD_BATCH_SIZE: usize = 20;
+// Event rewards paid per user per day; further events are recorded but not rewarded
+pub const MAX_EVENT_REWARDS_PER_DAY: u32 = 20;
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// Mirror of the ledger's TransferError type
+#[derive(CandidType, Deserialize, Debug)]
+pub enum LedgerError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u64 },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+    InvalidAmount,
+    MemoTooLarge,
+    SupplyExceeded,
+    Overflow,
+    Unauthorized,
+}
+
+#[update]
+pub fn set_reward_config(config: RewardConfig) -> Result<RewardConfig, BackendError> {
+    if !api::is_controller(&api::caller()) {
+        return Err(BackendError::forbidden());
+    }
+
+    REWARD_CONFIG.with(|cell| cell.borrow_mut().set(config.clone()))
+        .map_err(|_| BackendError::validation("config", "too_large"))?;
+
+    Ok(config)
+}
+
+#[query]
+pub fn get_reward_config() -> RewardConfig {
+    reward_config()
+}
+
+// The caller's rewards, newest first
+#[query]
+pub fn get_my_rewards(page: PageRequest) -> Result<Page<RewardRecord>, BackendError> {
+    let caller = api::caller();
+
+    if !DEV_MODE && caller == Principal::anonymous() {
+        return Err(BackendError::Unauthenticated);
+    }
+
+    rewards_of(caller, &page)
+}
+
+// Puts Failed rewards back in the queue, e.g. after the ledger was misconfigured
+#[update]
+pub fn retry_failed_rewards() -> Result<u32, BackendError> {
+    if !api::is_controller(&api::caller()) {
+        return Err(BackendError::forbidden());
+    }
+
+    let now = api::time();
+    let failed: Vec<RewardRecord> = REWARDS.with(|rewards| {
+        rewards.borrow()
+            .iter()
+            .map(|(_, record)| record)
+            .filter(|record| record.status == RewardStatus::Failed)
+            .collect()
+    });
+
+    REWARDS.with(|rewards| {
+        let mut rewards = rewards.borrow_mut();
+        for mut record in failed.iter().cloned() {
+            record.status = RewardStatus::Pending;
+            record.attempts = 0;
+            record.updated_at = now;
+            rewards.insert(record.idempotency_key.clone(), record);
+        }
+    });
+
+    schedule_payouts();
+    Ok(failed.len() as u32)
+}
+
+// Internal helper functions for other modules
+pub fn rewards_of(recipient: Principal, page: &PageRequest) -> Result<Page<RewardRecord>, BackendError> {
+    let ids = REWARDS_BY_RECIPIENT.with(|index| page_newest_first(&index.borrow(), recipient.as_slice(), 0, page, MAX_PAGE_SIZE))?;
+    let items = REWARDS.with(|rewards| {
+        let rewards = rewards.borrow();
+        ids.items.iter().filter_map(|key| rewards.get(key)).collect()
+    });
+    Ok(Page { items, next_cursor: ids.next_cursor })
+}
+
+// Indexes rewards recorded before the recipient index existed
+pub fn rebuild_reward_index() -> u64 {
+    let keys: Vec<_> = REWARDS.with(|rewards| rewards.borrow().iter().map(|(_, record)| reward_recipient_key(&record)).collect());
+    REWARDS_BY_RECIPIENT.with(|index| {
+        let mut index = index.borrow_mut();
+        for key in &keys {
+            index.insert(key.clone(), ());
+        }
+    });
+    keys.len() as u64
+}
+
+pub fn reward_config() -> RewardConfig {
+    REWARD_CONFIG.with(|cell| cell.borrow().get().clone())
+}
+
+pub fn reward_key(activity: &RewardActivity, subject: &str) -> String {
+    let prefix = match activity {
+        RewardActivity::ProfileCreated => "profile",
+        RewardActivity::MemberAdded => "member",
+        RewardActivity::EventRecorded => "event",
+        RewardActivity::ClaimApproved => "claim",
+    };
+    format!("{}:{}", prefix, subject)
+}
+
+fn reward_amount(config: &RewardConfig, activity: &RewardActivity) -> u64 {
+    match activity {
+        RewardActivity::ProfileCreated => config.profile_created,
+        RewardActivity::MemberAdded => config.member_added,
+        RewardActivity::EventRecorded => config.event_recorded,
+        RewardActivity::ClaimApproved => config.claim_approved,
+    }
+}
+
+fn reward_reason(activity: &RewardActivity) -> &'static str {
+    match activity {
+        RewardActivity::ProfileCreated => "Profile created",
+        RewardActivity::MemberAdded => "Family member added",
+        RewardActivity::EventRecorded => "Family event recorded",
+        RewardActivity::ClaimApproved => "Ghost profile claim approved",
+    }
+}
+
+// Records a reward once per idempotency key; returns None when nothing was queued
+pub fn enqueue_reward(
+    config: &RewardConfig,
+    recipient: Principal,
+    activity: RewardActivity,
+    subject: &str,
+    now: u64,
+) -> Option<RewardRecord> {
+    let amount = reward_amount(config, &activity);
+    if amount == 0 || config.token_canister.is_none() {
+        return None;
+    }
+
+    let key = reward_key(&activity, subject);
+    REWARDS.with(|rewards| {
+        let mut rewards = rewards.borrow_mut();
+        if rewards.contains_key(&key) {
+            return None;
+        }
+
+        let record = RewardRecord {
+            idempotency_key: key.clone(),
+            recipient,
+            activity,
+            amount,
+            status: RewardStatus::Pending,
+            attempts: 0,
+            token_tx_id: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        rewards.insert(key, record.clone());
+        REWARDS_BY_RECIPIENT.with(|index| index.borrow_mut().insert(reward_recipient_key(&record), ()));
+        Some(record)
+    })
+}
+
+// Called by endpoints after a rewardable action has been committed
+pub fn queue_reward(recipient: Principal, activity: RewardActivity, subject: &str) {
+    if enqueue_reward(&reward_config(), recipient, activity, subject, api::time()).is_some() {
+        schedule_payouts();
+    }
+}
+
+// A member pays out once they are linked to a real profile, and only once per person
+// per family, so adding unlinked members or re-adding the same person earns nothing
+pub fn linked_member_subject(family_id: &str, linked: Principal) -> String {
+    format!("{}:{}", family_id, linked.to_text())
+}
+
+// Queues a reward in the first free slot of `scope`, so at most `cap` rewards share it
+pub fn enqueue_capped_reward(
+    config: &RewardConfig,
+    recipient: Principal,
+    activity: RewardActivity,
+    scope: &str,
+    cap: u32,
+    now: u64,
+) -> Option<RewardRecord> {
+    (0..cap).find_map(|slot| {
+        enqueue_reward(config, recipient, activity.clone(), &format!("{}#{}", scope, slot), now)
+    })
+}
+
+// Event rewards are capped per recipient per day
+pub fn queue_event_reward(recipient: Principal) {
+    let now = api::time();
+    let scope = format!("{}:{}", recipient.to_text(), now / NANOS_PER_DAY);
+    let queued = enqueue_capped_reward(
+        &reward_config(), recipient, RewardActivity::EventRecorded, &scope, MAX_EVENT_REWARDS_PER_DAY, now,
+    );
+    if queued.is_some() {
+        schedule_payouts();
+    }
+}
+
+// Moves a Pending reward to InFlight; returns None if it is not payable
+pub fn claim_reward(key: &str, now: u64) -> Option<RewardRecord> {
+    REWARDS.with(|rewards| {
+        let mut rewards = rewards.borrow_mut();
+        let mut record = rewards.get(&key.to_string())?;
+        if record.status != RewardStatus::Pending {
+            return None;
+        }
+
+        record.status = RewardStatus::InFlight;
+        record.attempts += 1;
+        record.updated_at = now;
+        rewards.insert(key.to_string(), record.clone());
+        Some(record)
+    })
+}
+
+pub fn record_outcome(key: &str, outcome: Result<u64, String>, now: u64) {
+    REWARDS.with(|rewards| {
+        let mut rewards = rewards.borrow_mut();
+        if let Some(mut record) = rewards.get(&key.to_string()) {
+            match outcome {
+                Ok(tx_id) => {
+                    record.status = RewardStatus::Paid;
+                    record.token_tx_id = Some(tx_id);
+                    record.last_error = None;
+                }
+                Err(error) => {
+                    record.status = if record.attempts >= MAX_REWARD_ATTEMPTS {
+                        RewardStatus::Failed
+                    } else {
+                        RewardStatus::Pending
+                    };
+                    record.last_error = Some(error);
+                }
+            }
+            record.updated_at = now;
+            rewards.insert(key.to_string(), record);
+        }
+    });
+}
+
+fn pending_reward_keys(limit: usize) -> Vec<String> {
+    REWARDS.with(|rewards| {
+        rewards.borrow()
+            .iter()
+            .filter(|(_, record)| record.status == RewardStatus::Pending)
+            .map(|(key, _)| key)
+            .take(limit)
+            .collect()
+    })
+}
+
+pub fn start_reward_timer() {
+    ic_cdk_timers::set_timer_interval(REWARD_RETRY_INTERVAL, || ic_cdk::spawn(process_pending_rewards()));
+}
+
+fn schedule_payouts() {
+    ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(process_pending_rewards()));
+}
+
+async fn process_pending_rewards() {
+    for key in pending_reward_keys(REWARD_BATCH_SIZE) {
+        pay_reward(&key).await;
+    }
+}
+
+async fn pay_reward(key: &str) {
+    let Some(token_canister) = reward_config().token_canister else {
+        return;
+    };
+    let Some(record) = claim_reward(key, api::time()) else {
+        return;
+    };
+
+    let account = LedgerAccount {
+        owner: record.recipient,
+        subaccount: None,
+    };
+    let reason = reward_reason(&record.activity).to_string();
+
+    // A reject means reward_user did not commit, so the reward can safely be retried
+    let outcome = match ic_cdk::call::<_, (Result<u64, LedgerError>,)>(
+        token_canister,
+        "reward_user",
+        (account, Nat::from(record.amount), reason),
+    ).await {
+        Ok((Ok(tx_id),)) => Ok(tx_id),
+        Ok((Err(error),)) => Err(format!("Ledger rejected reward: {:?}", error)),
+        Err((code, message)) => Err(format!("Call to ledger failed ({:?}): {}", code, message)),
+    };
+
+    record_outcome(key, outcome, api::time());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RewardConfig {
+        RewardConfig {
+            token_canister: Some(Principal::from_slice(&[9])),
+            profile_created: 1_000,
+            member_added: 500,
+            event_recorded: 0,
+            claim_approved: 2_000,
+        }
+    }
+
+    fn user() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    #[test]
+    fn test_rewards_are_idempotent() {
+        let first = enqueue_reward(&config(), user(), RewardActivity::MemberAdded, "m1", 0);
+        assert_eq!(first.map(|r| r.amount), Some(500));
+
+        // Retried or repeated actions reuse the same key
+        assert!(enqueue_reward(&config(), user(), RewardActivity::MemberAdded, "m1", 1).is_none());
+        assert!(enqueue_reward(&config(), user(), RewardActivity::MemberAdded, "m2", 1).is_some());
+        assert_eq!(REWARDS.with(|r| r.borrow().len()), 2);
+
+        // Disabled activities and an unset ledger queue nothing
+        assert!(enqueue_reward(&config(), user(), RewardActivity::EventRecorded, "e1", 0).is_none());
+        let unset = RewardConfig { token_canister: None, ..config() };
+        assert!(enqueue_reward(&unset, user(), RewardActivity::ClaimApproved, "c1", 0).is_none());
+    }
+
+    #[test]
+    fn test_rewards_are_paged_by_recipient() {
+        for (subject, time) in [("m1", 1), ("m2", 2), ("m3", 3)] {
+            enqueue_reward(&config(), user(), RewardActivity::MemberAdded, subject, time);
+        }
+        enqueue_reward(&config(), Principal::from_slice(&[2]), RewardActivity::MemberAdded, "m4", 4);
+
+        let first = rewards_of(user(), &PageRequest { cursor: None, limit: Some(2) }).unwrap();
+        assert_eq!(first.items.iter().map(|r| r.created_at).collect::<Vec<_>>(), vec![3, 2]);
+        let rest = rewards_of(user(), &PageRequest { cursor: first.next_cursor, limit: Some(2) }).unwrap();
+        assert_eq!(rest.items.iter().map(|r| r.created_at).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(rest.next_cursor, None);
+    }
+
+    #[test]
+    fn test_event_rewards_are_capped() {
+        let config = RewardConfig { event_recorded: 10, ..config() };
+        for _ in 0..3 {
+            assert!(enqueue_capped_reward(&config, user(), RewardActivity::EventRecorded, "u:1", 3, 0).is_some());
+        }
+        assert!(enqueue_capped_reward(&config, user(), RewardActivity::EventRecorded, "u:1", 3, 0).is_none());
+
+        // The next day starts a fresh scope
+        assert!(enqueue_capped_reward(&config, user(), RewardActivity::EventRecorded, "u:2", 3, 0).is_some());
+        assert_eq!(REWARDS.with(|r| r.borrow().len()), 4);
+    }
+
+    #[test]
+    fn test_reward_lifecycle() {
+        enqueue_reward(&config(), user(), RewardActivity::ProfileCreated, "p1", 0);
+        let key = reward_key(&RewardActivity::ProfileCreated, "p1");
+
+        // An in-flight reward cannot be claimed twice
+        assert!(claim_reward(&key, 1).is_some());
+        assert!(claim_reward(&key, 1).is_none());
+
+        record_outcome(&key, Err("ledger unavailable".to_string()), 2);
+        let record = REWARDS.with(|r| r.borrow().get(&key)).unwrap();
+        assert_eq!(record.status, RewardStatus::Pending);
+        assert_eq!(pending_reward_keys(10), vec![key.clone()]);
+
+        claim_reward(&key, 3).unwrap();
+        record_outcome(&key, Ok(42), 4);
+        let record = REWARDS.with(|r| r.borrow().get(&key)).unwrap();
+        assert_eq!(record.status, RewardStatus::Paid);
+        assert_eq!(record.token_tx_id, Some(42));
+        assert_eq!(record.attempts, 2);
+        assert!(claim_reward(&key, 5).is_none());
+    }
+
+    #[test]
+    fn test_reward_gives_up_after_max_attempts() {
+        enqueue_reward(&config(), user(), RewardActivity::ClaimApproved, "c1", 0);
+        let key = reward_key(&RewardActivity::ClaimApproved, "c1");
+
+        for attempt in 0..MAX_REWARD_ATTEMPTS {
+            claim_reward(&key, attempt as u64).unwrap();
+            record_outcome(&key, Err("rejected".to_string()), attempt as u64);
+        }
+
+        let record = REWARDS.with(|r| r.borrow().get(&key)).unwrap();
+        assert_eq!(record.status, RewardStatus::Failed);
+        assert!(pending_reward_keys(10).is_empty());
+    }
+}
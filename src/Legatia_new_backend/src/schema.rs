@@ -0,0 +1,337 @@
+use candid::CandidType;
+use ic_stable_structures::{Memory, StableBTreeMap, Storable};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::ops::Bound;
+use std::time::Duration;
+
+use crate::storage::{
+    SCHEMA_VERSION, PROFILES, FAMILIES, USER_FAMILIES, CLAIM_REQUESTS, INVITATIONS, NOTIFICATIONS,
+    USER_SEARCH_INDEX, RELATIONSHIPS, REWARDS, REWARD_CONFIG, FAMILY_ROLES, SUCCESSION, FAMILY_PROPOSALS,
+    GOVERNANCE_CONFIGS, TREASURY_TRANSACTIONS, MEDIA_ASSETS, MEDIA_ACCESS_GRANTS, FAMILY_MEMBERS, MEMBER_EVENTS,
+};
+
+// Stored records are Candid prefixed with [RECORD_TAG, version]. Candid always starts
+// with "DIDL", so records written before tagging are told apart and read as version 0.
+const RECORD_TAG: u8 = 0xFF;
+const LEGACY_VERSION: u8 = 0;
+// Records re-tagged per timer run, to stay well within instruction limits
+const RETAG_BATCH_SIZE: usize = 500;
+
+thread_local! {
+    // Position in RETAG_MAPS and the last key re-tagged there, while re-tagging runs
+    static RETAG_CURSOR: RefCell<Option<RetagCursor>> = const { RefCell::new(None) };
+}
+
+// A record type kept in stable memory. Adding `Option` fields needs no version bump,
+// since Candid reads a missing `opt` field as None. Any other change to the shape
+// (renaming, removing or retyping a field) bumps SCHEMA_VERSION and decodes the
+// older shapes in `decode_version`.
+pub trait Versioned: CandidType + DeserializeOwned {
+    const SCHEMA_VERSION: u8;
+
+    // Decodes a record written at an older version
+    fn decode_version(_version: u8, payload: &[u8]) -> Result<Self, String> {
+        candid::decode_one(payload).map_err(|e| e.to_string())
+    }
+}
+
+pub fn encode<T: Versioned>(value: &T) -> Cow<'static, [u8]> {
+    let mut bytes = vec![RECORD_TAG, T::SCHEMA_VERSION];
+    bytes.extend(candid::encode_one(value).unwrap_or_else(|e| {
+        panic!("Failed to encode {} record: {}", std::any::type_name::<T>(), e)
+    }));
+    Cow::Owned(bytes)
+}
+
+// Traps rather than returning a default: a record that no longer decodes is a bug in
+// the upgrade, and trapping in post_upgrade rolls the upgrade back with data intact.
+pub fn decode<T: Versioned>(bytes: &[u8]) -> T {
+    let (version, payload) = match bytes {
+        [RECORD_TAG, version, payload @ ..] => (*version, payload),
+        _ => (LEGACY_VERSION, bytes),
+    };
+    let decoded = if version == T::SCHEMA_VERSION {
+        candid::decode_one(payload).map_err(|e| e.to_string())
+    } else if version < T::SCHEMA_VERSION {
+        T::decode_version(version, payload)
+    } else {
+        Err(format!("written by a newer build (schema version {})", T::SCHEMA_VERSION))
+    };
+    decoded.unwrap_or_else(|e| {
+        panic!("Failed to decode {} record at schema version {}: {}", std::any::type_name::<T>(), version, e)
+    })
+}
+
+// One-off changes to stored data, applied in order by post_upgrade. Append new
+// migrations at the end with the next version; never renumber or remove one.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    // Returns how many records were touched
+    pub run: fn() -> u64,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Index notifications, invitations and claims",
+        run: || crate::invitations::rebuild_indexes() + crate::ghost::rebuild_claim_indexes(),
+    },
+    Migration {
+        version: 2,
+        description: "Move family members into per-member storage",
+        run: crate::family::store::migrate_inline_members,
+    },
+    Migration {
+        version: 3,
+        description: "Tag stored records with their schema version (continues in the background)",
+        run: retag_all,
+    },
+    Migration {
+        version: 4,
+        description: "Index media assets by family, uploader and upload activity",
+        run: crate::family::media::rebuild_media_indexes,
+    },
+    Migration {
+        version: 5,
+        description: "Index rewards by recipient and treasury transactions by family, with running totals",
+        run: || crate::rewards::rebuild_reward_index() + crate::family::treasury::rebuild_treasury_indexes(),
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+// Migrations still to run on data at `stored`
+pub fn pending_migrations(stored: u32) -> Result<&'static [Migration], String> {
+    if stored > latest_version() {
+        return Err(format!(
+            "Stable memory is at schema version {} but this build only knows up to {}",
+            stored, latest_version()
+        ));
+    }
+    Ok(&MIGRATIONS[MIGRATIONS.partition_point(|migration| migration.version <= stored)..])
+}
+
+pub fn run_migrations() {
+    let stored = SCHEMA_VERSION.with(|version| *version.borrow().get());
+    let pending = pending_migrations(stored).unwrap_or_else(|e| ic_cdk::trap(&e));
+    for migration in pending {
+        let touched = (migration.run)();
+        set_version(migration.version);
+        ic_cdk::println!("Schema migration {} ({}): {} records", migration.version, migration.description, touched);
+    }
+    if RETAG_CURSOR.with(|cursor| cursor.borrow().is_some()) {
+        ic_cdk_timers::set_timer(Duration::ZERO, continue_retag);
+    }
+}
+
+// Fresh installs start with every migration already applied
+pub fn mark_current() {
+    set_version(latest_version());
+}
+
+fn set_version(version: u32) {
+    SCHEMA_VERSION.with(|cell| cell.borrow_mut().set(version).expect("Failed to store schema version"));
+}
+
+// Re-tags one batch of a map after the given key; returns the last key re-tagged,
+// or None once the map is done
+type RetagStep = fn(Option<&[u8]>, usize) -> Option<Vec<u8>>;
+
+const RETAG_MAPS: &[RetagStep] = &[
+    |after, limit| PROFILES.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| FAMILIES.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| FAMILY_MEMBERS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| MEMBER_EVENTS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| USER_FAMILIES.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| CLAIM_REQUESTS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| INVITATIONS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| NOTIFICATIONS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| USER_SEARCH_INDEX.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| RELATIONSHIPS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| REWARDS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| FAMILY_ROLES.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| SUCCESSION.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| FAMILY_PROPOSALS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| GOVERNANCE_CONFIGS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| TREASURY_TRANSACTIONS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| MEDIA_ASSETS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+    |after, limit| MEDIA_ACCESS_GRANTS.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+struct RetagCursor {
+    map: usize,
+    after: Option<Vec<u8>>,
+}
+
+// Rewrites every record so it carries a version tag, in timer batches so large maps
+// don't run post_upgrade out of instructions. Until then untagged records read as
+// version 0, which is also what they are. An upgrade before the batches finish
+// leaves the rest untagged until their next write.
+fn retag_all() -> u64 {
+    REWARD_CONFIG.with(|cell| {
+        let config = cell.borrow().get().clone();
+        cell.borrow_mut().set(config).expect("Failed to store reward config");
+    });
+    // run_migrations starts the batches once every migration has run
+    RETAG_CURSOR.with(|cursor| *cursor.borrow_mut() = Some(RetagCursor { map: 0, after: None }));
+    1
+}
+
+// A record that fails to decode traps the batch and stops re-tagging there
+fn continue_retag() {
+    let Some(cursor) = RETAG_CURSOR.with(|cursor| cursor.borrow_mut().take()) else {
+        return;
+    };
+    match retag_step(RETAG_MAPS, cursor, RETAG_BATCH_SIZE) {
+        Some(next) => {
+            RETAG_CURSOR.with(|cursor| *cursor.borrow_mut() = Some(next));
+            ic_cdk_timers::set_timer(Duration::ZERO, continue_retag);
+        }
+        None => ic_cdk::println!("Schema migration 3: all records tagged"),
+    }
+}
+
+fn retag_step(maps: &[RetagStep], cursor: RetagCursor, limit: usize) -> Option<RetagCursor> {
+    match maps[cursor.map](cursor.after.as_deref(), limit) {
+        Some(last) => Some(RetagCursor { map: cursor.map, after: Some(last) }),
+        None if cursor.map + 1 < maps.len() => Some(RetagCursor { map: cursor.map + 1, after: None }),
+        None => None,
+    }
+}
+
+fn retag_batch<K: Storable + Ord + Clone, V: Storable, M: Memory>(
+    map: &mut StableBTreeMap<K, V, M>,
+    after: Option<&[u8]>,
+    limit: usize,
+) -> Option<Vec<u8>> {
+    let start = match after {
+        Some(key) => Bound::Excluded(K::from_bytes(Cow::Borrowed(key))),
+        None => Bound::Unbounded,
+    };
+    let records: Vec<(K, V)> = map.range((start, Bound::Unbounded)).take(limit).collect();
+    let last = match records.last() {
+        Some((key, _)) if records.len() == limit => Some(key.to_bytes().into_owned()),
+        _ => None,
+    };
+    for (key, value) in records {
+        map.insert(key, value);
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(CandidType, Deserialize, Debug, PartialEq)]
+    struct NoteV1 {
+        text: String,
+    }
+
+    impl Versioned for NoteV1 {
+        const SCHEMA_VERSION: u8 = 1;
+    }
+
+    // Version 2 renamed `text` to `body`
+    #[derive(CandidType, Deserialize, Debug, PartialEq)]
+    struct Note {
+        body: String,
+        pinned: Option<bool>,
+    }
+
+    impl Versioned for Note {
+        const SCHEMA_VERSION: u8 = 2;
+
+        fn decode_version(version: u8, payload: &[u8]) -> Result<Self, String> {
+            match version {
+                0 | 1 => {
+                    let old: NoteV1 = candid::decode_one(payload).map_err(|e| e.to_string())?;
+                    Ok(Note { body: old.text, pinned: None })
+                }
+                _ => Err("unknown version".to_string()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decodes_current_older_and_untagged_records() {
+        let note = Note { body: "hi".to_string(), pinned: Some(true) };
+        assert_eq!(decode::<Note>(&encode(&note)), note);
+
+        let v1 = NoteV1 { text: "old".to_string() };
+        let upgraded = Note { body: "old".to_string(), pinned: None };
+        assert_eq!(decode::<Note>(&encode(&v1)), upgraded);
+        // Written before records were tagged
+        assert_eq!(decode::<Note>(&candid::encode_one(&v1).unwrap()), upgraded);
+    }
+
+    #[test]
+    #[should_panic(expected = "newer build")]
+    fn test_newer_records_fail_loudly() {
+        decode::<NoteV1>(&encode(&Note { body: "new".to_string(), pinned: None }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to decode")]
+    fn test_undecodable_records_fail_loudly() {
+        decode::<Note>(&candid::encode_one(42u64).unwrap());
+    }
+
+    thread_local! {
+        static NOTES: RefCell<StableBTreeMap<u64, Note, ic_stable_structures::DefaultMemoryImpl>> =
+            RefCell::new(StableBTreeMap::new(Default::default()));
+    }
+
+    impl Storable for Note {
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            encode(self)
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            decode(&bytes)
+        }
+
+        const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Unbounded;
+    }
+
+    #[test]
+    fn test_retag_walks_maps_in_batches() {
+        NOTES.with(|notes| {
+            let mut notes = notes.borrow_mut();
+            for id in 0..5u64 {
+                notes.insert(id, Note { body: id.to_string(), pinned: None });
+            }
+        });
+        let maps: &[RetagStep] = &[
+            |after, limit| NOTES.with(|map| retag_batch(&mut map.borrow_mut(), after, limit)),
+            |_, _| None,
+        ];
+
+        let mut cursor = RetagCursor { map: 0, after: None };
+        let mut steps = vec![];
+        while let Some(next) = retag_step(maps, cursor, 2) {
+            steps.push((next.map, next.after.as_deref().map(|key| u64::from_bytes(Cow::Borrowed(key)))));
+            cursor = next;
+        }
+        // Two full batches, then the short one finishes the map and moves on
+        assert_eq!(steps, vec![(0, Some(1)), (0, Some(3)), (1, None)]);
+    }
+
+    #[test]
+    fn test_pending_migrations() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(versions, (1..=latest_version()).collect::<Vec<_>>());
+
+        assert_eq!(pending_migrations(0).unwrap().len(), MIGRATIONS.len());
+        assert_eq!(pending_migrations(2).unwrap()[0].version, 3);
+        assert!(pending_migrations(latest_version()).unwrap().is_empty());
+        assert!(pending_migrations(latest_version() + 1).is_err());
+    }
+}
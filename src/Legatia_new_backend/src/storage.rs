@@ -1,9 +1,13 @@
 use candid::Principal;
 use std::cell::RefCell;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 
-use crate::types::{UserProfile, Family, UserFamilyList, ClaimRequest, FamilyInvitation, Notification, UserSearchResult};
+use crate::types::{UserProfile, Family, UserFamilyList, ClaimRequest, FamilyInvitation, Notification, UserSearchResult, FamilyRelationshipList,
+    RewardConfig, RewardRecord, FamilyRoleList, FamilySuccession, FamilyGovernanceConfig, FamilyProposal, TreasuryTransaction,
+    MediaAsset, MediaAccessGrant, FamilyMember, FamilyEvent, TreasuryTotals};
+use crate::indexes::IndexKey;
+use crate::family::store::{ChildKey, EventKey};
 
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 
@@ -18,6 +22,7 @@ thread_local! {
         )
     );
 
+    // Family records without their members; see family/store.rs
     pub static FAMILIES: RefCell<StableBTreeMap<String, Family, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))
@@ -53,6 +58,175 @@ thread_local! {
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))
         )
     );
+
+    // Relationship edges keyed by family ID
+    pub static RELATIONSHIPS: RefCell<StableBTreeMap<String, FamilyRelationshipList, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7)))
+        )
+    );
+
+    // LGT reward payouts keyed by idempotency key
+    pub static REWARDS: RefCell<StableBTreeMap<String, RewardRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8)))
+        )
+    );
+
+    pub static REWARD_CONFIG: RefCell<StableCell<RewardConfig, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+            RewardConfig::default()
+        ).expect("Failed to initialize reward config")
+    );
+
+    // Role assignments keyed by family ID
+    pub static FAMILY_ROLES: RefCell<StableBTreeMap<String, FamilyRoleList, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
+        )
+    );
+
+    // Ownership offers, successors and succession votes keyed by family ID
+    pub static SUCCESSION: RefCell<StableBTreeMap<String, FamilySuccession, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11)))
+        )
+    );
+
+    // Family governance proposals keyed by proposal ID
+    pub static FAMILY_PROPOSALS: RefCell<StableBTreeMap<String, FamilyProposal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
+        )
+    );
+
+    pub static GOVERNANCE_CONFIGS: RefCell<StableBTreeMap<String, FamilyGovernanceConfig, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))
+        )
+    );
+
+    // Family treasury deposits and spends keyed by transaction ID
+    pub static TREASURY_TRANSACTIONS: RefCell<StableBTreeMap<String, TreasuryTransaction, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
+        )
+    );
+
+    // Media metadata keyed by media ID
+    pub static MEDIA_ASSETS: RefCell<StableBTreeMap<String, MediaAsset, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))
+        )
+    );
+
+    // Media content keyed by "<media ID>:<chunk index>"
+    pub static MEDIA_CHUNKS: RefCell<StableBTreeMap<String, Vec<u8>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
+        )
+    );
+
+    // HTTP media access grants keyed by token hash
+    pub static MEDIA_ACCESS_GRANTS: RefCell<StableBTreeMap<String, MediaAccessGrant, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17)))
+        )
+    );
+
+    // Secondary indexes over notifications, invitations and claims; see indexes.rs
+    pub static NOTIFICATIONS_BY_RECIPIENT: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))
+        )
+    );
+
+    pub static INVITATIONS_BY_INVITEE: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))
+        )
+    );
+
+    pub static CLAIMS_BY_REQUESTER: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20)))
+        )
+    );
+
+    pub static CLAIMS_BY_FAMILY: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21)))
+        )
+    );
+
+    // Family members without their events, keyed by (family ID, member ID)
+    pub static FAMILY_MEMBERS: RefCell<StableBTreeMap<ChildKey, FamilyMember, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22)))
+        )
+    );
+
+    // Member events keyed by (family ID, member ID, event ID)
+    pub static MEMBER_EVENTS: RefCell<StableBTreeMap<EventKey, FamilyEvent, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23)))
+        )
+    );
+
+    // Last schema migration applied to stable memory; see schema.rs
+    pub static SCHEMA_VERSION: RefCell<StableCell<u32, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))),
+            0
+        ).expect("Failed to initialize schema version")
+    );
+
+    // Secondary indexes over media assets; see family/media.rs
+    pub static MEDIA_BY_FAMILY: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25)))
+        )
+    );
+
+    pub static MEDIA_BY_UPLOADER: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26)))
+        )
+    );
+
+    pub static OPEN_UPLOADS_BY_ACTIVITY: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27)))
+        )
+    );
+
+    // Bytes of media each family stores, counting uploads in progress, keyed by family ID
+    pub static FAMILY_MEDIA_BYTES: RefCell<StableBTreeMap<String, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(28)))
+        )
+    );
+
+    // Rewards by recipient and treasury transactions by family; see indexes.rs
+    pub static REWARDS_BY_RECIPIENT: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(29)))
+        )
+    );
+
+    pub static TREASURY_BY_FAMILY: RefCell<StableBTreeMap<IndexKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(30)))
+        )
+    );
+
+    // Running treasury totals keyed by family ID, kept in step with its transactions
+    pub static TREASURY_TOTALS: RefCell<StableBTreeMap<String, TreasuryTotals, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(31)))
+        )
+    );
 }
 
 // Helper function to generate unique IDs using secure random generation
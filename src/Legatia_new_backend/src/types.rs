@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use ic_stable_structures::storable::{Bound, Storable};
 use std::borrow::Cow;
 
+use crate::schema::{self, Versioned};
+
 // Development mode - allows anonymous access for testing
 pub const DEV_MODE: bool = true;
 
@@ -51,6 +53,7 @@ pub struct FamilyEvent {
     pub event_type: String, // "birth", "marriage", "death", "education", "achievement", "other"
     pub created_at: u64,
     pub created_by: Principal,
+    pub media_ids: Option<Vec<String>>, // Attached media assets
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -68,6 +71,7 @@ pub struct FamilyMember {
     pub events: Vec<FamilyEvent>,
     pub created_at: u64,
     pub created_by: Principal,
+    pub media_ids: Option<Vec<String>>, // Attached media assets
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -152,10 +156,385 @@ pub struct UpdateEventRequest {
     pub event_type: Option<String>,
 }
 
+// Relationship Graph Types
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RelationshipType {
+    ParentOf, // from_member is a parent of to_member
+    SpouseOf, // Symmetric, from/to order carries no meaning
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RelationshipQualifier {
+    Biological,
+    Adoptive,
+    Step,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FamilyRelationship {
+    pub id: String,
+    pub family_id: String,
+    pub from_member_id: String,
+    pub to_member_id: String,
+    pub relationship_type: RelationshipType,
+    pub qualifier: RelationshipQualifier,
+    pub start_date: Option<String>, // ISO date string, e.g. marriage date for SpouseOf
+    pub end_date: Option<String>, // ISO date string, e.g. divorce or widowhood for SpouseOf
+    pub created_at: u64,
+    pub created_by: Principal,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AddRelationshipRequest {
+    pub family_id: String,
+    pub from_member_id: String,
+    pub to_member_id: String,
+    pub relationship_type: RelationshipType,
+    pub qualifier: Option<RelationshipQualifier>, // Defaults to Biological
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RelatedMember {
+    pub member_id: String,
+    pub full_name: String,
+    pub generation: u32, // 1 for parents/children, 2 for grandparents/grandchildren, ...
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct KinshipResult {
+    pub from_member_id: String,
+    pub to_member_id: String,
+    pub description: String, // How to_member relates to from_member, e.g. "second cousin once removed"
+    pub path: Vec<String>, // Member IDs walked from from_member to to_member
+    pub common_ancestor_id: Option<String>,
+}
+
+// GEDCOM Import/Export Types
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ImportGedcomRequest {
+    pub name: String,
+    pub description: String,
+    pub is_visible: Option<bool>,
+    pub gedcom: String, // Full GEDCOM 5.5.1 or 7.0 file contents
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GedcomSkippedRecord {
+    pub line: u64,
+    pub xref: Option<String>, // Enclosing record, e.g. "I12" for an individual
+    pub tag: String,
+    pub reason: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GedcomImportReport {
+    pub family: Family,
+    pub gedcom_version: Option<String>,
+    pub members_imported: u32,
+    pub events_imported: u32,
+    pub relationships_imported: u32,
+    pub skipped_records: Vec<GedcomSkippedRecord>,
+}
+
+// Storage wrapper for a family's relationship edges
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FamilyRelationshipList(pub Vec<FamilyRelationship>);
+
 // Storage wrapper for Vec<String> to work around orphan rules
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
 pub struct UserFamilyList(pub Vec<String>);
 
+// Family Role Types
+// The family admin is always the Owner; other roles are granted explicitly
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FamilyRole {
+    Owner,
+    CoAdmin,
+    Editor,
+    Contributor,
+    Viewer,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FamilyRoleAssignment {
+    pub principal: Principal,
+    pub role: FamilyRole,
+    pub granted_by: Principal,
+    pub granted_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GrantRoleRequest {
+    pub family_id: String,
+    pub principal: Principal,
+    pub role: FamilyRole,
+}
+
+// Storage wrapper for a family's role assignments
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FamilyRoleList(pub Vec<FamilyRoleAssignment>);
+
+// Family Succession Types
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OwnershipOffer {
+    pub to: Principal,
+    pub offered_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SuccessionVote {
+    pub opened_at: u64,
+    pub closes_at: u64,
+    pub ballots: Vec<(Principal, Principal)>, // (voter, candidate)
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FamilySuccession {
+    pub successor: Option<Principal>,
+    pub dormancy_period_days: u32,
+    pub owner_last_active: u64,
+    pub pending_offer: Option<OwnershipOffer>,
+    pub vote: Option<SuccessionVote>,
+}
+
+// Family Governance Types
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FamilyGovernanceConfig {
+    pub voting_threshold_percent: u8, // Share of eligible voters needed to pass or reject
+    pub voting_period_days: u32,
+    pub proposal_cooldown_hours: u32, // Minimum time between proposals from the same member
+}
+
+impl Default for FamilyGovernanceConfig {
+    fn default() -> Self {
+        Self {
+            voting_threshold_percent: 51,
+            voting_period_days: 7,
+            proposal_cooldown_hours: 24,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum FamilyProposalType {
+    ChangeAdmin(Principal),
+    ModifyVisibility(bool),
+    AddMember(AddFamilyMemberRequest),
+    RemoveMember(String),
+    // Passing keeps a disputed event, rejecting removes it
+    ValidateEvent { member_id: String, event_id: String },
+    SpendTreasury(TreasurySpendRequest),
+    // Applies to proposals created after it passes
+    ChangeGovernanceConfig(FamilyGovernanceConfig),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum FamilyProposalStatus {
+    Active,
+    Executed,
+    Rejected,
+    Expired,
+    Failed, // Passed, but executing it returned an error
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FamilyProposal {
+    pub id: String,
+    pub family_id: String,
+    pub proposer: Principal,
+    pub proposal_type: FamilyProposalType,
+    pub title: String,
+    pub description: String,
+    pub votes: Vec<(Principal, bool)>, // (voter, approve)
+    pub status: FamilyProposalStatus,
+    pub created_at: u64,
+    pub voting_ends: u64,
+    pub executed_at: Option<u64>,
+    pub execution_result: Option<String>,
+    // Threshold in force when the proposal was created; None for older proposals
+    pub voting_threshold_percent: Option<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CreateFamilyProposalRequest {
+    pub family_id: String,
+    pub proposal_type: FamilyProposalType,
+    pub title: String,
+    pub description: String,
+}
+
+// Family Treasury Types
+// An account on the LGT ledger, mirroring the ledger's Account type
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LedgerAccount {
+    pub owner: Principal,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TreasuryTransactionKind {
+    Deposit,
+    Spend,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TreasuryTransactionStatus {
+    Pending, // Ledger call in progress
+    Completed,
+    Failed,
+    Unknown, // The ledger call may have gone through; retry to settle it
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TreasuryTransaction {
+    pub id: String,
+    pub family_id: String,
+    pub kind: TreasuryTransactionKind,
+    pub counterparty: LedgerAccount, // Depositor or recipient
+    pub amount: u64,                 // LGT e8s, excluding the ledger fee
+    pub memo: Option<String>,
+    pub initiated_by: Principal,
+    pub proposal_id: Option<String>, // Set for spends approved by a family vote
+    pub status: TreasuryTransactionStatus,
+    pub block_index: Option<u64>,
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TreasurySpendRequest {
+    pub family_id: String,
+    pub to: LedgerAccount,
+    pub amount: u64,
+    pub memo: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FamilyTreasury {
+    pub family_id: String,
+    pub account: LedgerAccount, // Deposit address on the LGT ledger
+    pub total_deposited: u64,
+    pub total_spent: u64,
+    pub total_unconfirmed: u64, // Pending or unknown transfers, counted in neither total
+}
+
+// Running totals per family; failed transfers count in none of them
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TreasuryTotals {
+    pub deposited: u64,
+    pub spent: u64,
+    pub unconfirmed: u64,
+}
+
+// Media Types
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MediaKind {
+    Photo,
+    Document,
+    Audio,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum MediaStatus {
+    Uploading, // Chunks still arriving; not downloadable yet
+    Ready,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MediaAsset {
+    pub id: String,
+    pub family_id: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub kind: MediaKind,
+    pub size: u64,         // Bytes
+    pub chunk_count: u32,
+    pub sha256: Vec<u8>,   // Content hash, checked when the upload completes
+    pub status: MediaStatus,
+    pub uploaded_by: Principal,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BeginMediaUploadRequest {
+    pub family_id: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub sha256: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MediaLinkRequest {
+    pub media_id: String,
+    pub member_id: String,
+    pub event_id: Option<String>, // Link to one of the member's events instead of the member
+}
+
+// Grants HTTP access to a family's media; stored under the SHA-256 of the token
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MediaAccessGrant {
+    pub family_id: String,
+    pub principal: Principal, // Access ends early if this principal loses its family role
+    pub expires_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MediaAccessToken {
+    pub token: String, // Append as ?token=... to /media/<media ID> URLs
+    pub family_id: String,
+    pub expires_at: u64,
+}
+
+// HTTP Gateway Types
+pub type HeaderField = (String, String);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<HeaderField>,
+    pub body: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<HeaderField>,
+    pub body: Vec<u8>,
+    pub streaming_strategy: Option<StreamingStrategy>,
+}
+
+// Byte range [start, end) of a media asset still to be sent
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct StreamingCallbackToken {
+    pub media_id: String,
+    pub access_token: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StreamingCallbackHttpResponse {
+    pub body: Vec<u8>,
+    pub token: Option<StreamingCallbackToken>,
+}
+
+candid::define_function!(pub StreamingCallback : (StreamingCallbackToken) -> (StreamingCallbackHttpResponse) query);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum StreamingStrategy {
+    Callback {
+        callback: StreamingCallback,
+        token: StreamingCallbackToken,
+    },
+}
+
 // Ghost Profile Claiming System
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct GhostProfileMatch {
@@ -178,7 +557,7 @@ pub struct ClaimRequest {
     pub status: ClaimStatus,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum ClaimStatus {
     Pending,
     Approved,
@@ -209,7 +588,7 @@ pub struct FamilyInvitation {
     pub relationship_to_admin: String, // How they relate to the family admin
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum InvitationStatus {
     Pending,
     Accepted,
@@ -254,7 +633,7 @@ pub struct Notification {
     pub metadata: Option<String>, // JSON metadata for specific actions
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum NotificationType {
     FamilyInvitation,
     GhostProfileClaim,
@@ -262,127 +641,427 @@ pub enum NotificationType {
     SystemAlert,
 }
 
-// Storable implementations
+// Token Reward Types
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RewardActivity {
+    ProfileCreated,
+    MemberAdded,
+    EventRecorded,
+    ClaimApproved,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RewardStatus {
+    Pending,  // Waiting to be paid, or waiting for a retry
+    InFlight, // reward_user call in progress
+    Paid,
+    Failed, // Gave up after too many attempts
+}
+
+// LGT amounts are in e8s; a zero amount disables the reward
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RewardConfig {
+    pub token_canister: Option<Principal>,
+    pub profile_created: u64,
+    pub member_added: u64,
+    pub event_recorded: u64,
+    pub claim_approved: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RewardRecord {
+    pub idempotency_key: String,
+    pub recipient: Principal,
+    pub activity: RewardActivity,
+    pub amount: u64,
+    pub status: RewardStatus,
+    pub attempts: u32,
+    pub token_tx_id: Option<u64>,
+    pub last_error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+// Pagination Types
+// Cursors are opaque to clients: pass back the next_cursor of the previous page
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PageRequest {
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>, // None on the last page
+}
+
+// Time bounds are exclusive nanosecond timestamps
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NotificationFilter {
+    pub notification_type: Option<NotificationType>,
+    pub unread_only: Option<bool>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InvitationFilter {
+    pub status: Option<InvitationStatus>,
+    pub family_id: Option<String>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ClaimFilter {
+    pub status: Option<ClaimStatus>,
+    pub family_id: Option<String>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EventFilter {
+    pub member_id: Option<String>,
+    pub event_type: Option<String>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+}
+
+// Storable implementations; see schema.rs for how stored types evolve
+impl Versioned for UserProfile {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for UserProfile {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        match candid::encode_one(self) {
-            Ok(bytes) => Cow::Owned(bytes),
-            Err(_) => {
-                // Log error and return empty bytes as fallback
-                ic_cdk::println!("Failed to serialize UserProfile");
-                Cow::Owned(vec![])
-            }
-        }
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        match candid::decode_one(&bytes) {
-            Ok(profile) => profile,
-            Err(_) => {
-                // Log error and return default profile as fallback
-                ic_cdk::println!("Failed to deserialize UserProfile, using default");
-                UserProfile::default()
-            }
-        }
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Versioned for Family {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for Family {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        match candid::encode_one(self) {
-            Ok(bytes) => Cow::Owned(bytes),
-            Err(_) => {
-                // Log error and return empty bytes as fallback
-                ic_cdk::println!("Failed to serialize Family");
-                Cow::Owned(vec![])
-            }
-        }
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        match candid::decode_one(&bytes) {
-            Ok(family) => family,
-            Err(_) => {
-                // Log error and return default family as fallback
-                ic_cdk::println!("Failed to deserialize Family, using default");
-                Family::default()
-            }
-        }
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for FamilyMember {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for FamilyMember {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for FamilyEvent {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for FamilyEvent {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Versioned for UserFamilyList {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for UserFamilyList {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for FamilyRelationshipList {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for FamilyRelationshipList {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for FamilyRoleList {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for FamilyRoleList {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Versioned for FamilySuccession {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for FamilySuccession {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for FamilyGovernanceConfig {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for FamilyGovernanceConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for FamilyProposal {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for FamilyProposal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for TreasuryTransaction {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for TreasuryTransaction {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for TreasuryTotals {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for TreasuryTotals {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for MediaAsset {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for MediaAsset {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for MediaAccessGrant {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for MediaAccessGrant {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for ClaimRequest {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for ClaimRequest {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Versioned for FamilyInvitation {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for FamilyInvitation {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Versioned for Notification {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for Notification {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Versioned for UserSearchResult {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for UserSearchResult {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Versioned for InvitationStatus {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
 impl Storable for InvitationStatus {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).unwrap())
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).unwrap()
+        schema::decode(&bytes)
     }
 
     const BOUND: Bound = Bound::Unbounded;
-}
\ No newline at end of file
+}
+
+impl Versioned for RewardConfig {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for RewardConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Versioned for RewardRecord {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for RewardRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        schema::encode(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        schema::decode(&bytes)
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
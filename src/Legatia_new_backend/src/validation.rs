@@ -2,7 +2,9 @@
 
 pub const MAX_NAME_LENGTH: usize = 100;
 pub const MAX_DESCRIPTION_LENGTH: usize = 500;
+#[allow(dead_code)]
 pub const MAX_MESSAGE_LENGTH: usize = 1000;
+#[allow(dead_code)]
 pub const MAX_REASON_LENGTH: usize = 200;
 pub const MIN_NAME_LENGTH: usize = 1;
 pub const MAX_SEARCH_QUERY_LENGTH: usize = 50;
@@ -75,6 +77,7 @@ pub fn validate_description(description: &str) -> Result<(), ValidationError> {
 }
 
 /// Validate a message field (invitations, etc.)
+#[allow(dead_code)]
 pub fn validate_message(message: &str) -> Result<(), ValidationError> {
     let trimmed = message.trim();
     
@@ -99,15 +102,15 @@ pub fn validate_search_query(query: &str) -> Result<(), ValidationError> {
     let trimmed = query.trim();
     
     if trimmed.is_empty() {
-        return Err(ValidationError::Empty("search query".to_string()));
+        return Err(ValidationError::Empty("query".to_string()));
     }
     
     if trimmed.len() < 2 {
-        return Err(ValidationError::TooShort("search query".to_string()));
+        return Err(ValidationError::TooShort("query".to_string()));
     }
     
     if trimmed.len() > MAX_SEARCH_QUERY_LENGTH {
-        return Err(ValidationError::TooLong("search query".to_string()));
+        return Err(ValidationError::TooLong("query".to_string()));
     }
     
     // Allow letters, numbers, spaces, hyphens, apostrophes, periods, and @ for email-like searches
@@ -116,13 +119,14 @@ pub fn validate_search_query(query: &str) -> Result<(), ValidationError> {
     });
     
     if !valid_chars {
-        return Err(ValidationError::InvalidCharacters("search query".to_string()));
+        return Err(ValidationError::InvalidCharacters("query".to_string()));
     }
     
     Ok(())
 }
 
 /// Validate a reason field for claims, etc.
+#[allow(dead_code)]
 pub fn validate_reason(reason: &str) -> Result<(), ValidationError> {
     let trimmed = reason.trim();
     
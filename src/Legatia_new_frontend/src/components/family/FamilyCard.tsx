@@ -3,9 +3,8 @@ import { Link } from 'react-router-dom';
 import { Card, CardHeader, CardTitle, CardDescription, CardContent } from '../ui/card';
 import { Badge } from '../ui/badge';
 import { Button } from '../ui/button';
-import { Avatar, AvatarFallback } from '../ui/avatar';
 import { Family } from '../../types';
-import { Users, Eye, EyeOff, ChevronRight } from 'lucide-react';
+import { Eye, EyeOff, ChevronRight } from 'lucide-react';
 import { format } from 'date-fns';
 
 interface FamilyCardProps {
@@ -22,15 +21,6 @@ export const FamilyCard: React.FC<FamilyCardProps> = ({ family, className }) =>
     }
   };
 
-  const getInitials = (name: string): string => {
-    return name
-      .split(' ')
-      .map(n => n[0])
-      .join('')
-      .toUpperCase()
-      .slice(0, 2);
-  };
-
   return (
     <Card className={`glass-card hover:shadow-lg transition-all duration-200 ${className}`}>
       <CardHeader>
@@ -53,47 +43,13 @@ export const FamilyCard: React.FC<FamilyCardProps> = ({ family, className }) =>
       
       <CardContent>
         <div className="space-y-4">
-          {/* Family members preview */}
-          <div className="flex items-center justify-between">
-            <div className="flex items-center space-x-2">
-              <Users className="h-4 w-4 text-muted-foreground" />
-              <span className="text-sm text-muted-foreground">
-                {family.members?.length || 0} member{(family.members?.length || 0) !== 1 ? 's' : ''}
-              </span>
-            </div>
+          {/* Families are listed without their members; the detail page pages those in */}
+          <div className="flex items-center justify-end">
             <Badge variant="secondary" className="text-xs">
               Created {formatDate(family.created_at)}
             </Badge>
           </div>
 
-          {/* Member avatars */}
-          {(family.members?.length || 0) > 0 && (
-            <div className="flex items-center space-x-2">
-              <div className="flex -space-x-2">
-                {family.members?.slice(0, 4).map((member) => (
-                  <Avatar key={member.id} className="h-8 w-8 border-2 border-white">
-                    <AvatarFallback className="text-xs bg-primary text-primary-foreground">
-                      {getInitials(member.full_name)}
-                    </AvatarFallback>
-                  </Avatar>
-                ))}
-                {(family.members?.length || 0) > 4 && (
-                  <div className="h-8 w-8 rounded-full bg-muted border-2 border-white flex items-center justify-center">
-                    <span className="text-xs text-muted-foreground">
-                      +{(family.members?.length || 0) - 4}
-                    </span>
-                  </div>
-                )}
-              </div>
-              {(family.members?.length || 0) > 0 && (
-                <span className="text-xs text-muted-foreground">
-                  {family.members?.slice(0, 2).map(m => m.full_name.split(' ')[0]).join(', ')}
-                  {(family.members?.length || 0) > 2 && ` and ${(family.members?.length || 0) - 2} more`}
-                </span>
-              )}
-            </div>
-          )}
-
           {/* Action button */}
           <Button asChild className="w-full mt-4">
             <Link to={`/family/${family.id}`}>
@@ -4,13 +4,16 @@ import { CreateFamilyRequest, AddFamilyMemberRequest, AddEventRequest, UpdateFam
 export const useFamily = () => {
   const {
     families,
+    familiesCursor,
     currentFamily,
     loading,
     error,
     fetchFamilies,
+    fetchMoreFamilies,
     createFamily,
     selectFamily,
     fetchFamily,
+    fetchMemberEvents,
     addFamilyMember,
     removeFamilyMember,
     updateFamilyMember,
@@ -30,9 +33,11 @@ export const useFamily = () => {
     
     // Actions
     fetchFamilies,
+    fetchMoreFamilies,
     createFamily,
     selectFamily,
     fetchFamily,
+    fetchMemberEvents,
     addFamilyMember,
     removeFamilyMember,
     updateFamilyMember,
@@ -44,6 +49,7 @@ export const useFamily = () => {
     
     // Computed
     hasFamilies: families.length > 0,
+    hasMoreFamilies: familiesCursor !== null,
     isLoading: loading,
   };
 };
\ No newline at end of file
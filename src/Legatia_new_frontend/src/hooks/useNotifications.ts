@@ -3,10 +3,12 @@ import { useNotificationStore } from '../stores/notificationStore';
 export const useNotifications = () => {
   const {
     notifications,
+    notificationsCursor,
     unreadCount,
     loading,
     error,
     fetchNotifications,
+    fetchMoreNotifications,
     fetchUnreadCount,
     markAsRead,
     markAllAsRead,
@@ -23,6 +25,7 @@ export const useNotifications = () => {
     
     // Actions
     fetchNotifications,
+    fetchMoreNotifications,
     fetchUnreadCount,
     markAsRead,
     markAllAsRead,
@@ -31,6 +34,7 @@ export const useNotifications = () => {
     
     // Computed
     hasNotifications: notifications.length > 0,
+    hasMoreNotifications: notificationsCursor !== null,
     hasUnread: unreadCount > 0,
     isLoading: loading,
   };
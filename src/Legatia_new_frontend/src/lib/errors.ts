@@ -0,0 +1,42 @@
+import { BackendError, FamilyRole } from '../types';
+
+function roleName(role: FamilyRole): string {
+  return Object.keys(role)[0].replace(/([a-z])([A-Z])/g, '$1-$2').toLowerCase();
+}
+
+function readable(reason: string): string {
+  return reason.replace(/_/g, ' ');
+}
+
+// Turn a backend error into a message that can be shown to the user
+export function describeBackendError(error: BackendError): string {
+  if ('Unauthenticated' in error) {
+    return 'Please log in to continue';
+  }
+  if ('NotFound' in error) {
+    return `No ${error.NotFound.kind} found with ID ${error.NotFound.id}`;
+  }
+  if ('Unauthorized' in error) {
+    const [role] = error.Unauthorized.required_role;
+    return role ? `This requires the ${roleName(role)} role` : 'You are not allowed to do this';
+  }
+  if ('Validation' in error) {
+    return `Invalid ${readable(error.Validation.field)}: ${readable(error.Validation.reason)}`;
+  }
+  if ('Malformed' in error) {
+    const { field, line, reason } = error.Malformed;
+    return line > 0n ? `${field}, line ${line}: ${reason}` : `${field}: ${reason}`;
+  }
+  if ('Conflict' in error) {
+    return error.Conflict.reason;
+  }
+  if ('RateLimited' in error) {
+    return `Too many requests, try again in ${error.RateLimited.retry_after_secs} seconds`;
+  }
+  return `A canister call failed: ${error.ExternalCall.reason}`;
+}
+
+// Whether the error means the caller has not created a profile yet
+export function isMissingProfile(error: BackendError): boolean {
+  return 'NotFound' in error && error.NotFound.kind === 'profile';
+}
@@ -0,0 +1,52 @@
+import { BackendError, EventFilter, InvitationFilter, NotificationFilter, Page, PageRequest, PageResult } from '../types';
+
+export const PAGE_SIZE = 20;
+
+// The largest page the backend returns
+export const MAX_PAGE_SIZE = 100;
+
+export function pageRequest(cursor: string | null = null, limit: number = PAGE_SIZE): PageRequest {
+  return { cursor: cursor === null ? [] : [cursor], limit: [limit] };
+}
+
+export function nextCursor<T>(page: Page<T>): string | null {
+  return page.next_cursor[0] ?? null;
+}
+
+export const noNotificationFilter: NotificationFilter = {
+  notification_type: [],
+  unread_only: [],
+  created_after: [],
+  created_before: [],
+};
+
+export const noInvitationFilter: InvitationFilter = {
+  status: [],
+  family_id: [],
+  created_after: [],
+  created_before: [],
+};
+
+export function memberEventsFilter(memberId: string): EventFilter {
+  return { member_id: [memberId], event_type: [], created_after: [], created_before: [] };
+}
+
+// Follow the cursors to the last page, for views that need every record of one
+// family or member
+export async function fetchAllPages<T>(
+  fetchPage: (page: PageRequest) => Promise<PageResult<T>>
+): Promise<{ Ok: T[] } | { Err: BackendError }> {
+  const items: T[] = [];
+  let cursor: string | null = null;
+
+  do {
+    const result = await fetchPage(pageRequest(cursor, MAX_PAGE_SIZE));
+    if ('Err' in result) {
+      return result;
+    }
+    items.push(...result.Ok.items);
+    cursor = nextCursor(result.Ok);
+  } while (cursor !== null);
+
+  return { Ok: items };
+}
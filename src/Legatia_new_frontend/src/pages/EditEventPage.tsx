@@ -1,4 +1,4 @@
-import React, { useEffect } from 'react';
+import React, { useEffect, useState } from 'react';
 import { useParams, useNavigate } from 'react-router-dom';
 import { useForm } from 'react-hook-form';
 import { zodResolver } from '@hookform/resolvers/zod';
@@ -27,7 +27,8 @@ type EditEventFormData = z.infer<typeof editEventSchema>;
 export const EditEventPage: React.FC = () => {
   const { id: familyId, memberId, eventId } = useParams<{ id: string; memberId: string; eventId: string }>();
   const navigate = useNavigate();
-  const { currentFamily, loading, error, fetchFamily, updateMemberEvent, clearError } = useFamily();
+  const { currentFamily, loading, error, fetchFamily, fetchMemberEvents, updateMemberEvent, clearError } = useFamily();
+  const [eventsLoaded, setEventsLoaded] = useState(false);
 
   const {
     register,
@@ -51,6 +52,14 @@ export const EditEventPage: React.FC = () => {
     }
   }, [error, clearError]);
 
+  // Members are loaded without their events; fetch this member's once the family is in
+  const familyLoaded = currentFamily?.id === familyId;
+  useEffect(() => {
+    if (familyId && memberId && familyLoaded) {
+      fetchMemberEvents(familyId, memberId).finally(() => setEventsLoaded(true));
+    }
+  }, [familyId, memberId, familyLoaded, fetchMemberEvents]);
+
   const currentMember = currentFamily?.members?.find(m => m.id === memberId);
   const currentEvent = currentMember?.events?.find(e => e.id === eventId);
 
@@ -146,7 +155,7 @@ export const EditEventPage: React.FC = () => {
     );
   }
 
-  if (!currentEvent && !loading) {
+  if (!currentEvent && !loading && eventsLoaded) {
     return (
       <div className="text-center py-12">
         <h2 className="text-2xl font-bold text-gray-900 mb-4">Event Not Found</h2>
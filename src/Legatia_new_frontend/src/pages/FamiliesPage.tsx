@@ -8,7 +8,7 @@ import { useFamily } from '../hooks/useFamily';
 import { Plus, Search, Users } from 'lucide-react';
 
 export const FamiliesPage: React.FC = () => {
-  const { families, loading, error, fetchFamilies, clearError } = useFamily();
+  const { families, loading, error, fetchFamilies, fetchMoreFamilies, hasMoreFamilies, clearError } = useFamily();
   const [searchTerm, setSearchTerm] = useState('');
 
   useEffect(() => {
@@ -114,10 +114,16 @@ export const FamiliesPage: React.FC = () => {
       )}
 
       {/* Loading more indicator */}
-      {loading && families.length > 0 && (
+      {loading && families.length > 0 ? (
         <div className="flex justify-center py-4">
           <LoadingSpinner size="sm" />
         </div>
+      ) : hasMoreFamilies && (
+        <div className="flex justify-center py-4">
+          <Button variant="outline" onClick={() => fetchMoreFamilies()}>
+            Load More Families
+          </Button>
+        </div>
       )}
     </div>
   );
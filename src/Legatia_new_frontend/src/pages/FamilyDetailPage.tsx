@@ -258,11 +258,6 @@ export const FamilyDetailPage: React.FC = () => {
                       )}
 
                       <div className="flex flex-wrap gap-1 mt-2">
-                        {member.events && member.events.length > 0 && (
-                          <Badge variant="outline" className="text-xs">
-                            {member.events.length} event{member.events.length !== 1 ? 's' : ''}
-                          </Badge>
-                        )}
                         {(!member.profile_principal || member.profile_principal.length === 0) && (
                           <Badge variant="secondary" className="text-xs">
                             Ghost Profile
@@ -281,18 +276,17 @@ export const FamilyDetailPage: React.FC = () => {
                             Add Event
                           </Link>
                         </Button>
-                        {member.events && member.events.length > 0 && (
-                          <Button 
-                            size="sm" 
-                            variant="ghost" 
-                            className="text-xs h-7"
-                            asChild
-                          >
-                            <Link to={`/family/${currentFamily.id}/member/${member.id}/events`}>
-                              View Events
-                            </Link>
-                          </Button>
-                        )}
+                        {/* Events are fetched on the events page, so the link is always shown */}
+                        <Button 
+                          size="sm" 
+                          variant="ghost" 
+                          className="text-xs h-7"
+                          asChild
+                        >
+                          <Link to={`/family/${currentFamily.id}/member/${member.id}/events`}>
+                            View Events
+                          </Link>
+                        </Button>
                         {/* Edit button for ghost profiles (admin only) */}
                         {isAdmin && (!member.profile_principal || member.profile_principal.length === 0) ? (
                           <Button 
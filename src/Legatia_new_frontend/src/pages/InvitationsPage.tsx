@@ -5,6 +5,8 @@ import { Badge } from '../components/ui/badge';
 import { LoadingSpinner } from '../components/layout/LoadingSpinner';
 import { useAuth } from '../hooks/useAuth';
 import { FamilyInvitation, ProcessInvitationRequest } from '../types';
+import { describeBackendError } from '../lib/errors';
+import { nextCursor, noInvitationFilter, pageRequest } from '../lib/pagination';
 import { 
   Mail, 
   User, 
@@ -22,6 +24,10 @@ export const InvitationsPage: React.FC = () => {
   const { actor } = useAuth();
   const [receivedInvitations, setReceivedInvitations] = useState<FamilyInvitation[]>([]);
   const [sentInvitations, setSentInvitations] = useState<FamilyInvitation[]>([]);
+  // Cursors of the next page of each list; null once every page is loaded
+  const [receivedCursor, setReceivedCursor] = useState<string | null>(null);
+  const [sentCursor, setSentCursor] = useState<string | null>(null);
+  const [loadingMore, setLoadingMore] = useState(false);
   const [loading, setLoading] = useState(true);
   const [processing, setProcessing] = useState(false);
   const [activeTab, setActiveTab] = useState<'received' | 'sent'>('received');
@@ -36,20 +42,22 @@ export const InvitationsPage: React.FC = () => {
     setLoading(true);
     try {
       const [receivedResult, sentResult] = await Promise.all([
-        actor.get_my_invitations(),
-        actor.get_sent_invitations()
+        actor.get_my_invitations(noInvitationFilter, pageRequest()),
+        actor.get_sent_invitations(noInvitationFilter, pageRequest())
       ]);
 
       if ('Ok' in receivedResult) {
-        setReceivedInvitations(receivedResult.Ok);
+        setReceivedInvitations(receivedResult.Ok.items);
+        setReceivedCursor(nextCursor(receivedResult.Ok));
       } else {
-        toast.error(`Failed to load received invitations: ${receivedResult.Err}`);
+        toast.error(`Failed to load received invitations: ${describeBackendError(receivedResult.Err)}`);
       }
 
       if ('Ok' in sentResult) {
-        setSentInvitations(sentResult.Ok);
+        setSentInvitations(sentResult.Ok.items);
+        setSentCursor(nextCursor(sentResult.Ok));
       } else {
-        toast.error(`Failed to load sent invitations: ${sentResult.Err}`);
+        toast.error(`Failed to load sent invitations: ${describeBackendError(sentResult.Err)}`);
       }
     } catch (error) {
       console.error('Error loading invitations:', error);
@@ -59,6 +67,38 @@ export const InvitationsPage: React.FC = () => {
     }
   };
 
+  // Append the next page of the invitations in the active tab
+  const loadMoreInvitations = async () => {
+    const received = activeTab === 'received';
+    const cursor = received ? receivedCursor : sentCursor;
+    if (!actor || cursor === null || loadingMore) return;
+
+    setLoadingMore(true);
+    try {
+      const result = received
+        ? await actor.get_my_invitations(noInvitationFilter, pageRequest(cursor))
+        : await actor.get_sent_invitations(noInvitationFilter, pageRequest(cursor));
+
+      if ('Ok' in result) {
+        const page = result.Ok;
+        if (received) {
+          setReceivedInvitations(invitations => [...invitations, ...page.items]);
+          setReceivedCursor(nextCursor(page));
+        } else {
+          setSentInvitations(invitations => [...invitations, ...page.items]);
+          setSentCursor(nextCursor(page));
+        }
+      } else {
+        toast.error(`Failed to load invitations: ${describeBackendError(result.Err)}`);
+      }
+    } catch (error) {
+      console.error('Error loading more invitations:', error);
+      toast.error('Failed to load invitations');
+    } finally {
+      setLoadingMore(false);
+    }
+  };
+
   const processInvitation = async (invitationId: string, accept: boolean) => {
     if (!actor || processing) return;
 
@@ -76,7 +116,7 @@ export const InvitationsPage: React.FC = () => {
         // Reload invitations to reflect the change
         await loadInvitations();
       } else {
-        toast.error(`Failed to process invitation: ${result.Err}`);
+        toast.error(`Failed to process invitation: ${describeBackendError(result.Err)}`);
       }
     } catch (error) {
       console.error('Error processing invitation:', error);
@@ -193,6 +233,7 @@ export const InvitationsPage: React.FC = () => {
   }
 
   const currentInvitations = activeTab === 'received' ? receivedInvitations : sentInvitations;
+  const hasMoreInvitations = (activeTab === 'received' ? receivedCursor : sentCursor) !== null;
 
   return (
     <div className="space-y-6">
@@ -215,7 +256,7 @@ export const InvitationsPage: React.FC = () => {
           }`}
         >
           <Inbox className="h-4 w-4 mr-2 inline" />
-          Received ({receivedInvitations.length})
+          Received ({receivedInvitations.length}{receivedCursor !== null && '+'})
         </button>
         <button
           onClick={() => setActiveTab('sent')}
@@ -226,7 +267,7 @@ export const InvitationsPage: React.FC = () => {
           }`}
         >
           <Send className="h-4 w-4 mr-2 inline" />
-          Sent ({sentInvitations.length})
+          Sent ({sentInvitations.length}{sentCursor !== null && '+'})
         </button>
       </div>
 
@@ -251,6 +292,13 @@ export const InvitationsPage: React.FC = () => {
           {currentInvitations.map((invitation) => 
             renderInvitation(invitation, activeTab === 'received')
           )}
+          {hasMoreInvitations && (
+            <div className="flex justify-center py-4">
+              <Button variant="outline" onClick={loadMoreInvitations} disabled={loadingMore}>
+                {loadingMore ? 'Loading...' : 'Load More Invitations'}
+              </Button>
+            </div>
+          )}
         </div>
       )}
     </div>
@@ -25,6 +25,8 @@ export const NotificationsPage: React.FC = () => {
     loading,
     error,
     fetchNotifications,
+    fetchMoreNotifications,
+    hasMoreNotifications,
     markAsRead,
     markAllAsRead,
     clearError,
@@ -196,6 +198,13 @@ export const NotificationsPage: React.FC = () => {
               </CardContent>
             </Card>
           ))}
+          {hasMoreNotifications && (
+            <div className="flex justify-center py-4">
+              <Button variant="outline" onClick={() => fetchMoreNotifications()} disabled={loading}>
+                Load More Notifications
+              </Button>
+            </div>
+          )}
         </div>
       )}
     </div>
@@ -7,6 +7,7 @@ import { Badge } from '../components/ui/badge';
 import { LoadingSpinner } from '../components/layout/LoadingSpinner';
 import { useAuth } from '../hooks/useAuth';
 import { UserSearchMatch } from '../types';
+import { describeBackendError } from '../lib/errors';
 import { 
   Search, 
   User, 
@@ -43,7 +44,7 @@ export const UserSearchPage: React.FC = () => {
           setSearchResults(result.Ok);
           setHasSearched(true);
         } else {
-          toast.error(`Search failed: ${result.Err}`);
+          toast.error(`Search failed: ${describeBackendError(result.Err)}`);
           setSearchResults([]);
           setHasSearched(true);
         }
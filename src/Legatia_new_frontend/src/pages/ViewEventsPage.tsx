@@ -23,7 +23,7 @@ export const ViewEventsPage: React.FC = () => {
   const { id: familyId, memberId } = useParams<{ id: string; memberId: string }>();
   const navigate = useNavigate();
   const { user } = useAuth();
-  const { currentFamily, loading, error, fetchFamily, clearError } = useFamily();
+  const { currentFamily, loading, error, fetchFamily, fetchMemberEvents, clearError } = useFamily();
   const [events, setEvents] = useState<FamilyEvent[]>([]);
   const [loadingEvents, setLoadingEvents] = useState(false);
 
@@ -41,6 +41,15 @@ export const ViewEventsPage: React.FC = () => {
     }
   }, [error, clearError]);
 
+  // Members are loaded without their events; fetch this member's once the family is in
+  const familyLoaded = currentFamily?.id === familyId;
+  useEffect(() => {
+    if (familyId && memberId && familyLoaded) {
+      setLoadingEvents(true);
+      fetchMemberEvents(familyId, memberId).finally(() => setLoadingEvents(false));
+    }
+  }, [familyId, memberId, familyLoaded, fetchMemberEvents]);
+
   const currentMember = currentFamily?.members?.find(m => m.id === memberId);
 
   useEffect(() => {
@@ -194,7 +203,11 @@ export const ViewEventsPage: React.FC = () => {
           </CardTitle>
         </CardHeader>
         <CardContent>
-          {sortedEvents.length > 0 ? (
+          {loadingEvents ? (
+            <div className="flex justify-center py-8">
+              <LoadingSpinner size="md" text="Loading events..." />
+            </div>
+          ) : sortedEvents.length > 0 ? (
             <div className="space-y-4">
               {sortedEvents.map((event) => (
                 <Card key={event.id} className="p-4 border-l-4 border-l-primary">
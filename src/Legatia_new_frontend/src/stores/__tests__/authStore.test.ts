@@ -209,7 +209,7 @@ describe('authStore', () => {
         birth_country: 'USA',
       }
       
-      mockBackendActor.create_profile?.mockResolvedValue({ Err: 'Profile creation failed' })
+      mockBackendActor.create_profile?.mockResolvedValue({ Err: { Validation: { field: 'full_name', reason: 'too_long' } } })
       
       const { result } = renderHook(() => useAuthStore())
       
@@ -217,7 +217,7 @@ describe('authStore', () => {
         await result.current.createProfile(profileData)
       })
       
-      expect(result.current.error).toBe('Profile creation failed')
+      expect(result.current.error).toBe('Invalid full name: too long')
       expect(result.current.user).toBe(null)
     })
 
@@ -277,7 +277,7 @@ describe('authStore', () => {
         full_name: ['Jane Doe'],
       }
       
-      mockBackendActor.update_profile?.mockResolvedValue({ Err: 'Update failed' })
+      mockBackendActor.update_profile?.mockResolvedValue({ Err: { Validation: { field: 'birthday', reason: 'invalid_date' } } })
       
       const { result } = renderHook(() => useAuthStore())
       
@@ -285,7 +285,7 @@ describe('authStore', () => {
         await result.current.updateProfile(updateData)
       })
       
-      expect(result.current.error).toBe('Update failed')
+      expect(result.current.error).toBe('Invalid birthday: invalid date')
     })
   })
 
@@ -312,7 +312,7 @@ describe('authStore', () => {
         actor: mockBackendActor as any,
       })
       
-      mockBackendActor.get_profile?.mockResolvedValue({ Err: 'Profile not found' })
+      mockBackendActor.get_profile?.mockResolvedValue({ Err: { NotFound: { kind: 'profile', id: mockPrincipal.toText() } } })
       
       const { result } = renderHook(() => useAuthStore())
       
@@ -321,8 +321,26 @@ describe('authStore', () => {
       })
       
       expect(result.current.user).toBe(null)
+      expect(result.current.error).toBe(null)
       expect(result.current.loading).toBe(false)
     })
+
+    it('should report other profile errors', async () => {
+      useAuthStore.setState({
+        actor: mockBackendActor as any,
+      })
+      
+      mockBackendActor.get_profile?.mockResolvedValue({ Err: { Unauthenticated: null } })
+      
+      const { result } = renderHook(() => useAuthStore())
+      
+      await act(async () => {
+        await result.current.fetchProfile()
+      })
+      
+      expect(result.current.user).toBe(null)
+      expect(result.current.error).toBe('Please log in to continue')
+    })
   })
 
   describe('Utility functions', () => {
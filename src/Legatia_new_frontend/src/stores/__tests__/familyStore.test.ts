@@ -4,6 +4,7 @@ import { useFamilyStore } from '../familyStore'
 import { useAuthStore } from '../authStore'
 import { mockBackendActor, mockPrincipal } from '../../test/__mocks__/auth'
 import { createMockFamily } from '../../test/utils/test-utils'
+import { memberEventsFilter } from '../../lib/pagination'
 
 // Mock the auth store
 vi.mock('../authStore', () => ({
@@ -15,11 +16,37 @@ vi.mock('../authStore', () => ({
 describe('familyStore', () => {
   const mockFamily = createMockFamily()
   const mockFamilies = [mockFamily, createMockFamily({ id: 'family-2', name: 'Second Family' })]
+  const mockMember = (id: string) => ({
+    id,
+    profile_principal: [] as [],
+    full_name: 'Jane Doe',
+    surname_at_birth: 'Smith',
+    sex: 'Female',
+    birthday: [] as [],
+    birth_city: [] as [],
+    birth_country: [] as [],
+    death_date: [] as [],
+    relationship_to_admin: 'Sister',
+    events: [],
+    created_at: BigInt(Date.now() * 1000000),
+    created_by: mockPrincipal,
+  })
+  const mockEvent = {
+    id: 'event-1',
+    member_id: 'member-1',
+    title: 'Birthday',
+    description: 'Member birthday celebration',
+    event_date: '2023-01-01',
+    event_type: 'Birthday',
+    created_at: BigInt(Date.now() * 1000000),
+    created_by: mockPrincipal,
+  }
 
   beforeEach(() => {
     // Reset store state
     useFamilyStore.setState({
       families: [],
+      familiesCursor: null,
       currentFamily: null,
       loading: false,
       error: null,
@@ -52,6 +79,7 @@ describe('familyStore', () => {
       const { result } = renderHook(() => useFamilyStore())
       
       expect(result.current.families).toEqual([])
+      expect(result.current.familiesCursor).toBe(null)
       expect(result.current.currentFamily).toBe(null)
       expect(result.current.loading).toBe(false)
       expect(result.current.error).toBe(null)
@@ -60,7 +88,7 @@ describe('familyStore', () => {
 
   describe('fetchFamilies', () => {
     it('should fetch families successfully', async () => {
-      mockBackendActor.get_user_families?.mockResolvedValue({ Ok: mockFamilies })
+      mockBackendActor.get_user_families?.mockResolvedValue({ Ok: { items: mockFamilies, next_cursor: ['family-2'] } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -68,14 +96,43 @@ describe('familyStore', () => {
         await result.current.fetchFamilies()
       })
       
-      expect(mockBackendActor.get_user_families).toHaveBeenCalledOnce()
+      expect(mockBackendActor.get_user_families).toHaveBeenCalledWith({ cursor: [], limit: [20] })
       expect(result.current.families).toEqual(mockFamilies)
+      expect(result.current.familiesCursor).toBe('family-2')
       expect(result.current.loading).toBe(false)
       expect(result.current.error).toBe(null)
     })
 
+    it('should append the next page of families', async () => {
+      const thirdFamily = createMockFamily({ id: 'family-3', name: 'Third Family' })
+      useFamilyStore.setState({ families: mockFamilies, familiesCursor: 'family-2' })
+      mockBackendActor.get_user_families?.mockResolvedValue({ Ok: { items: [thirdFamily], next_cursor: [] } })
+      
+      const { result } = renderHook(() => useFamilyStore())
+      
+      await act(async () => {
+        await result.current.fetchMoreFamilies()
+      })
+      
+      expect(mockBackendActor.get_user_families).toHaveBeenCalledWith({ cursor: ['family-2'], limit: [20] })
+      expect(result.current.families).toEqual([...mockFamilies, thirdFamily])
+      expect(result.current.familiesCursor).toBe(null)
+    })
+
+    it('should not fetch past the last page of families', async () => {
+      useFamilyStore.setState({ families: mockFamilies, familiesCursor: null })
+      
+      const { result } = renderHook(() => useFamilyStore())
+      
+      await act(async () => {
+        await result.current.fetchMoreFamilies()
+      })
+      
+      expect(mockBackendActor.get_user_families).not.toHaveBeenCalled()
+    })
+
     it('should handle fetch families failure', async () => {
-      mockBackendActor.get_user_families?.mockResolvedValue({ Err: 'Failed to fetch families' })
+      mockBackendActor.get_user_families?.mockResolvedValue({ Err: { Unauthenticated: null } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -84,7 +141,7 @@ describe('familyStore', () => {
       })
       
       expect(result.current.families).toEqual([])
-      expect(result.current.error).toBe('Failed to fetch families')
+      expect(result.current.error).toBe('Please log in to continue')
       expect(result.current.loading).toBe(false)
     })
 
@@ -145,7 +202,7 @@ describe('familyStore', () => {
         is_visible: [true],
       }
       
-      mockBackendActor.create_family?.mockResolvedValue({ Err: 'Family creation failed' })
+      mockBackendActor.create_family?.mockResolvedValue({ Err: { NotFound: { kind: 'profile', id: mockPrincipal.toText() } } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -153,7 +210,7 @@ describe('familyStore', () => {
         await result.current.createFamily(createData)
       })
       
-      expect(result.current.error).toBe('Family creation failed')
+      expect(result.current.error).toBe('You must create a user profile before creating a family. Please complete your profile first.')
       expect(result.current.families).toEqual([])
     })
   })
@@ -186,9 +243,12 @@ describe('familyStore', () => {
   })
 
   describe('fetchFamily', () => {
-    it('should fetch specific family successfully', async () => {
+    it('should fetch specific family with every page of its members', async () => {
       useFamilyStore.setState({ families: mockFamilies })
       mockBackendActor.get_family?.mockResolvedValue({ Ok: mockFamily })
+      mockBackendActor.get_family_members
+        ?.mockResolvedValueOnce({ Ok: { items: [mockMember('member-1')], next_cursor: ['member-1'] } })
+        .mockResolvedValueOnce({ Ok: { items: [mockMember('member-2')], next_cursor: [] } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -197,12 +257,29 @@ describe('familyStore', () => {
       })
       
       expect(mockBackendActor.get_family).toHaveBeenCalledWith(mockFamily.id)
-      expect(result.current.currentFamily).toEqual(mockFamily)
+      expect(mockBackendActor.get_family_members).toHaveBeenNthCalledWith(1, mockFamily.id, { cursor: [], limit: [100] })
+      expect(mockBackendActor.get_family_members).toHaveBeenNthCalledWith(2, mockFamily.id, { cursor: ['member-1'], limit: [100] })
+      expect(result.current.currentFamily?.members.map(m => m.id)).toEqual(['member-1', 'member-2'])
+      expect(result.current.families[0]).toEqual(mockFamily)
       expect(result.current.loading).toBe(false)
     })
 
+    it('should report a failed member page', async () => {
+      mockBackendActor.get_family?.mockResolvedValue({ Ok: mockFamily })
+      mockBackendActor.get_family_members?.mockResolvedValueOnce({ Err: { Unauthorized: { required_role: [{ Viewer: null }] } } })
+      
+      const { result } = renderHook(() => useFamilyStore())
+      
+      await act(async () => {
+        await result.current.fetchFamily(mockFamily.id)
+      })
+      
+      expect(result.current.currentFamily).toBe(null)
+      expect(result.current.error).toBe('This requires the viewer role')
+    })
+
     it('should handle fetch family failure', async () => {
-      mockBackendActor.get_family?.mockResolvedValue({ Err: 'Family not found' })
+      mockBackendActor.get_family?.mockResolvedValue({ Err: { NotFound: { kind: 'family', id: 'missing-family' } } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -210,7 +287,31 @@ describe('familyStore', () => {
         await result.current.fetchFamily('non-existent-id')
       })
       
-      expect(result.current.error).toBe('Family not found')
+      expect(result.current.error).toBe('No family found with ID missing-family')
+    })
+  })
+
+  describe('fetchMemberEvents', () => {
+    it('should fill in the events of one member of the current family', async () => {
+      useFamilyStore.setState({
+        currentFamily: { ...mockFamily, members: [mockMember('member-1'), mockMember('member-2')] } as any,
+      })
+      mockBackendActor.get_family_events?.mockResolvedValue({ Ok: { items: [mockEvent], next_cursor: [] } })
+      
+      const { result } = renderHook(() => useFamilyStore())
+      
+      await act(async () => {
+        await result.current.fetchMemberEvents(mockFamily.id, 'member-1')
+      })
+      
+      expect(mockBackendActor.get_family_events).toHaveBeenCalledWith(
+        mockFamily.id,
+        memberEventsFilter('member-1'),
+        { cursor: [], limit: [100] }
+      )
+      expect(result.current.currentFamily?.members[0].events).toEqual([mockEvent])
+      expect(result.current.currentFamily?.members[1].events).toEqual([])
+      expect(result.current.loading).toBe(false)
     })
   })
 
@@ -224,23 +325,7 @@ describe('familyStore', () => {
         relationship_to_admin: 'Sister',
       }
       
-      const mockMember = {
-        id: 'member-1',
-        profile_principal: [],
-        full_name: 'Jane Doe',
-        surname_at_birth: 'Smith',
-        sex: 'Female',
-        birthday: [],
-        birth_city: [],
-        birth_country: [],
-        death_date: [],
-        relationship_to_admin: 'Sister',
-        events: [],
-        created_at: BigInt(Date.now() * 1000000),
-        created_by: mockPrincipal,
-      }
-      
-      mockBackendActor.add_family_member?.mockResolvedValue({ Ok: mockMember })
+      mockBackendActor.add_family_member?.mockResolvedValue({ Ok: mockMember('member-1') })
       mockBackendActor.get_family?.mockResolvedValue({ Ok: mockFamily })
       
       const { result } = renderHook(() => useFamilyStore())
@@ -262,7 +347,7 @@ describe('familyStore', () => {
         relationship_to_admin: 'Sister',
       }
       
-      mockBackendActor.add_family_member?.mockResolvedValue({ Err: 'Failed to add member' })
+      mockBackendActor.add_family_member?.mockResolvedValue({ Err: { Unauthorized: { required_role: [{ Contributor: null }] } } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -270,7 +355,7 @@ describe('familyStore', () => {
         await result.current.addFamilyMember(memberData)
       })
       
-      expect(result.current.error).toBe('Failed to add member')
+      expect(result.current.error).toBe('This requires the contributor role')
     })
   })
 
@@ -290,7 +375,7 @@ describe('familyStore', () => {
     })
 
     it('should handle remove family member failure', async () => {
-      mockBackendActor.remove_family_member?.mockResolvedValue({ Err: 'Failed to remove member' })
+      mockBackendActor.remove_family_member?.mockResolvedValue({ Err: { Unauthorized: { required_role: [{ CoAdmin: null }] } } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -298,7 +383,7 @@ describe('familyStore', () => {
         await result.current.removeFamilyMember(mockFamily.id, 'member-id')
       })
       
-      expect(result.current.error).toBe('Failed to remove member')
+      expect(result.current.error).toBe('This requires the co-admin role')
     })
   })
 
@@ -313,19 +398,8 @@ describe('familyStore', () => {
         event_type: 'Birthday',
       }
       
-      const mockEvent = {
-        id: 'event-1',
-        member_id: 'member-1',
-        title: 'Birthday',
-        description: 'Member birthday celebration',
-        event_date: '2023-01-01',
-        event_type: 'Birthday',
-        created_at: BigInt(Date.now() * 1000000),
-        created_by: mockPrincipal,
-      }
-      
       mockBackendActor.add_member_event?.mockResolvedValue({ Ok: mockEvent })
-      mockBackendActor.get_family?.mockResolvedValue({ Ok: mockFamily })
+      mockBackendActor.get_family_events?.mockResolvedValue({ Ok: { items: [mockEvent], next_cursor: [] } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -334,7 +408,11 @@ describe('familyStore', () => {
       })
       
       expect(mockBackendActor.add_member_event).toHaveBeenCalledWith(eventData)
-      expect(mockBackendActor.get_family).toHaveBeenCalledWith(mockFamily.id)
+      expect(mockBackendActor.get_family_events).toHaveBeenCalledWith(
+        mockFamily.id,
+        memberEventsFilter('member-1'),
+        { cursor: [], limit: [100] }
+      )
     })
   })
 
@@ -359,7 +437,7 @@ describe('familyStore', () => {
     })
 
     it('should handle toggle visibility failure', async () => {
-      mockBackendActor.toggle_family_visibility?.mockResolvedValue({ Err: 'Failed to update visibility' })
+      mockBackendActor.toggle_family_visibility?.mockResolvedValue({ Err: { Unauthorized: { required_role: [] } } })
       
       const { result } = renderHook(() => useFamilyStore())
       
@@ -367,7 +445,7 @@ describe('familyStore', () => {
         await result.current.toggleFamilyVisibility(mockFamily.id, false)
       })
       
-      expect(result.current.error).toBe('Failed to update visibility')
+      expect(result.current.error).toBe('You are not allowed to do this')
     })
   })
 
@@ -387,6 +465,7 @@ describe('familyStore', () => {
     it('should reset store', () => {
       useFamilyStore.setState({
         families: mockFamilies,
+        familiesCursor: 'family-2',
         currentFamily: mockFamily,
         loading: true,
         error: 'Some error',
@@ -4,6 +4,7 @@ import { useNotificationStore } from '../notificationStore'
 import { useAuthStore } from '../authStore'
 import { mockBackendActor, mockPrincipal } from '../../test/__mocks__/auth'
 import { Notification } from '../../types'
+import { noNotificationFilter } from '../../lib/pagination'
 
 // Mock the auth store
 vi.mock('../authStore', () => ({
@@ -42,6 +43,7 @@ describe('notificationStore', () => {
     // Reset store state
     useNotificationStore.setState({
       notifications: [],
+      notificationsCursor: null,
       unreadCount: 0,
       loading: false,
       error: null,
@@ -82,7 +84,8 @@ describe('notificationStore', () => {
 
   describe('fetchNotifications', () => {
     it('should fetch notifications successfully', async () => {
-      mockBackendActor.get_my_notifications?.mockResolvedValue({ Ok: mockNotifications })
+      mockBackendActor.get_my_notifications?.mockResolvedValue({ Ok: { items: mockNotifications, next_cursor: ['notif-2'] } })
+      mockBackendActor.get_unread_notification_count?.mockResolvedValue({ Ok: BigInt(3) })
       
       const { result } = renderHook(() => useNotificationStore())
       
@@ -90,15 +93,32 @@ describe('notificationStore', () => {
         await result.current.fetchNotifications()
       })
       
-      expect(mockBackendActor.get_my_notifications).toHaveBeenCalledOnce()
+      expect(mockBackendActor.get_my_notifications).toHaveBeenCalledWith(noNotificationFilter, { cursor: [], limit: [20] })
       expect(result.current.notifications).toEqual(mockNotifications)
-      expect(result.current.unreadCount).toBe(1) // Only one unread notification
+      expect(result.current.notificationsCursor).toBe('notif-2')
+      // The count covers every page, not just the one loaded
+      expect(result.current.unreadCount).toBe(3)
       expect(result.current.loading).toBe(false)
       expect(result.current.error).toBe(null)
     })
 
+    it('should append the next page of notifications', async () => {
+      useNotificationStore.setState({ notifications: [mockNotifications[0]], notificationsCursor: 'notif-1' })
+      mockBackendActor.get_my_notifications?.mockResolvedValue({ Ok: { items: [mockNotifications[1]], next_cursor: [] } })
+      
+      const { result } = renderHook(() => useNotificationStore())
+      
+      await act(async () => {
+        await result.current.fetchMoreNotifications()
+      })
+      
+      expect(mockBackendActor.get_my_notifications).toHaveBeenCalledWith(noNotificationFilter, { cursor: ['notif-1'], limit: [20] })
+      expect(result.current.notifications).toEqual(mockNotifications)
+      expect(result.current.notificationsCursor).toBe(null)
+    })
+
     it('should handle fetch notifications failure', async () => {
-      mockBackendActor.get_my_notifications?.mockResolvedValue({ Err: 'Failed to fetch notifications' })
+      mockBackendActor.get_my_notifications?.mockResolvedValue({ Err: { RateLimited: { retry_after_secs: BigInt(30) } } })
       
       const { result } = renderHook(() => useNotificationStore())
       
@@ -107,7 +127,7 @@ describe('notificationStore', () => {
       })
       
       expect(result.current.notifications).toEqual([])
-      expect(result.current.error).toBe('Failed to fetch notifications')
+      expect(result.current.error).toBe('Too many requests, try again in 30 seconds')
       expect(result.current.loading).toBe(false)
     })
 
@@ -154,7 +174,7 @@ describe('notificationStore', () => {
     })
 
     it('should handle fetch unread count failure silently', async () => {
-      mockBackendActor.get_unread_notification_count?.mockResolvedValue({ Err: 'Failed to fetch count' })
+      mockBackendActor.get_unread_notification_count?.mockResolvedValue({ Err: { Unauthenticated: null } })
       
       const { result } = renderHook(() => useNotificationStore())
       
@@ -218,7 +238,7 @@ describe('notificationStore', () => {
     })
 
     it('should handle mark as read failure', async () => {
-      mockBackendActor.mark_notification_read?.mockResolvedValue({ Err: 'Failed to mark as read' })
+      mockBackendActor.mark_notification_read?.mockResolvedValue({ Err: { NotFound: { kind: 'notification', id: 'notification-1' } } })
       
       const { result } = renderHook(() => useNotificationStore())
       
@@ -226,7 +246,7 @@ describe('notificationStore', () => {
         await result.current.markAsRead('notif-1')
       })
       
-      expect(result.current.error).toBe('Failed to mark as read')
+      expect(result.current.error).toBe('No notification found with ID notification-1')
     })
 
     it('should handle not authenticated state', async () => {
@@ -282,7 +302,7 @@ describe('notificationStore', () => {
     })
 
     it('should handle mark all as read failure', async () => {
-      mockBackendActor.mark_all_notifications_read?.mockResolvedValue({ Err: 'Failed to mark all as read' })
+      mockBackendActor.mark_all_notifications_read?.mockResolvedValue({ Err: { Unauthenticated: null } })
       
       const { result } = renderHook(() => useNotificationStore())
       
@@ -290,7 +310,7 @@ describe('notificationStore', () => {
         await result.current.markAllAsRead()
       })
       
-      expect(result.current.error).toBe('Failed to mark all as read')
+      expect(result.current.error).toBe('Please log in to continue')
       expect(result.current.loading).toBe(false)
     })
   })
@@ -311,6 +331,7 @@ describe('notificationStore', () => {
     it('should reset store', () => {
       useNotificationStore.setState({
         notifications: mockNotifications,
+        notificationsCursor: 'notif-2',
         unreadCount: 5,
         loading: true,
         error: 'Some error',
@@ -323,6 +344,7 @@ describe('notificationStore', () => {
       })
       
       expect(result.current.notifications).toEqual([])
+      expect(result.current.notificationsCursor).toBe(null)
       expect(result.current.unreadCount).toBe(0)
       expect(result.current.loading).toBe(false)
       expect(result.current.error).toBe(null)
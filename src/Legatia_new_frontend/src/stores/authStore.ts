@@ -1,6 +1,7 @@
 import { create } from 'zustand';
 import { Principal } from '@dfinity/principal';
 import { authService } from '../services/auth';
+import { describeBackendError, isMissingProfile } from '../lib/errors';
 import { UserProfile, CreateProfileRequest, UpdateProfileRequest, BackendActor } from '../types';
 
 interface AuthState {
@@ -158,7 +159,7 @@ export const useAuthStore = create<AuthState>((set, get) => ({
         });
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -190,7 +191,7 @@ export const useAuthStore = create<AuthState>((set, get) => ({
         });
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -217,12 +218,17 @@ export const useAuthStore = create<AuthState>((set, get) => ({
           user: result.Ok,
           loading: false 
         });
-      } else {
+      } else if (isMissingProfile(result.Err)) {
         // User doesn't have a profile yet - this is okay
         set({ 
           user: null,
           loading: false 
         });
+      } else {
+        set({ 
+          error: describeBackendError(result.Err),
+          loading: false 
+        });
       }
     } catch (error) {
       console.error('Fetch profile failed:', error);
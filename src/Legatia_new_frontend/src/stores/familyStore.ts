@@ -1,19 +1,25 @@
 import { create } from 'zustand';
-import { Family, FamilyMember, FamilyEvent, CreateFamilyRequest, AddFamilyMemberRequest, AddEventRequest, UpdateFamilyMemberRequest, UpdateEventRequest } from '../types';
+import { Family, CreateFamilyRequest, AddFamilyMemberRequest, AddEventRequest, UpdateFamilyMemberRequest, UpdateEventRequest } from '../types';
 import { useAuthStore } from './authStore';
+import { describeBackendError, isMissingProfile } from '../lib/errors';
+import { fetchAllPages, memberEventsFilter, nextCursor, pageRequest } from '../lib/pagination';
 
 interface FamilyState {
   // State
   families: Family[];
+  // Cursor of the next page of families; null once every page is loaded
+  familiesCursor: string | null;
   currentFamily: Family | null;
   loading: boolean;
   error: string | null;
 
   // Actions
   fetchFamilies: () => Promise<void>;
+  fetchMoreFamilies: () => Promise<void>;
   createFamily: (data: CreateFamilyRequest) => Promise<void>;
   selectFamily: (familyId: string) => void;
   fetchFamily: (familyId: string) => Promise<void>;
+  fetchMemberEvents: (familyId: string, memberId: string) => Promise<void>;
   addFamilyMember: (data: AddFamilyMemberRequest) => Promise<void>;
   removeFamilyMember: (familyId: string, memberId: string) => Promise<void>;
   updateFamilyMember: (data: UpdateFamilyMemberRequest) => Promise<void>;
@@ -27,11 +33,12 @@ interface FamilyState {
 export const useFamilyStore = create<FamilyState>((set, get) => ({
   // Initial state
   families: [],
+  familiesCursor: null,
   currentFamily: null,
   loading: false,
   error: null,
 
-  // Fetch user's families
+  // Fetch the first page of the user's families
   fetchFamilies: async () => {
     const actor = useAuthStore.getState().actor;
     if (!actor) {
@@ -41,16 +48,17 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
 
     set({ loading: true, error: null });
     try {
-      const result = await actor.get_user_families();
+      const result = await actor.get_user_families(pageRequest());
       
       if ('Ok' in result) {
         set({ 
-          families: result.Ok,
+          families: result.Ok.items,
+          familiesCursor: nextCursor(result.Ok),
           loading: false 
         });
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -63,6 +71,38 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
     }
   },
 
+  // Append the next page of the user's families
+  fetchMoreFamilies: async () => {
+    const actor = useAuthStore.getState().actor;
+    const { familiesCursor } = get();
+    if (!actor || familiesCursor === null) return;
+
+    set({ loading: true, error: null });
+    try {
+      const result = await actor.get_user_families(pageRequest(familiesCursor));
+      
+      if ('Ok' in result) {
+        const page = result.Ok;
+        set((state) => ({ 
+          families: [...state.families, ...page.items],
+          familiesCursor: nextCursor(page),
+          loading: false 
+        }));
+      } else {
+        set({ 
+          error: describeBackendError(result.Err),
+          loading: false 
+        });
+      }
+    } catch (error) {
+      console.error('Fetch more families failed:', error);
+      set({ 
+        error: error instanceof Error ? error.message : 'Failed to fetch families',
+        loading: false 
+      });
+    }
+  },
+
   // Create new family
   createFamily: async (data: CreateFamilyRequest) => {
     const actor = useAuthStore.getState().actor;
@@ -83,11 +123,10 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
           loading: false 
         }));
       } else {
-        let errorMessage = result.Err;
-        // Check for specific backend error about needing a profile
-        if (result.Err.includes('must create a user profile')) {
-          errorMessage = 'You must create a user profile before creating a family. Please complete your profile first.';
-        }
+        // The backend reports a missing profile as a NotFound error for the caller's profile
+        const errorMessage = isMissingProfile(result.Err)
+          ? 'You must create a user profile before creating a family. Please complete your profile first.'
+          : describeBackendError(result.Err);
         set({ 
           error: errorMessage,
           loading: false 
@@ -95,16 +134,8 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
       }
     } catch (error) {
       console.error('Create family failed:', error);
-      let errorMessage = 'Failed to create family';
-      if (error instanceof Error) {
-        errorMessage = error.message;
-        // Check for specific backend error about needing a profile
-        if (error.message.includes('must create a user profile')) {
-          errorMessage = 'You must create a user profile before creating a family. Please complete your profile first.';
-        }
-      }
       set({ 
-        error: errorMessage,
+        error: error instanceof Error ? error.message : 'Failed to create family',
         loading: false 
       });
     }
@@ -119,7 +150,8 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
     }
   },
 
-  // Fetch specific family details
+  // Fetch a family with all of its members; their events are fetched per member
+  // with fetchMemberEvents
   fetchFamily: async (familyId: string) => {
     const actor = useAuthStore.getState().actor;
     if (!actor) {
@@ -130,17 +162,26 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
     set({ loading: true, error: null });
     try {
       const result = await actor.get_family(familyId);
+      if ('Err' in result) {
+        set({ 
+          error: describeBackendError(result.Err),
+          loading: false 
+        });
+        return;
+      }
+
+      const members = await fetchAllPages((page) => actor.get_family_members(familyId, page));
       
-      if ('Ok' in result) {
+      if ('Ok' in members) {
         const family = result.Ok;
         set((state) => ({
-          currentFamily: family,
+          currentFamily: { ...family, members: members.Ok },
           families: state.families.map(f => f.id === familyId ? family : f),
           loading: false
         }));
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(members.Err),
           loading: false 
         });
       }
@@ -153,6 +194,46 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
     }
   },
 
+  // Fetch every event of one member of the current family
+  fetchMemberEvents: async (familyId: string, memberId: string) => {
+    const actor = useAuthStore.getState().actor;
+    if (!actor) {
+      set({ error: 'Not authenticated' });
+      return;
+    }
+
+    set({ loading: true, error: null });
+    try {
+      const result = await fetchAllPages((page) =>
+        actor.get_family_events(familyId, memberEventsFilter(memberId), page)
+      );
+      
+      if ('Ok' in result) {
+        const events = result.Ok;
+        set((state) => ({
+          currentFamily: state.currentFamily?.id === familyId
+            ? {
+                ...state.currentFamily,
+                members: state.currentFamily.members.map(m => m.id === memberId ? { ...m, events } : m),
+              }
+            : state.currentFamily,
+          loading: false
+        }));
+      } else {
+        set({ 
+          error: describeBackendError(result.Err),
+          loading: false 
+        });
+      }
+    } catch (error) {
+      console.error('Fetch member events failed:', error);
+      set({ 
+        error: error instanceof Error ? error.message : 'Failed to fetch events',
+        loading: false 
+      });
+    }
+  },
+
   // Add family member
   addFamilyMember: async (data: AddFamilyMemberRequest) => {
     const actor = useAuthStore.getState().actor;
@@ -170,7 +251,7 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
         await get().fetchFamily(data.family_id);
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -200,7 +281,7 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
         await get().fetchFamily(familyId);
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -226,11 +307,11 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
       const result = await actor.add_member_event(data);
       
       if ('Ok' in result) {
-        // Refresh the current family to show new event
-        await get().fetchFamily(data.family_id);
+        // Refresh the member's events to show the new event
+        await get().fetchMemberEvents(data.family_id, data.member_id);
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -260,7 +341,7 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
         await get().fetchFamily(data.family_id);
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -286,11 +367,11 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
       const result = await actor.update_member_event(data);
       
       if ('Ok' in result) {
-        // Refresh the current family to show updated event
-        await get().fetchFamily(data.family_id);
+        // Refresh the member's events to show the updated event
+        await get().fetchMemberEvents(data.family_id, data.member_id);
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -328,7 +409,7 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
         }));
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -345,5 +426,5 @@ export const useFamilyStore = create<FamilyState>((set, get) => ({
   clearError: () => set({ error: null }),
 
   // Reset store
-  reset: () => set({ families: [], currentFamily: null, loading: false, error: null }),
+  reset: () => set({ families: [], familiesCursor: null, currentFamily: null, loading: false, error: null }),
 }));
\ No newline at end of file
@@ -1,16 +1,21 @@
 import { create } from 'zustand';
 import { Notification } from '../types';
 import { useAuthStore } from './authStore';
+import { describeBackendError } from '../lib/errors';
+import { nextCursor, noNotificationFilter, pageRequest } from '../lib/pagination';
 
 interface NotificationState {
   // State
   notifications: Notification[];
+  // Cursor of the next page of notifications; null once every page is loaded
+  notificationsCursor: string | null;
   unreadCount: number;
   loading: boolean;
   error: string | null;
 
   // Actions
   fetchNotifications: () => Promise<void>;
+  fetchMoreNotifications: () => Promise<void>;
   fetchUnreadCount: () => Promise<void>;
   markAsRead: (notificationId: string) => Promise<void>;
   markAllAsRead: () => Promise<void>;
@@ -21,11 +26,12 @@ interface NotificationState {
 export const useNotificationStore = create<NotificationState>((set, get) => ({
   // Initial state
   notifications: [],
+  notificationsCursor: null,
   unreadCount: 0,
   loading: false,
   error: null,
 
-  // Fetch notifications
+  // Fetch the first page of notifications, newest first
   fetchNotifications: async () => {
     const actor = useAuthStore.getState().actor;
     if (!actor) {
@@ -35,20 +41,19 @@ export const useNotificationStore = create<NotificationState>((set, get) => ({
 
     set({ loading: true, error: null });
     try {
-      const result = await actor.get_my_notifications();
+      const result = await actor.get_my_notifications(noNotificationFilter, pageRequest());
       
       if ('Ok' in result) {
-        const notifications = result.Ok;
-        const unreadCount = notifications.filter(n => !n.read).length;
-        
         set({ 
-          notifications,
-          unreadCount,
+          notifications: result.Ok.items,
+          notificationsCursor: nextCursor(result.Ok),
           loading: false 
         });
+        // A page only holds some of the notifications, so the unread count comes from the backend
+        await get().fetchUnreadCount();
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -61,6 +66,38 @@ export const useNotificationStore = create<NotificationState>((set, get) => ({
     }
   },
 
+  // Append the next page of notifications
+  fetchMoreNotifications: async () => {
+    const actor = useAuthStore.getState().actor;
+    const { notificationsCursor } = get();
+    if (!actor || notificationsCursor === null) return;
+
+    set({ loading: true, error: null });
+    try {
+      const result = await actor.get_my_notifications(noNotificationFilter, pageRequest(notificationsCursor));
+      
+      if ('Ok' in result) {
+        const page = result.Ok;
+        set((state) => ({ 
+          notifications: [...state.notifications, ...page.items],
+          notificationsCursor: nextCursor(page),
+          loading: false 
+        }));
+      } else {
+        set({ 
+          error: describeBackendError(result.Err),
+          loading: false 
+        });
+      }
+    } catch (error) {
+      console.error('Fetch more notifications failed:', error);
+      set({ 
+        error: error instanceof Error ? error.message : 'Failed to fetch notifications',
+        loading: false 
+      });
+    }
+  },
+
   // Fetch unread count
   fetchUnreadCount: async () => {
     const actor = useAuthStore.getState().actor;
@@ -96,7 +133,7 @@ export const useNotificationStore = create<NotificationState>((set, get) => ({
           unreadCount: Math.max(0, state.unreadCount - 1)
         }));
       } else {
-        set({ error: result.Err });
+        set({ error: describeBackendError(result.Err) });
       }
     } catch (error) {
       console.error('Mark as read failed:', error);
@@ -126,7 +163,7 @@ export const useNotificationStore = create<NotificationState>((set, get) => ({
         }));
       } else {
         set({ 
-          error: result.Err,
+          error: describeBackendError(result.Err),
           loading: false 
         });
       }
@@ -143,5 +180,5 @@ export const useNotificationStore = create<NotificationState>((set, get) => ({
   clearError: () => set({ error: null }),
 
   // Reset store
-  reset: () => set({ notifications: [], unreadCount: 0, loading: false, error: null }),
+  reset: () => set({ notifications: [], notificationsCursor: null, unreadCount: 0, loading: false, error: null }),
 }));
\ No newline at end of file
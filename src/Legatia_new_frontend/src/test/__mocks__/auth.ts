@@ -24,7 +24,7 @@ export const mockBackendActor: Partial<BackendActor> = {
   get_profile: vi.fn().mockResolvedValue({ Ok: mockUserProfile }),
   create_profile: vi.fn().mockResolvedValue({ Ok: mockUserProfile }),
   update_profile: vi.fn().mockResolvedValue({ Ok: mockUserProfile }),
-  get_user_families: vi.fn().mockResolvedValue({ Ok: [] }),
+  get_user_families: vi.fn().mockResolvedValue({ Ok: { items: [], next_cursor: [] } }),
   create_family: vi.fn().mockResolvedValue({ 
     Ok: {
       id: 'test-family-id',
@@ -37,7 +37,9 @@ export const mockBackendActor: Partial<BackendActor> = {
       updated_at: BigInt(Date.now() * 1000000),
     }
   }),
-  get_my_notifications: vi.fn().mockResolvedValue({ Ok: [] }),
+  get_family_members: vi.fn().mockResolvedValue({ Ok: { items: [], next_cursor: [] } }),
+  get_family_events: vi.fn().mockResolvedValue({ Ok: { items: [], next_cursor: [] } }),
+  get_my_notifications: vi.fn().mockResolvedValue({ Ok: { items: [], next_cursor: [] } }),
   get_unread_notification_count: vi.fn().mockResolvedValue({ Ok: BigInt(0) }),
 }
 
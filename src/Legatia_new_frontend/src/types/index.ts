@@ -52,11 +52,14 @@ export interface FamilyMember {
   birth_country?: [string] | [];
   death_date?: [string] | [];
   relationship_to_admin: string;
+  // Empty in get_family_members pages; page events with get_family_events
   events: FamilyEvent[];
   created_at: bigint;
   created_by: Principal;
 }
 
+// get_user_families and get_family return families without members; page those
+// with get_family_members
 export interface Family {
   id: string;
   name: string;
@@ -118,13 +121,51 @@ export interface UpdateEventRequest {
   event_type?: [string] | [];
 }
 
-export type ProfileResult = { Ok: UserProfile } | { Err: string };
-export type FamilyResult = { Ok: Family } | { Err: string };
-export type FamilyListResult = { Ok: Family[] } | { Err: string };
-export type FamilyMemberResult = { Ok: FamilyMember } | { Err: string };
-export type FamilyEventResult = { Ok: FamilyEvent } | { Err: string };
-export type FamilyEventsResult = { Ok: FamilyEvent[] } | { Err: string };
-export type StringResult = { Ok: string } | { Err: string };
+export type FamilyRole =
+  | { Owner: null }
+  | { CoAdmin: null }
+  | { Editor: null }
+  | { Contributor: null }
+  | { Viewer: null };
+
+// The error returned by every backend call
+export type BackendError =
+  | { Unauthenticated: null }
+  | { NotFound: { kind: string; id: string } }
+  | { Unauthorized: { required_role: [FamilyRole] | [] } }
+  | { Validation: { field: string; reason: string } }
+  | { Malformed: { field: string; line: bigint; reason: string } }
+  | { Conflict: { reason: string } }
+  | { RateLimited: { retry_after_secs: bigint } }
+  | { ExternalCall: { reason: string } };
+
+export type ProfileResult = { Ok: UserProfile } | { Err: BackendError };
+export type FamilyResult = { Ok: Family } | { Err: BackendError };
+export type FamilyMemberResult = { Ok: FamilyMember } | { Err: BackendError };
+export type FamilyEventResult = { Ok: FamilyEvent } | { Err: BackendError };
+export type FamilyEventsResult = { Ok: FamilyEvent[] } | { Err: BackendError };
+export type StringResult = { Ok: string } | { Err: BackendError };
+
+// Pagination Types
+// `cursor` is the `next_cursor` of the previous page; a page without one is the last
+export interface PageRequest {
+  cursor: [string] | [];
+  limit: [number] | [];
+}
+
+export interface Page<T> {
+  items: T[];
+  next_cursor: [string] | [];
+}
+
+export type PageResult<T> = { Ok: Page<T> } | { Err: BackendError };
+
+export interface EventFilter {
+  member_id: [string] | [];
+  event_type: [string] | [];
+  created_after: [bigint] | [];
+  created_before: [bigint] | [];
+}
 
 // Ghost Profile Claiming System Types
 export interface GhostProfileMatch {
@@ -158,10 +199,16 @@ export interface ProcessClaimRequest {
   admin_message?: [string] | [];
 }
 
-export type GhostProfileMatchResult = { Ok: GhostProfileMatch[] } | { Err: string };
-export type ClaimRequestResult = { Ok: ClaimRequest } | { Err: string };
-export type ClaimRequestsResult = { Ok: ClaimRequest[] } | { Err: string };
-export type ProfileWithGhostResult = { Ok: [UserProfile, GhostProfileMatch[]] } | { Err: string };
+export type GhostProfileMatchResult = { Ok: GhostProfileMatch[] } | { Err: BackendError };
+export type ClaimRequestResult = { Ok: ClaimRequest } | { Err: BackendError };
+
+export interface ClaimFilter {
+  status: [ClaimStatus] | [];
+  family_id: [string] | [];
+  created_after: [bigint] | [];
+  created_before: [bigint] | [];
+}
+export type ProfileWithGhostResult = { Ok: [UserProfile, GhostProfileMatch[]] } | { Err: BackendError };
 
 export interface BackendActor {
   create_profile: (request: CreateProfileRequest) => Promise<ProfileResult>;
@@ -172,8 +219,10 @@ export interface BackendActor {
   whoami: () => Promise<Principal>;
   
   create_family: (request: CreateFamilyRequest) => Promise<FamilyResult>;
-  get_user_families: () => Promise<FamilyListResult>;
+  get_user_families: (page: PageRequest) => Promise<PageResult<Family>>;
   get_family: (family_id: string) => Promise<FamilyResult>;
+  get_family_members: (family_id: string, page: PageRequest) => Promise<PageResult<FamilyMember>>;
+  get_family_events: (family_id: string, filter: EventFilter, page: PageRequest) => Promise<PageResult<FamilyEvent>>;
   
   add_family_member: (request: AddFamilyMemberRequest) => Promise<FamilyMemberResult>;
   remove_family_member: (family_id: string, member_id: string) => Promise<StringResult>;
@@ -187,20 +236,20 @@ export interface BackendActor {
   
   find_matching_ghost_profiles: () => Promise<GhostProfileMatchResult>;
   submit_ghost_profile_claim: (family_id: string, member_id: string) => Promise<ClaimRequestResult>;
-  get_pending_claims_for_admin: () => Promise<ClaimRequestsResult>;
+  get_pending_claims_for_admin: (filter: ClaimFilter, page: PageRequest) => Promise<PageResult<ClaimRequest>>;
   process_ghost_profile_claim: (request: ProcessClaimRequest) => Promise<StringResult>;
-  get_my_claim_requests: () => Promise<ClaimRequestsResult>;
+  get_my_claim_requests: (filter: ClaimFilter, page: PageRequest) => Promise<PageResult<ClaimRequest>>;
   
   // User search and invitation functions
   search_users: (query: string) => Promise<UserSearchResult>;
   send_family_invitation: (request: SendInvitationRequest) => Promise<StringResult>;
   process_family_invitation: (request: ProcessInvitationRequest) => Promise<StringResult>;
-  get_my_invitations: () => Promise<FamilyInvitationsResult>;
-  get_sent_invitations: () => Promise<FamilyInvitationsResult>;
+  get_my_invitations: (filter: InvitationFilter, page: PageRequest) => Promise<PageResult<FamilyInvitation>>;
+  get_sent_invitations: (filter: InvitationFilter, page: PageRequest) => Promise<PageResult<FamilyInvitation>>;
   
   // Notification functions
-  get_my_notifications: () => Promise<NotificationsResult>;
-  get_unread_notification_count: () => Promise<{ Ok: bigint } | { Err: string }>;
+  get_my_notifications: (filter: NotificationFilter, page: PageRequest) => Promise<PageResult<Notification>>;
+  get_unread_notification_count: () => Promise<{ Ok: bigint } | { Err: BackendError }>;
   mark_notification_read: (notification_id: string) => Promise<StringResult>;
   mark_all_notifications_read: () => Promise<StringResult>;
 }
@@ -213,7 +262,7 @@ export interface UserSearchMatch {
   user_principal: Principal;
 }
 
-export type UserSearchResult = { Ok: UserSearchMatch[] } | { Err: string };
+export type UserSearchResult = { Ok: UserSearchMatch[] } | { Err: BackendError };
 
 // Family Invitation Types
 export interface FamilyInvitation {
@@ -244,7 +293,12 @@ export interface ProcessInvitationRequest {
   accept: boolean;
 }
 
-export type FamilyInvitationsResult = { Ok: FamilyInvitation[] } | { Err: string };
+export interface InvitationFilter {
+  status: [InvitationStatus] | [];
+  family_id: [string] | [];
+  created_after: [bigint] | [];
+  created_before: [bigint] | [];
+}
 
 // Notification Types
 export interface Notification {
@@ -261,7 +315,12 @@ export interface Notification {
 
 export type NotificationType = { FamilyInvitation: null } | { GhostProfileClaim: null } | { FamilyUpdate: null } | { SystemAlert: null };
 
-export type NotificationsResult = { Ok: Notification[] } | { Err: string };
+export interface NotificationFilter {
+  notification_type: [NotificationType] | [];
+  unread_only: [boolean] | [];
+  created_after: [bigint] | [];
+  created_before: [bigint] | [];
+}
 
 // React-specific types
 export type ViewType = 'loading' | 'login' | 'create-profile' | 'profile' | 'edit-profile' | 'families' | 'family-detail' | 'create-family' | 'add-member' | 'add-event' | 'ghost-matches' | 'claim-requests' | 'admin-claims' | 'user-search' | 'send-invitation' | 'my-invitations' | 'notifications' | 'error';
\ No newline at end of file